@@ -0,0 +1,67 @@
+use anyhow::{Result, bail};
+use nmcr_md_parser::ParsedMarkdown;
+use nmcr_types::{Template, TemplateFile};
+use nmcr_types_internal::FormattedLocation;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Point asset references at files next to the markdown file that declares them.
+pub(crate) fn resolve_assets(parsed: &mut ParsedMarkdown, source: &Path) -> Result<()> {
+    let dir = source.parent().unwrap_or(Path::new(""));
+    match parsed {
+        ParsedMarkdown::Template(template) => resolve_template(template, dir),
+        ParsedMarkdown::Tree(tree) => resolve_templates(&mut tree.files, dir),
+        ParsedMarkdown::Collection(collection) => resolve_templates(&mut collection.templates, dir),
+        ParsedMarkdown::Partials(_) => Ok(()),
+    }
+}
+
+fn resolve_templates(templates: &mut [Template], dir: &Path) -> Result<()> {
+    for template in templates {
+        resolve_template(template, dir)?;
+    }
+    Ok(())
+}
+
+fn resolve_template(template: &mut Template, dir: &Path) -> Result<()> {
+    match template {
+        Template::TemplateFile(file) => resolve_file(file, dir),
+        Template::TemplateTree(tree) => resolve_templates(&mut tree.files, dir),
+    }
+}
+
+fn resolve_file(file: &mut TemplateFile, dir: &Path) -> Result<()> {
+    let Some(asset) = &file.asset else {
+        return Ok(());
+    };
+    let relative: PathBuf = Path::new(asset)
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect();
+    let resolved = dir.join(relative);
+    if !resolved.is_file() {
+        bail!(
+            "Asset '{}' referenced by template '{}' at {} not found",
+            asset,
+            file.id,
+            FormattedLocation(&file.location)
+        );
+    }
+    // Symlinks and `..` may point anywhere, so compare the real paths
+    let root = fs::canonicalize(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })?;
+    if !fs::canonicalize(&resolved)?.starts_with(&root) {
+        bail!(
+            "Asset '{}' referenced by template '{}' at {} is outside {}",
+            asset,
+            file.id,
+            FormattedLocation(&file.location),
+            root.display()
+        );
+    }
+    file.asset = Some(resolved.to_string_lossy().into_owned());
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use anyhow::{Result, bail};
+use nmcr_id::EntityId;
+use nmcr_types::{TemplateFile, TemplateInclude};
+use nmcr_types_internal::FormattedLocation;
+
+use crate::{CatalogTree, FileRef, TemplateCatalog};
+
+impl TemplateCatalog {
+    /// Append the files of every tree include to the including tree, following includes of
+    /// included trees and rejecting cycles.
+    pub(crate) fn resolve_includes(&mut self) -> Result<()> {
+        let mut resolved: Vec<(Vec<usize>, Vec<TemplateFile>)> = Vec::new();
+        for (idx, tree) in self.trees.iter().enumerate() {
+            self.collect_resolved(tree, vec![idx], &mut resolved)?;
+        }
+        for (position, files) in resolved {
+            let (root, rest) = position
+                .split_first()
+                .expect("tree position is never empty");
+            let mut tree = &mut self.trees[*root];
+            for idx in rest {
+                tree = &mut tree.subtrees[*idx];
+            }
+            tree.files.extend(files);
+        }
+        Ok(())
+    }
+
+    fn collect_resolved(
+        &self,
+        tree: &CatalogTree,
+        position: Vec<usize>,
+        resolved: &mut Vec<(Vec<usize>, Vec<TemplateFile>)>,
+    ) -> Result<()> {
+        if !tree.includes.is_empty() {
+            let mut stack = vec![tree.id()];
+            resolved.push((position.clone(), self.included_files(tree, &mut stack)?));
+        }
+        for (idx, subtree) in tree.subtrees.iter().enumerate() {
+            let mut nested = position.clone();
+            nested.push(idx);
+            self.collect_resolved(subtree, nested, resolved)?;
+        }
+        Ok(())
+    }
+
+    fn included_files<'a>(
+        &'a self,
+        tree: &'a CatalogTree,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<Vec<TemplateFile>> {
+        let mut files = Vec::new();
+        for include in &tree.includes {
+            let id = self.include_target(tree, include);
+            let included = if let Some(file) = self.get_file(&id)? {
+                match file {
+                    FileRef::Standalone(file) | FileRef::TreeMember { file, .. } => {
+                        vec![file.clone()]
+                    }
+                }
+            } else if let Some(target) = self.get_tree(&id)? {
+                self.expanded_files(target, include, stack)?
+            } else {
+                bail!(
+                    "Unknown template '{}' included by tree '{}' at {}",
+                    include.id,
+                    tree.id(),
+                    FormattedLocation(&include.location)
+                );
+            };
+            files.extend(apply_include(include, included)?);
+        }
+        Ok(files)
+    }
+
+    /// Id an include refers to: templates of the including tree's namespaces come first,
+    /// innermost first.
+    fn include_target(&self, tree: &CatalogTree, include: &TemplateInclude) -> String {
+        EntityId::namespaces(tree.id())
+            .map(|namespace| EntityId::qualify(namespace, &include.id))
+            .find(|qualified| self.index.contains_key(qualified))
+            .unwrap_or_else(|| include.id.clone())
+    }
+
+    /// Files of the tree at every level, with the includes of each level resolved.
+    fn expanded_files<'a>(
+        &'a self,
+        tree: &'a CatalogTree,
+        include: &TemplateInclude,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<Vec<TemplateFile>> {
+        if stack.contains(&tree.id()) {
+            let start = stack.iter().position(|id| *id == tree.id()).unwrap_or(0);
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(tree.id());
+            bail!(
+                "Tree includes form a cycle: {} at {}",
+                cycle.join(" -> "),
+                FormattedLocation(&include.location)
+            );
+        }
+
+        stack.push(tree.id());
+        let mut files = tree.files.clone();
+        files.extend(self.included_files(tree, stack)?);
+        for subtree in &tree.subtrees {
+            files.extend(self.expanded_files(subtree, include, stack)?);
+        }
+        stack.pop();
+        Ok(files)
+    }
+}
+
+/// Move the included files under the include path and rename their args.
+fn apply_include(
+    include: &TemplateInclude,
+    mut files: Vec<TemplateFile>,
+) -> Result<Vec<TemplateFile>> {
+    for name in include.args.keys() {
+        if !files
+            .iter()
+            .any(|file| file.args.iter().any(|arg| &arg.name == name))
+        {
+            bail!(
+                "Include of '{}' maps unknown arg '{}' at {}",
+                include.id,
+                name,
+                FormattedLocation(&include.location)
+            );
+        }
+    }
+
+    for file in files.iter_mut() {
+        let Some(path) = file.path.as_deref() else {
+            bail!(
+                "Included template '{}' has no path at {}",
+                file.id,
+                FormattedLocation(&include.location)
+            );
+        };
+        if let Some(prefix) = include.path.as_deref() {
+            file.path = Some(join_path(prefix, path));
+        }
+
+        for (name, from) in &include.args {
+            let Some(idx) = file.args.iter().position(|arg| &arg.name == name) else {
+                continue;
+            };
+            let mut source = file.args[idx].clone();
+            if source.derived.is_some() {
+                bail!(
+                    "Include of '{}' maps derived arg '{}' at {}",
+                    include.id,
+                    name,
+                    FormattedLocation(&include.location)
+                );
+            }
+            // The original arg becomes an alias of the tree arg, so the content is untouched.
+            file.args[idx].derived = Some(format!("{{{{{from}}}}}"));
+            file.args[idx].required = false;
+            if !file.args.iter().any(|arg| &arg.name == from) {
+                source.name = from.clone();
+                file.args.push(source);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() || prefix == "." {
+        return path.to_string();
+    }
+    format!("{prefix}/{}", path.trim_start_matches("./"))
+}
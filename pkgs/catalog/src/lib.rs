@@ -1,16 +1,30 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
+use nmcr_id::{EntityId, NAMESPACE_SEPARATOR};
 use nmcr_md_parser::ParsedMarkdown;
-use nmcr_md_parser::prelude::parse_file;
-use nmcr_types::{Location, Template, TemplateFile, TemplateTree};
-use std::collections::{HashMap, HashSet};
+use nmcr_md_parser::prelude::parse_file_with_helpers;
+use nmcr_template::{RendererOptions, TemplateRenderer, script_helper_name};
+use nmcr_types::{
+    Arg, ArgKind, ArgKindAny, Location, Template, TemplateFile, TemplateInclude, TemplatePartial,
+    TemplateTree,
+};
+use nmcr_types_internal::{TemplateLayer, TemplateSource};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
+mod assets;
+mod includes;
+mod partials;
+use partials::PartialSet;
+
 #[derive(Debug, Clone)]
 pub struct CatalogTree {
     id: String,
     name: String,
     description: String,
     files: Vec<TemplateFile>,
+    subtrees: Vec<CatalogTree>,
+    includes: Vec<TemplateInclude>,
+    hooks: Vec<String>,
     location: Location,
 }
 
@@ -27,10 +41,63 @@ impl CatalogTree {
         &self.description
     }
 
+    /// Files declared directly in this tree or pulled in by its includes, without those of
+    /// nested subtrees.
     pub fn files(&self) -> &[TemplateFile] {
         &self.files
     }
 
+    pub fn subtrees(&self) -> &[CatalogTree] {
+        &self.subtrees
+    }
+
+    /// Files of this tree followed by those of every nested subtree, depth first.
+    pub fn all_files(&self) -> Vec<&TemplateFile> {
+        let mut files: Vec<&TemplateFile> = self.files.iter().collect();
+        for subtree in &self.subtrees {
+            files.extend(subtree.all_files());
+        }
+        files
+    }
+
+    /// This tree followed by every nested subtree, depth first.
+    pub fn descendants(&self) -> Vec<&CatalogTree> {
+        let mut trees = vec![self];
+        for subtree in &self.subtrees {
+            trees.extend(subtree.descendants());
+        }
+        trees
+    }
+
+    /// Post-generation commands declared on this tree, without those of its subtrees.
+    pub fn hooks(&self) -> &[String] {
+        &self.hooks
+    }
+
+    fn subtree_at(&self, position: &[usize]) -> Option<&CatalogTree> {
+        match position.split_first() {
+            Some((idx, rest)) => self.subtrees.get(*idx)?.subtree_at(rest),
+            None => Some(self),
+        }
+    }
+
+    fn subtree_at_mut(&mut self, position: &[usize]) -> Option<&mut CatalogTree> {
+        match position.split_first() {
+            Some((idx, rest)) => self.subtrees.get_mut(*idx)?.subtree_at_mut(rest),
+            None => Some(self),
+        }
+    }
+
+    /// Ids of this tree, its members and every nested subtree.
+    fn ids(&self) -> Vec<String> {
+        let mut ids = vec![self.id.clone()];
+        ids.extend(self.files.iter().map(|file| file.id.clone()));
+        for subtree in &self.subtrees {
+            ids.extend(subtree.ids());
+        }
+        ids
+    }
+
     pub fn location(&self) -> &Location {
         &self.location
     }
@@ -40,23 +107,81 @@ impl CatalogTree {
 pub struct TemplateCatalog {
     files: Vec<TemplateFile>,
     trees: Vec<CatalogTree>,
+    partials: PartialSet,
     index: HashMap<String, TemplateRef>,
+    origins: HashMap<String, TemplateOrigin>,
 }
 
-#[derive(Debug)]
+/// The layer a template, tree or tree member was loaded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateOrigin {
+    pub layer: TemplateLayer,
+    /// Layer of the template with the same id this one replaced.
+    pub overrides: Option<TemplateLayer>,
+}
+
+#[derive(Debug, Clone)]
 enum TemplateRef {
     File(usize),
-    Tree(usize),
-    TreeFile { tree: usize, file: usize },
+    /// Position of the tree: the root tree index followed by subtree indices.
+    Tree(Vec<usize>),
+    TreeFile {
+        tree: Vec<usize>,
+        file: usize,
+    },
 }
 
 impl TemplateCatalog {
     pub fn load(paths: &[PathBuf]) -> Result<Self> {
-        let mut builder = CatalogBuilder::default();
-        for path in paths {
-            builder.ingest(path)?;
+        Self::load_sources(&[TemplateSource {
+            layer: TemplateLayer::Local,
+            namespace: None,
+            paths: paths.to_vec(),
+            out: None,
+        }])
+    }
+
+    /// Load template files by source, qualifying the ids of each source with its namespace.
+    /// Templates and tree members from a higher layer replace those with the same id from
+    /// lower layers, while duplicate ids within a layer still fail.
+    pub fn load_sources(sources: &[TemplateSource]) -> Result<Self> {
+        Self::load_sources_with_helpers(sources, &[])
+    }
+
+    /// Load template files like [`Self::load_sources`] for a renderer with the given helper
+    /// scripts, so templates calling one of them without params don't take it as an arg.
+    pub fn load_sources_with_helpers(
+        sources: &[TemplateSource],
+        helper_scripts: &[PathBuf],
+    ) -> Result<Self> {
+        let mut sources: Vec<&TemplateSource> = sources.iter().collect();
+        sources.sort_by_key(|source| source.layer);
+
+        let mut builder = CatalogBuilder {
+            helpers: helper_scripts
+                .iter()
+                .filter_map(|script| script_helper_name(script))
+                .collect(),
+            ..Default::default()
+        };
+        for source in sources {
+            let namespace = source.namespace.as_deref();
+            // Workspaces nest source namespaces under the member's, as in `web::acme`
+            if let Some(namespace) = namespace
+                && namespace
+                    .split(NAMESPACE_SEPARATOR)
+                    .any(|segment| EntityId::normalize_segment(segment) != segment)
+            {
+                bail!(
+                    "Invalid namespace '{namespace}': expected a snake_case name such as `company`"
+                );
+            }
+            builder.layer = source.layer;
+            for path in &source.paths {
+                builder.ingest(path, namespace)?;
+            }
         }
-        Ok(builder.finish())
+        builder.finish()
     }
 
     pub fn is_empty(&self) -> bool {
@@ -67,10 +192,48 @@ impl TemplateCatalog {
         &self.files
     }
 
+    /// Root trees; nested subtrees are reachable through [`CatalogTree::subtrees`].
     pub fn tree_templates(&self) -> &[CatalogTree] {
         &self.trees
     }
 
+    /// Every tree including nested subtrees, depth first.
+    pub fn all_trees(&self) -> Vec<&CatalogTree> {
+        self.trees
+            .iter()
+            .flat_map(CatalogTree::descendants)
+            .collect()
+    }
+
+    pub fn partials(&self) -> impl Iterator<Item = &TemplatePartial> {
+        self.partials.iter()
+    }
+
+    /// Build a renderer with every catalog partial and the given helper scripts registered.
+    pub fn renderer(&self, helper_scripts: &[PathBuf]) -> Result<TemplateRenderer> {
+        self.renderer_with_options(RendererOptions::default(), helper_scripts)
+    }
+
+    pub fn renderer_with_options(
+        &self,
+        options: RendererOptions,
+        helper_scripts: &[PathBuf],
+    ) -> Result<TemplateRenderer> {
+        let mut renderer = TemplateRenderer::with_options(options);
+        for partial in self.partials.iter() {
+            renderer.register_partial(&partial.name, &partial.content)?;
+        }
+        for script in helper_scripts {
+            renderer.register_script_helper_file(script)?;
+        }
+        Ok(renderer)
+    }
+
+    /// The layer the template, tree or tree member with this id was loaded from.
+    pub fn origin(&self, id: &str) -> Option<TemplateOrigin> {
+        self.origins.get(id).copied()
+    }
+
     pub fn instructions(&self) -> Option<String> {
         if self.is_empty() {
             None
@@ -79,47 +242,107 @@ impl TemplateCatalog {
         }
     }
 
-    pub fn get_tree(&self, id: &str) -> Option<&CatalogTree> {
-        match self.index.get(id) {
-            Some(TemplateRef::Tree(idx)) => self.trees.get(*idx),
+    /// Find a tree by id. An id without a namespace also matches a namespaced tree when
+    /// only one namespace declares it, and fails listing the candidates otherwise.
+    pub fn get_tree(&self, id: &str) -> Result<Option<&CatalogTree>> {
+        let found = self.lookup(id, |entry| matches!(entry, TemplateRef::Tree(_)))?;
+        Ok(match found {
+            Some(TemplateRef::Tree(position)) => self.tree_at(position),
             _ => None,
-        }
+        })
     }
 
-    pub fn get_file(&self, id: &str) -> Option<FileRef<'_>> {
-        match self.index.get(id) {
+    /// Find a file by id, resolving ids without a namespace like [`Self::get_tree`].
+    pub fn get_file(&self, id: &str) -> Result<Option<FileRef<'_>>> {
+        let found = self.lookup(id, |entry| !matches!(entry, TemplateRef::Tree(_)))?;
+        Ok(match found {
             Some(TemplateRef::File(idx)) => self.files.get(*idx).map(FileRef::Standalone),
-            Some(TemplateRef::TreeFile { tree, file }) => self.trees.get(*tree).and_then(|tree| {
+            Some(TemplateRef::TreeFile { tree, file }) => self.tree_at(tree).and_then(|tree| {
                 tree.files()
                     .get(*file)
                     .map(|f| FileRef::TreeMember { tree, file: f })
             }),
             _ => None,
+        })
+    }
+
+    fn lookup(&self, id: &str, kind: fn(&TemplateRef) -> bool) -> Result<Option<&TemplateRef>> {
+        if let Some(entry) = self.index.get(id).filter(|entry| kind(entry)) {
+            return Ok(Some(entry));
+        }
+        let mut candidates: Vec<&String> = self
+            .index
+            .iter()
+            .filter(|(key, entry)| kind(entry) && *key != id && EntityId::matches(key, id))
+            .map(|(key, _)| key)
+            .collect();
+        candidates.sort();
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [qualified] => Ok(self.index.get(*qualified)),
+            _ => bail!(
+                "Template id '{id}' is ambiguous; use one of: {}",
+                candidates
+                    .iter()
+                    .map(|id| id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
     }
+
+    fn tree_at(&self, position: &[usize]) -> Option<&CatalogTree> {
+        let (root, rest) = position.split_first()?;
+        self.trees.get(*root)?.subtree_at(rest)
+    }
 }
 
 #[derive(Debug)]
 pub enum FileRef<'a> {
     Standalone(&'a TemplateFile),
+    /// A file declared in `tree`, which may itself be a subtree.
     TreeMember {
         tree: &'a CatalogTree,
         file: &'a TemplateFile,
     },
 }
 
-#[derive(Default)]
 struct CatalogBuilder {
     files: Vec<TemplateFile>,
     trees: Vec<CatalogTree>,
+    partials: PartialSet,
     index: HashMap<String, TemplateRef>,
+    origins: HashMap<String, TemplateOrigin>,
     ids: IdRegistry,
+    /// Layer of the source being ingested.
+    layer: TemplateLayer,
+    /// Names of the script helpers the templates may call.
+    helpers: BTreeSet<String>,
+}
+
+impl Default for CatalogBuilder {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            trees: Vec::new(),
+            partials: PartialSet::default(),
+            index: HashMap::new(),
+            origins: HashMap::new(),
+            ids: IdRegistry::default(),
+            layer: TemplateLayer::Local,
+            helpers: BTreeSet::new(),
+        }
+    }
 }
 
 impl CatalogBuilder {
-    fn ingest(&mut self, path: &Path) -> Result<()> {
-        let parsed = parse_file(path)
+    fn ingest(&mut self, path: &Path, namespace: Option<&str>) -> Result<()> {
+        let mut parsed = parse_file_with_helpers(path, &self.helpers)
             .with_context(|| format!("Failed to parse template file: {}", path.display()))?;
+        assets::resolve_assets(&mut parsed, path)?;
+        if let Some(namespace) = namespace {
+            qualify_ids(&mut parsed, namespace);
+        }
         match parsed {
             ParsedMarkdown::Template(t) => match t {
                 Template::TemplateFile(file) => self.add_file(file)?,
@@ -130,7 +353,15 @@ impl CatalogBuilder {
             ParsedMarkdown::Tree(tree) => {
                 self.add_tree(tree)?;
             }
+            ParsedMarkdown::Partials(partials) => {
+                for partial in partials {
+                    self.partials.add(partial, self.layer)?;
+                }
+            }
             ParsedMarkdown::Collection(collection) => {
+                for partial in collection.partials {
+                    self.partials.add(partial, self.layer)?;
+                }
                 let mut trees = Vec::new();
                 let mut files = Vec::new();
                 for template in collection.templates {
@@ -157,66 +388,318 @@ impl CatalogBuilder {
         Ok(())
     }
 
-    fn add_file(&mut self, file: TemplateFile) -> Result<()> {
-        self.ids.claim(&file.id, &file.location)?;
+    fn add_file(&mut self, mut file: TemplateFile) -> Result<()> {
+        if let Some(target) = self.overridden(&file.id)? {
+            file.id = target;
+            return self.override_file(file);
+        }
+        self.claim(&file.id, &file.location)?;
         let idx = self.files.len();
         self.files.push(file.clone());
         self.index.insert(file.id.clone(), TemplateRef::File(idx));
         Ok(())
     }
 
-    fn add_tree(&mut self, tree: TemplateTree) -> Result<Vec<String>> {
-        self.ids.claim(&tree.id, &tree.location)?;
-        let tree_index = self.trees.len();
-        let mut files: Vec<TemplateFile> = Vec::new();
+    fn add_tree(&mut self, mut tree: TemplateTree) -> Result<Vec<String>> {
         let mut member_ids: Vec<String> = Vec::new();
+        if let Some(target) = self.overridden(&tree.id)? {
+            // Move the members into the namespace of the tree being replaced
+            let from = EntityId::split(&tree.id).0.map(str::to_string);
+            if let Some(to) = EntityId::split(&target).0
+                && Some(to) != from.as_deref()
+            {
+                rename_tree(&mut tree, &|id| {
+                    let local = from
+                        .as_deref()
+                        .and_then(|from| id.strip_prefix(from)?.strip_prefix(NAMESPACE_SEPARATOR))
+                        .unwrap_or(id);
+                    EntityId::qualify(to, local)
+                });
+            }
+            self.override_tree(tree, &mut member_ids)?;
+            return Ok(member_ids);
+        }
+        let catalog_tree = self.build_tree(tree, vec![self.trees.len()], &mut member_ids)?;
+        self.trees.push(catalog_tree);
+        Ok(member_ids)
+    }
+
+    /// Id of the template from a lower layer that a template with this id replaces: the
+    /// same id or the only id sharing it inside a namespace nested in the id's own.
+    fn overridden(&self, id: &str) -> Result<Option<String>> {
+        let lower = |key: &str| {
+            self.origins
+                .get(key)
+                .is_some_and(|origin| origin.layer < self.layer)
+        };
+        if self.origins.contains_key(id) {
+            return Ok(lower(id).then(|| id.to_string()));
+        }
+        let (namespace, local) = EntityId::split(id);
+        let mut candidates: Vec<&String> = self
+            .origins
+            .keys()
+            .filter(|key| {
+                lower(key)
+                    && EntityId::matches(key, local)
+                    && key.as_str() != local
+                    && namespace.is_none_or(|namespace| {
+                        EntityId::namespaces(key)
+                            .skip(1)
+                            .any(|outer| outer == namespace)
+                    })
+            })
+            .collect();
+        candidates.sort();
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [target] => Ok(Some(target.to_string())),
+            _ => bail!(
+                "Template id '{id}' from the {} layer overrides several templates; \
+                 namespace it to pick one of: {}",
+                self.layer,
+                candidates
+                    .iter()
+                    .map(|id| id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Replace a standalone file or tree member from a lower layer with the same id. A tree
+    /// member keeps its output path unless the override declares one.
+    fn override_file(&mut self, mut file: TemplateFile) -> Result<()> {
+        let target = match self.index.get(&file.id) {
+            Some(TemplateRef::Tree(_)) | None => bail!(
+                "Template '{}' from the {} layer can't override a tree with a file",
+                file.id,
+                self.layer
+            ),
+            Some(target) => target.clone(),
+        };
+        let replaced = self.release(&file.id);
+        self.claim(&file.id, &file.location)?;
+        self.mark_override(&file.id, replaced);
+        self.index.insert(file.id.clone(), target.clone());
+        match target {
+            TemplateRef::File(idx) => self.files[idx] = file,
+            TemplateRef::TreeFile { tree, file: member } => {
+                let slot = &mut self.tree_at_mut(&tree).expect("indexed tree exists").files[member];
+                if file.path.is_none() {
+                    file.path = slot.path.take();
+                }
+                *slot = file;
+            }
+            TemplateRef::Tree(_) => unreachable!("trees are rejected above"),
+        }
+        Ok(())
+    }
+
+    /// Replace a tree from a lower layer with the same id, together with all its members
+    /// and subtrees.
+    fn override_tree(&mut self, tree: TemplateTree, member_ids: &mut Vec<String>) -> Result<()> {
+        let Some(TemplateRef::Tree(position)) = self.index.get(&tree.id).cloned() else {
+            bail!(
+                "Template '{}' from the {} layer can't override a file with a tree",
+                tree.id,
+                self.layer
+            );
+        };
+        let previous = self
+            .tree_at_mut(&position)
+            .expect("indexed tree exists")
+            .ids();
+        let replaced = self.release(&tree.id);
+        for id in previous.iter().skip(1) {
+            self.release(id);
+        }
+        let id = tree.id.clone();
+        let built = self.build_tree(tree, position.clone(), member_ids)?;
+        *self.tree_at_mut(&position).expect("indexed tree exists") = built;
+        self.mark_override(&id, replaced);
+        Ok(())
+    }
+
+    fn claim(&mut self, id: &str, location: &Location) -> Result<()> {
+        self.ids.claim(id, location)?;
+        self.origins.insert(
+            id.to_string(),
+            TemplateOrigin {
+                layer: self.layer,
+                overrides: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Forget an id so a higher layer can claim it, returning the layer it came from.
+    fn release(&mut self, id: &str) -> Option<TemplateLayer> {
+        self.ids.release(id);
+        self.index.remove(id);
+        self.origins.remove(id).map(|origin| origin.layer)
+    }
+
+    fn mark_override(&mut self, id: &str, replaced: Option<TemplateLayer>) {
+        if let Some(origin) = self.origins.get_mut(id) {
+            origin.overrides = replaced;
+        }
+    }
+
+    fn tree_at_mut(&mut self, position: &[usize]) -> Option<&mut CatalogTree> {
+        let (root, rest) = position.split_first()?;
+        self.trees.get_mut(*root)?.subtree_at_mut(rest)
+    }
+
+    /// Index the tree and, recursively, its subtrees by their own ids.
+    fn build_tree(
+        &mut self,
+        tree: TemplateTree,
+        position: Vec<usize>,
+        member_ids: &mut Vec<String>,
+    ) -> Result<CatalogTree> {
+        self.claim(&tree.id, &tree.location)?;
+        let mut files: Vec<TemplateFile> = Vec::new();
+        let mut subtrees: Vec<CatalogTree> = Vec::new();
         for template in tree.files.into_iter() {
             match template {
                 Template::TemplateFile(file) => {
-                    self.ids.claim(&file.id, &file.location)?;
+                    self.claim(&file.id, &file.location)?;
                     member_ids.push(file.id.clone());
-                    let file_index = files.len();
-                    files.push(file.clone());
                     self.index.insert(
                         file.id.clone(),
                         TemplateRef::TreeFile {
-                            tree: tree_index,
-                            file: file_index,
+                            tree: position.clone(),
+                            file: files.len(),
                         },
                     );
+                    files.push(file);
                 }
                 Template::TemplateTree(nested) => {
-                    return Err(anyhow!(
-                        "Nested template trees are not supported (tree '{}', nested '{}')",
-                        tree.id,
-                        nested.id
-                    ));
+                    let mut nested_position = position.clone();
+                    nested_position.push(subtrees.len());
+                    subtrees.push(self.build_tree(nested, nested_position, member_ids)?);
                 }
             }
         }
 
-        let catalog_tree = CatalogTree {
-            id: tree.id.clone(),
+        self.index
+            .insert(tree.id.clone(), TemplateRef::Tree(position));
+        Ok(CatalogTree {
+            id: tree.id,
             name: tree.name,
             description: tree.description,
             files,
+            subtrees,
+            includes: tree.includes,
+            hooks: tree.hooks,
             location: tree.location,
-        };
-        self.index
-            .insert(tree.id.clone(), TemplateRef::Tree(tree_index));
-        self.trees.push(catalog_tree);
-        Ok(member_ids)
+        })
     }
 
-    fn finish(self) -> TemplateCatalog {
-        TemplateCatalog {
+    fn finish(mut self) -> Result<TemplateCatalog> {
+        self.partials.validate()?;
+        for file in self.files.iter_mut() {
+            add_partial_args(&self.partials, &self.helpers, file)?;
+        }
+        for tree in self.trees.iter_mut() {
+            add_tree_partial_args(&self.partials, &self.helpers, tree)?;
+        }
+
+        let mut catalog = TemplateCatalog {
             files: self.files,
             trees: self.trees,
+            partials: self.partials,
             index: self.index,
+            origins: self.origins,
+        };
+        catalog.resolve_includes()?;
+        Ok(catalog)
+    }
+}
+
+/// Prefix the ids of every parsed template and the names of its partials with the
+/// namespace of its source.
+fn qualify_ids(parsed: &mut ParsedMarkdown, namespace: &str) {
+    let qualify_partials = |partials: &mut Vec<TemplatePartial>| {
+        for partial in partials.iter_mut() {
+            partial.name = EntityId::qualify(namespace, &partial.name);
+        }
+    };
+    match parsed {
+        ParsedMarkdown::Template(template) => qualify(template, namespace),
+        ParsedMarkdown::Tree(tree) => qualify_tree(tree, namespace),
+        ParsedMarkdown::Collection(collection) => {
+            for template in collection.templates.iter_mut() {
+                qualify(template, namespace);
+            }
+            qualify_partials(&mut collection.partials);
+        }
+        ParsedMarkdown::Partials(partials) => qualify_partials(partials),
+    }
+}
+
+fn qualify(template: &mut Template, namespace: &str) {
+    match template {
+        Template::TemplateFile(file) => file.id = EntityId::qualify(namespace, &file.id),
+        Template::TemplateTree(tree) => qualify_tree(tree, namespace),
+    }
+}
+
+fn qualify_tree(tree: &mut TemplateTree, namespace: &str) {
+    rename_tree(tree, &|id| EntityId::qualify(namespace, id));
+}
+
+/// Rename the tree and every template nested in it.
+fn rename_tree(tree: &mut TemplateTree, rename: &dyn Fn(&str) -> String) {
+    tree.id = rename(&tree.id);
+    for template in tree.files.iter_mut() {
+        match template {
+            Template::TemplateFile(file) => file.id = rename(&file.id),
+            Template::TemplateTree(subtree) => rename_tree(subtree, rename),
         }
     }
 }
 
+/// Surface args used inside included partials as args of the including file.
+fn add_partial_args(
+    partials: &PartialSet,
+    helpers: &BTreeSet<String>,
+    file: &mut TemplateFile,
+) -> Result<()> {
+    if file.raw == Some(true) || file.asset.is_some() {
+        return Ok(());
+    }
+    let used = partials.args_for(&file.content, &file.location, &file.id, helpers)?;
+    for name in used {
+        if file.args.iter().any(|arg| arg.name == name) {
+            continue;
+        }
+        file.args.push(Arg {
+            name,
+            description: String::new(),
+            kind: ArgKind::Any(ArgKindAny),
+            required: true,
+            derived: None,
+        });
+    }
+    Ok(())
+}
+
+fn add_tree_partial_args(
+    partials: &PartialSet,
+    helpers: &BTreeSet<String>,
+    tree: &mut CatalogTree,
+) -> Result<()> {
+    for file in tree.files.iter_mut() {
+        add_partial_args(partials, helpers, file)?;
+    }
+    for subtree in tree.subtrees.iter_mut() {
+        add_tree_partial_args(partials, helpers, subtree)?;
+    }
+    Ok(())
+}
+
 #[derive(Default)]
 struct IdRegistry {
     seen: HashMap<String, Location>,
@@ -234,6 +717,10 @@ impl IdRegistry {
         self.seen.insert(id.to_string(), location.clone());
         Ok(())
     }
+
+    fn release(&mut self, id: &str) {
+        self.seen.remove(id);
+    }
 }
 
 #[cfg(test)]
@@ -250,7 +737,7 @@ mod tests {
         let path = fixture("rust-crate.md");
         let catalog = TemplateCatalog::load(&[path]).expect("catalog loads");
         assert!(
-            catalog.tree_templates().len() >= 1,
+            !catalog.tree_templates().is_empty(),
             "expected at least one tree template"
         );
         let tree = &catalog.tree_templates()[0];
@@ -269,7 +756,7 @@ mod tests {
                 !is_standalone,
                 "tree member {file_id} should not appear as standalone"
             );
-            match catalog.get_file(file_id) {
+            match catalog.get_file(file_id).unwrap() {
                 Some(FileRef::TreeMember { file: nested, .. }) => {
                     assert_eq!(nested.id, *file_id);
                 }
@@ -294,12 +781,688 @@ mod tests {
         let tree = &catalog.tree_templates()[0];
         let bogus_id = format!("{}__missing", tree.id());
         assert!(
-            catalog.get_file(&bogus_id).is_none(),
+            catalog.get_file(&bogus_id).unwrap().is_none(),
             "unexpected match for {bogus_id}"
         );
         assert!(
-            catalog.get_tree("not_a_tree").is_none(),
+            catalog.get_tree("not_a_tree").unwrap().is_none(),
             "unexpected tree match"
         );
     }
+
+    #[test]
+    fn indexes_nested_subtrees_by_their_own_ids() {
+        let catalog = TemplateCatalog::load(&[fixture("monorepo.md")]).expect("catalog loads");
+        let roots: Vec<&str> = catalog.tree_templates().iter().map(|t| t.id()).collect();
+        assert_eq!(roots, ["monorepo"]);
+        assert!(catalog.standalone_files().is_empty());
+
+        let root = catalog.get_tree("monorepo").unwrap().expect("root tree");
+        let subtrees: Vec<&str> = root.subtrees().iter().map(|t| t.id()).collect();
+        assert_eq!(subtrees, ["monorepo_crate", "monorepo_web"]);
+        let paths: Vec<&str> = root
+            .all_files()
+            .iter()
+            .filter_map(|f| f.path.as_deref())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "./README.md",
+                "./crate/Cargo.toml",
+                "./crate/src/lib.rs",
+                "./web/package.json"
+            ]
+        );
+
+        let web = catalog.get_tree("monorepo_web").unwrap().expect("subtree");
+        assert_eq!(web.all_files().len(), 1);
+        match catalog.get_file("monorepo_crate_crate_src_lib_rs").unwrap() {
+            Some(FileRef::TreeMember { tree, .. }) => assert_eq!(tree.id(), "monorepo_crate"),
+            other => panic!("expected subtree member lookup, got {:?}", other),
+        }
+        assert_eq!(catalog.all_trees().len(), 3);
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).expect("write fixture");
+        path
+    }
+
+    #[test]
+    fn partial_args_roll_up_into_templates() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let partials = write(
+            dir.path(),
+            "shared.partial.md",
+            "# Shared\n\n## `license`\n\n```md\n{{> author}} {{license}}\n```\n\n## `author`\n\n```md\n{{author}} {{year}}\n```\n",
+        );
+        let readme = write(
+            dir.path(),
+            "readme.md",
+            "# Readme\n\n```md\n{{name}}\n\n{{> license year=current_year}}\n```\n",
+        );
+
+        let catalog = TemplateCatalog::load(&[readme, partials]).expect("catalog loads");
+        let file = &catalog.standalone_files()[0];
+        let names: Vec<_> = file.args.iter().map(|arg| arg.name.as_str()).collect();
+        assert_eq!(names, vec!["current_year", "name", "author", "license"]);
+
+        let rendered = catalog
+            .renderer(&[])
+            .expect("renderer")
+            .render(
+                &file.id,
+                &file.content,
+                &serde_json::json!({"name": "pkg", "current_year": 2025, "author": "Sasha", "license": "MIT"}),
+            )
+            .expect("rendered");
+        assert_eq!(rendered, "pkg\n\nSasha 2025 MIT");
+    }
+
+    #[test]
+    fn unknown_partials_error() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let readme = write(
+            dir.path(),
+            "readme.md",
+            "# Readme\n\n```md\n{{> missing}}\n```\n",
+        );
+        let err = TemplateCatalog::load(&[readme]).expect_err("unknown partial should fail");
+        assert!(
+            err.to_string().contains("Unknown partial 'missing'"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn cyclic_partials_error() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let partials = write(
+            dir.path(),
+            "cycle.partial.md",
+            "# Cycle\n\n## `a`\n\n```md\n{{> b}}\n```\n\n## `b`\n\n```md\n{{> a}}\n```\n",
+        );
+        let err = TemplateCatalog::load(&[partials]).expect_err("cycle should fail");
+        assert!(
+            err.to_string()
+                .contains("Partials form a cycle: a -> b -> a"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn script_helpers_are_not_args() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let script = write(dir.path(), "timestamp.rhai", "\"2025-01-01\"");
+        write(
+            dir.path(),
+            "stamp.partial.md",
+            "# Stamp\n\n## `stamp`\n\n```md\n{{timestamp}} {{author}}\n```\n",
+        );
+        let notes = write(
+            dir.path(),
+            "notes.md",
+            "# Notes\n\n```md\n{{timestamp}} {{> stamp}}\n```\n",
+        );
+        let partials = dir.path().join("stamp.partial.md");
+        let sources = [TemplateSource {
+            layer: TemplateLayer::Local,
+            namespace: None,
+            paths: vec![notes, partials],
+            out: None,
+        }];
+
+        let catalog =
+            TemplateCatalog::load_sources_with_helpers(&sources, std::slice::from_ref(&script))
+                .expect("catalog loads");
+        let file = &catalog.standalone_files()[0];
+        let args: Vec<&str> = file.args.iter().map(|arg| arg.name.as_str()).collect();
+        assert_eq!(args, ["author"]);
+
+        let rendered = catalog
+            .renderer(&[script])
+            .unwrap()
+            .render_map(
+                "notes",
+                &file.content,
+                &serde_json::Map::from_iter([("author".to_string(), "Sasha".into())]),
+            )
+            .unwrap();
+        assert_eq!(rendered, "2025-01-01 2025-01-01 Sasha");
+    }
+
+    const LICENSE_TREE: &str = "# License\n\n## `./LICENSE`\n\n```text\n{{holder}}\n```\n\n## `./NOTICE`\n\n```text\n{{#if year}}{{year}}{{/if}}\n```\n";
+
+    #[test]
+    fn includes_resolve_into_tree_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let license = write(dir.path(), "license.md", LICENSE_TREE);
+        let app = write(
+            dir.path(),
+            "app.md",
+            "# App\n\ninclude: `license` at ./legal with holder=author\n\n## `./README.md`\n\n```md\n{{author}}\n```\n\n## `./main.txt`\n\n```text\nmain\n```\n",
+        );
+
+        let catalog = TemplateCatalog::load(&[app, license.clone()]).expect("catalog loads");
+        let tree = catalog.get_tree("app").unwrap().expect("app tree");
+        let paths: Vec<_> = tree
+            .files()
+            .iter()
+            .filter_map(|file| file.path.as_deref())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                "./README.md",
+                "./main.txt",
+                "./legal/LICENSE",
+                "./legal/NOTICE"
+            ]
+        );
+
+        let included = &tree.files()[2];
+        assert_eq!(included.location.path, license.display().to_string());
+        let holder = included
+            .args
+            .iter()
+            .find(|arg| arg.name == "holder")
+            .unwrap();
+        assert_eq!(holder.derived.as_deref(), Some("{{author}}"));
+        let author = included
+            .args
+            .iter()
+            .find(|arg| arg.name == "author")
+            .unwrap();
+        assert!(author.required && author.derived.is_none());
+
+        let renderer = catalog.renderer(&[]).expect("renderer");
+        let mut context = serde_json::json!({"author": "Sasha"})
+            .as_object()
+            .cloned()
+            .unwrap();
+        let derived = nmcr_template::DerivedArg::from_args(&included.args);
+        renderer
+            .apply_derived(&included.id, &derived, &mut context)
+            .expect("derived");
+        let rendered = renderer
+            .render_map(&included.id, &included.content, &context)
+            .expect("rendered");
+        assert_eq!(rendered, "Sasha");
+    }
+
+    #[test]
+    fn include_errors() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let license = write(dir.path(), "license.md", LICENSE_TREE);
+        let tree = |name: &str, include: &str| {
+            format!(
+                "# {name}\n\ninclude: {include}\n\n## `./a.txt`\n\n```text\na\n```\n\n## `./b.txt`\n\n```text\nb\n```\n"
+            )
+        };
+
+        let unknown = write(dir.path(), "unknown.md", &tree("Unknown", "missing"));
+        let err = TemplateCatalog::load(&[unknown]).expect_err("unknown include");
+        assert!(
+            err.to_string()
+                .contains("Unknown template 'missing' included by tree 'unknown'"),
+            "unexpected error: {err}"
+        );
+
+        let unmapped = write(
+            dir.path(),
+            "unmapped.md",
+            &tree("Unmapped", "license with owner=author"),
+        );
+        let err = TemplateCatalog::load(&[unmapped, license]).expect_err("unknown arg");
+        assert!(
+            err.to_string()
+                .contains("Include of 'license' maps unknown arg 'owner'"),
+            "unexpected error: {err}"
+        );
+
+        let a = write(dir.path(), "a.md", &tree("A", "b"));
+        let b = write(dir.path(), "b.md", &tree("B", "a"));
+        let err = TemplateCatalog::load(&[a, b]).expect_err("cycle");
+        assert!(
+            err.to_string()
+                .contains("Tree includes form a cycle: a -> b -> a"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn assets_resolve_next_to_markdown() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let content = "# Logo\n\n![logo](./assets/logo.png)\n";
+        let missing = write(dir.path(), "logo.md", content);
+        let err = TemplateCatalog::load(std::slice::from_ref(&missing)).expect_err("missing asset");
+        assert!(
+            err.to_string()
+                .contains("Asset './assets/logo.png' referenced by template 'logo'"),
+            "unexpected error: {err}"
+        );
+
+        std::fs::create_dir(dir.path().join("assets")).expect("assets dir");
+        std::fs::write(dir.path().join("assets/logo.png"), [0x89, b'P']).expect("write asset");
+        let catalog = TemplateCatalog::load(&[missing]).expect("catalog loads");
+        let Some(FileRef::Standalone(file)) = catalog.get_file("logo").unwrap() else {
+            panic!("expected the logo file");
+        };
+        assert_eq!(
+            file.asset.as_deref().map(Path::new),
+            Some(dir.path().join("assets/logo.png").as_path())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn assets_outside_the_markdown_directory_error() {
+        let outside = tempfile::tempdir().expect("tempdir");
+        std::fs::write(outside.path().join("secret.txt"), "secret").expect("write secret");
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("assets")).expect("symlink");
+        let path = write(
+            dir.path(),
+            "leak.md",
+            "# Leak\n\n![secret](./assets/secret.txt)\n",
+        );
+
+        let err = TemplateCatalog::load(&[path]).expect_err("escaping asset");
+        assert!(
+            err.to_string()
+                .contains("Asset './assets/secret.txt' referenced by template 'leak'")
+                && err.to_string().contains("is outside"),
+            "unexpected error: {err}"
+        );
+    }
+
+    fn source(namespace: &str, paths: Vec<PathBuf>) -> TemplateSource {
+        TemplateSource {
+            layer: TemplateLayer::Source,
+            namespace: Some(namespace.to_string()),
+            paths,
+            out: None,
+        }
+    }
+
+    #[test]
+    fn namespaces_qualify_ids_and_resolve_unqualified_lookups() {
+        let first = tempfile::tempdir().expect("tempdir");
+        let second = tempfile::tempdir().expect("tempdir");
+        let readme = "# Readme\n\n```md\n{{name}}\n```\n";
+        let a = vec![write(first.path(), "readme.md", readme)];
+        let b = vec![
+            write(second.path(), "readme.md", readme),
+            write(second.path(), "license.md", LICENSE_TREE),
+            write(
+                second.path(),
+                "app.md",
+                "# App\n\ninclude: `license`\n\n## `./README.md`\n\n```md\nreadme\n```\n\n## `./main.txt`\n\n```text\nmain\n```\n",
+            ),
+        ];
+
+        let catalog = TemplateCatalog::load_sources(&[source("a", a.clone()), source("b", b)])
+            .expect("catalog loads");
+        let Some(FileRef::Standalone(file)) = catalog.get_file("a::readme").unwrap() else {
+            panic!("expected the qualified readme");
+        };
+        assert_eq!(file.id, "a::readme");
+
+        let err = catalog.get_file("readme").expect_err("ambiguous id");
+        assert!(
+            err.to_string()
+                .contains("Template id 'readme' is ambiguous; use one of: a::readme, b::readme"),
+            "unexpected error: {err}"
+        );
+
+        let tree = catalog
+            .get_tree("app")
+            .unwrap()
+            .expect("unique unqualified id");
+        assert_eq!(tree.id(), "b::app");
+        assert_eq!(tree.files().len(), 4);
+        assert!(catalog.get_tree("a::app").unwrap().is_none());
+
+        let nested = TemplateCatalog::load_sources(&[source("web::acme", a.clone())])
+            .expect("nested namespace loads");
+        assert!(nested.get_file("web::acme::readme").unwrap().is_some());
+
+        let err =
+            TemplateCatalog::load_sources(&[source("My Co", a)]).expect_err("invalid namespace");
+        assert!(
+            err.to_string().contains("Invalid namespace 'My Co'"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn namespaced_ids_clash_within_a_namespace() {
+        let first = tempfile::tempdir().expect("tempdir");
+        let second = tempfile::tempdir().expect("tempdir");
+        let a = vec![write(first.path(), "license.md", LICENSE_TREE)];
+        let b = vec![write(second.path(), "license.md", LICENSE_TREE)];
+
+        let err = TemplateCatalog::load_sources(&[source("a", a.clone()), source("a", b.clone())])
+            .expect_err("same namespace twice");
+        assert!(
+            format!("{err:?}").contains("Duplicate template id: a::license"),
+            "unexpected error: {err:?}"
+        );
+
+        let catalog = TemplateCatalog::load_sources(&[source("a", a), source("b", b)])
+            .expect("catalog loads");
+        let err = catalog.get_tree("license").expect_err("ambiguous tree id");
+        assert_eq!(
+            err.to_string(),
+            "Template id 'license' is ambiguous; use one of: a::license, b::license"
+        );
+        assert!(catalog.get_tree("c::license").unwrap().is_none());
+    }
+
+    #[test]
+    fn partials_are_scoped_per_namespace_and_overridden_by_higher_layers() {
+        let first = tempfile::tempdir().expect("tempdir");
+        let second = tempfile::tempdir().expect("tempdir");
+        let local = tempfile::tempdir().expect("tempdir");
+        let header = |dir: &Path, text: &str| {
+            write(
+                dir,
+                "header.partial.md",
+                &format!("# Header\n\n```md\n{text}\n```\n"),
+            )
+        };
+        let readme = |dir: &Path| {
+            write(
+                dir,
+                "readme.md",
+                "# Readme\n\n```md\n{{> header}} {{name}}\n```\n",
+            )
+        };
+        let sources = [
+            source("a", vec![header(first.path(), "A"), readme(first.path())]),
+            source(
+                "b",
+                vec![header(second.path(), "B {{year}}"), readme(second.path())],
+            ),
+        ];
+
+        let catalog = TemplateCatalog::load_sources(&sources).expect("catalog loads");
+        let renderer = catalog.renderer(&[]).unwrap();
+        let render = |id: &str| {
+            let Some(FileRef::Standalone(template)) = catalog.get_file(id).unwrap() else {
+                panic!("{id} should be a standalone file");
+            };
+            let args: Vec<&str> = template.args.iter().map(|arg| arg.name.as_str()).collect();
+            let rendered = renderer
+                .render_map(
+                    &template.id,
+                    &template.content,
+                    &serde_json::Map::from_iter([
+                        ("name".to_string(), "x".into()),
+                        ("year".to_string(), "2025".into()),
+                    ]),
+                )
+                .unwrap();
+            (args.join(","), rendered)
+        };
+        assert_eq!(render("a::readme"), ("name".into(), "A x".into()));
+        assert_eq!(render("b::readme"), ("name,year".into(), "B 2025 x".into()));
+
+        let overridden = [
+            sources[0].clone(),
+            TemplateSource {
+                layer: TemplateLayer::Local,
+                namespace: None,
+                paths: vec![header(local.path(), "Local")],
+                out: None,
+            },
+        ];
+        let catalog = TemplateCatalog::load_sources(&overridden).expect("catalog loads");
+        let Some(FileRef::Standalone(template)) = catalog.get_file("a::readme").unwrap() else {
+            panic!("a::readme should be a standalone file");
+        };
+        let rendered = catalog
+            .renderer(&[])
+            .unwrap()
+            .render_map(
+                &template.id,
+                &template.content,
+                &serde_json::Map::from_iter([("name".to_string(), "x".into())]),
+            )
+            .unwrap();
+        assert_eq!(rendered, "Local x");
+
+        let duplicate = [
+            sources[0].clone(),
+            source("a", vec![header(local.path(), "Again")]),
+        ];
+        let err = TemplateCatalog::load_sources(&duplicate).expect_err("duplicate should fail");
+        assert!(
+            format!("{err:#}").contains("Duplicate partial name: a::header"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn nested_namespaces_resolve_and_override_by_suffix() {
+        let shared = tempfile::tempdir().expect("tempdir");
+        let local = tempfile::tempdir().expect("tempdir");
+        let sources = [
+            TemplateSource {
+                layer: TemplateLayer::Local,
+                namespace: Some("web".into()),
+                paths: vec![
+                    write(local.path(), "readme.md", "# Readme\n\n```md\nlocal\n```\n"),
+                    write(
+                        local.path(),
+                        "cargo.md",
+                        "# Rust Crate Lib\n\n## Cargo.toml\n\n```toml\nlocal\n```\n",
+                    ),
+                ],
+                out: None,
+            },
+            source(
+                "web::acme",
+                vec![
+                    write(
+                        shared.path(),
+                        "readme.md",
+                        "# Readme\n\n```md\nshared\n```\n",
+                    ),
+                    write(shared.path(), "license.md", LICENSE_TREE),
+                    write(shared.path(), "crate.md", CRATE_TREE),
+                ],
+            ),
+        ];
+
+        let catalog = TemplateCatalog::load_sources(&sources).expect("catalog loads");
+        for query in ["readme", "acme::readme", "web::acme::readme"] {
+            let Some(FileRef::Standalone(file)) = catalog.get_file(query).unwrap() else {
+                panic!("expected '{query}' to resolve");
+            };
+            assert_eq!(file.id, "web::acme::readme");
+            assert_eq!(file.content, "local");
+        }
+        assert!(catalog.get_file("web::readme").unwrap().is_none());
+        assert!(catalog.get_file("me::readme").unwrap().is_none());
+        assert_eq!(
+            catalog.get_tree("license").unwrap().expect("tree").id(),
+            "web::acme::license"
+        );
+
+        let tree = catalog
+            .get_tree("acme::rust_crate_lib")
+            .unwrap()
+            .expect("tree");
+        assert_eq!(tree.files()[0].id, "web::acme::rust_crate_lib_cargo_toml");
+        assert_eq!(tree.files()[0].content, "local");
+    }
+
+    const CRATE_TREE: &str = "# Rust Crate Lib\n\n## `./Cargo.toml`\n\n```toml\nshared\n```\n\n## `./src/lib.rs`\n\n```rust\nlib\n```\n";
+
+    #[test]
+    fn higher_layers_override_templates_and_tree_members() {
+        let shared = tempfile::tempdir().expect("tempdir");
+        let local = tempfile::tempdir().expect("tempdir");
+        let shared_paths = vec![
+            write(shared.path(), "crate.md", CRATE_TREE),
+            write(
+                shared.path(),
+                "readme.md",
+                "# Readme\n\n```md\nshared\n```\n",
+            ),
+        ];
+        let local_paths = vec![
+            write(
+                local.path(),
+                "cargo.md",
+                "# Rust Crate Lib\n\n## Cargo.toml\n\n```toml\nlocal\n```\n",
+            ),
+            write(local.path(), "readme.md", "# Readme\n\n```md\nlocal\n```\n"),
+        ];
+        let layers = |namespace: Option<&str>, local_paths: Vec<PathBuf>| {
+            [
+                TemplateSource {
+                    layer: TemplateLayer::Local,
+                    namespace: None,
+                    paths: local_paths,
+                    out: None,
+                },
+                TemplateSource {
+                    layer: TemplateLayer::Source,
+                    namespace: namespace.map(str::to_string),
+                    paths: shared_paths.clone(),
+                    out: None,
+                },
+            ]
+        };
+
+        let catalog = TemplateCatalog::load_sources(&layers(None, local_paths.clone()))
+            .expect("catalog loads");
+        let tree = catalog.get_tree("rust_crate_lib").unwrap().expect("tree");
+        assert_eq!(tree.files()[0].content, "local");
+        assert_eq!(tree.files()[0].path.as_deref(), Some("./Cargo.toml"));
+        assert_eq!(tree.files()[1].content, "lib");
+        let Some(FileRef::TreeMember { file, .. }) =
+            catalog.get_file("rust_crate_lib_cargo_toml").unwrap()
+        else {
+            panic!("expected the overridden member");
+        };
+        assert_eq!(file.content, "local");
+        assert_eq!(catalog.standalone_files().len(), 1);
+        assert_eq!(catalog.standalone_files()[0].content, "local");
+        assert_eq!(
+            catalog.origin("readme"),
+            Some(TemplateOrigin {
+                layer: TemplateLayer::Local,
+                overrides: Some(TemplateLayer::Source),
+            })
+        );
+        assert_eq!(
+            catalog.origin("rust_crate_lib"),
+            Some(TemplateOrigin {
+                layer: TemplateLayer::Source,
+                overrides: None,
+            })
+        );
+
+        let namespaced = TemplateCatalog::load_sources(&layers(Some("acme"), local_paths))
+            .expect("catalog loads");
+        let tree = namespaced
+            .get_tree("acme::rust_crate_lib")
+            .unwrap()
+            .expect("tree");
+        assert_eq!(tree.files()[0].id, "acme::rust_crate_lib_cargo_toml");
+        assert_eq!(tree.files()[0].content, "local");
+
+        let replacement = write(
+            local.path(),
+            "crate.md",
+            "# Rust Crate Lib\n\n## `./Cargo.toml`\n\n```toml\nlocal\n```\n\n## `./src/main.rs`\n\n```rust\nmain\n```\n",
+        );
+        let catalog =
+            TemplateCatalog::load_sources(&layers(None, vec![replacement])).expect("catalog loads");
+        let tree = catalog.get_tree("rust_crate_lib").unwrap().expect("tree");
+        let paths: Vec<_> = tree
+            .files()
+            .iter()
+            .filter_map(|file| file.path.as_deref())
+            .collect();
+        assert_eq!(paths, ["./Cargo.toml", "./src/main.rs"]);
+        assert!(
+            catalog
+                .get_file("rust_crate_lib_src_lib_rs")
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(catalog.tree_templates().len(), 1);
+
+        let file_over_tree = write(
+            local.path(),
+            "conflict.md",
+            "# Rust Crate Lib\n\n```toml\nlocal\n```\n",
+        );
+        let err = TemplateCatalog::load_sources(&layers(None, vec![file_over_tree]))
+            .expect_err("file can't replace a tree");
+        assert!(
+            err.to_string()
+                .contains("Template 'rust_crate_lib' from the local layer can't override a tree"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn override_conflicts_error() {
+        let first = tempfile::tempdir().expect("tempdir");
+        let second = tempfile::tempdir().expect("tempdir");
+        let local = tempfile::tempdir().expect("tempdir");
+        let readme = "# Readme\n\n```md\nshared\n```\n";
+        let a = vec![write(first.path(), "readme.md", readme)];
+        let b = vec![write(second.path(), "readme.md", readme)];
+        let local_source = |paths: Vec<PathBuf>| TemplateSource {
+            layer: TemplateLayer::Local,
+            namespace: None,
+            paths,
+            out: None,
+        };
+
+        let file = vec![write(
+            local.path(),
+            "readme.md",
+            "# Readme\n\n```md\nlocal\n```\n",
+        )];
+        let err = TemplateCatalog::load_sources(&[
+            local_source(file.clone()),
+            source("a", a.clone()),
+            source("b", b),
+        ])
+        .expect_err("ambiguous override");
+        assert_eq!(
+            err.to_string(),
+            "Template id 'readme' from the local layer overrides several templates; \
+             namespace it to pick one of: a::readme, b::readme"
+        );
+
+        let tree = vec![write(
+            local.path(),
+            "tree.md",
+            "# Readme\n\n## `./README.md`\n\n```md\nlocal\n```\n\n## `./NOTICE`\n\n```text\nnotice\n```\n",
+        )];
+        let err = TemplateCatalog::load_sources(&[local_source(tree), source("a", a)])
+            .expect_err("tree can't replace a file");
+        assert_eq!(
+            err.to_string(),
+            "Template 'a::readme' from the local layer can't override a file with a tree"
+        );
+
+        let err =
+            TemplateCatalog::load_sources(&[local_source(vec![file[0].clone(), file[0].clone()])])
+                .expect_err("same layer duplicates");
+        assert!(
+            format!("{err:?}").contains("Duplicate template id: readme"),
+            "unexpected error: {err:?}"
+        );
+    }
 }
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result, anyhow, bail};
+use nmcr_id::EntityId;
+use nmcr_template::{discover_partials, discover_placeholders_with_helpers, resolve_partial};
+use nmcr_types::{Location, TemplatePartial};
+use nmcr_types_internal::{FormattedLocation, TemplateLayer};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Partials of the catalog, named `namespace::name` when their source has a namespace.
+/// Templates and partials include the one of their innermost namespace that declares the
+/// name, then the one without a namespace.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PartialSet {
+    partials: BTreeMap<String, TemplatePartial>,
+    layers: BTreeMap<String, TemplateLayer>,
+}
+
+impl PartialSet {
+    /// Add a partial from the given layer. Like a template, it replaces the partial with the
+    /// same name from a lower layer, or the only one sharing its name inside a namespace
+    /// nested in its own. Two partials of one layer can't share a name.
+    pub(crate) fn add(&mut self, partial: TemplatePartial, layer: TemplateLayer) -> Result<()> {
+        if let Some(existing) = self.partials.get(&partial.name)
+            && self.layers[&partial.name] >= layer
+        {
+            let first = FormattedLocation(&existing.location).to_string();
+            let duplicate = FormattedLocation(&partial.location).to_string();
+            return Err(anyhow!("Duplicate partial name: {}", partial.name))
+                .with_context(|| format!("duplicate occurrence at {duplicate}"))
+                .with_context(|| format!("first occurrence at {first}"));
+        }
+        if !self.partials.contains_key(&partial.name)
+            && let Some(target) = self.overridden(&partial.name, layer)?
+        {
+            let replacement = TemplatePartial {
+                name: target.clone(),
+                ..partial.clone()
+            };
+            self.layers.insert(target.clone(), layer);
+            self.partials.insert(target, replacement);
+        }
+        self.layers.insert(partial.name.clone(), layer);
+        self.partials.insert(partial.name.clone(), partial);
+        Ok(())
+    }
+
+    /// Name of the partial from a lower layer nested in the namespace of `name` that a
+    /// partial with this name replaces.
+    fn overridden(&self, name: &str, layer: TemplateLayer) -> Result<Option<String>> {
+        let (namespace, local) = EntityId::split(name);
+        let mut candidates: Vec<&String> = self
+            .partials
+            .keys()
+            .filter(|key| {
+                self.layers[key.as_str()] < layer
+                    && EntityId::matches(key, local)
+                    && key.as_str() != local
+                    && namespace.is_none_or(|namespace| {
+                        EntityId::namespaces(key)
+                            .skip(1)
+                            .any(|outer| outer == namespace)
+                    })
+            })
+            .collect();
+        candidates.sort();
+        match candidates.as_slice() {
+            [] => Ok(None),
+            [target] => Ok(Some(target.to_string())),
+            _ => bail!(
+                "Partial '{name}' from the {layer} layer overrides several partials; \
+                 namespace it to pick one of: {}",
+                candidates
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &TemplatePartial> {
+        self.partials.values()
+    }
+
+    /// Check that every partial reference resolves and that partials don't include themselves.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let mut done: BTreeSet<&str> = BTreeSet::new();
+        for name in self.partials.keys() {
+            let mut stack: Vec<&str> = Vec::new();
+            self.visit(name, &mut stack, &mut done)?;
+        }
+        Ok(())
+    }
+
+    fn visit<'a>(
+        &'a self,
+        name: &'a str,
+        stack: &mut Vec<&'a str>,
+        done: &mut BTreeSet<&'a str>,
+    ) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = stack.iter().position(|entry| *entry == name) {
+            let mut cycle = stack[start..].to_vec();
+            cycle.push(name);
+            let partial = &self.partials[name];
+            bail!(
+                "Partials form a cycle: {} at {}",
+                cycle.join(" -> "),
+                FormattedLocation(&partial.location)
+            );
+        }
+
+        let partial = &self.partials[name];
+        stack.push(name);
+        for reference in discover_partials(&partial.content) {
+            let (next, _) = self.get_key_value(&reference.name, &partial.location, name)?;
+            self.visit(next, stack, done)?;
+        }
+        stack.pop();
+        done.insert(name);
+        Ok(())
+    }
+
+    /// Collect the args used by the partials a template includes, following nested partials.
+    pub(crate) fn args_for(
+        &self,
+        content: &str,
+        location: &Location,
+        owner: &str,
+        helpers: &BTreeSet<String>,
+    ) -> Result<BTreeSet<String>> {
+        let mut args = BTreeSet::new();
+        for reference in discover_partials(content) {
+            let (name, partial) = self.get_key_value(&reference.name, location, owner)?;
+            let mut nested = self.args_for(&partial.content, &partial.location, name, helpers)?;
+            nested.extend(
+                discover_placeholders_with_helpers(&partial.content, helpers)
+                    .into_iter()
+                    .map(|placeholder| placeholder.name),
+            );
+            nested.retain(|arg| !reference.params.contains(arg));
+            args.extend(nested);
+        }
+        Ok(args)
+    }
+
+    /// The partial a `{{> name}}` in `owner`, a template id or partial name, includes.
+    fn get_key_value(
+        &self,
+        name: &str,
+        location: &Location,
+        owner: &str,
+    ) -> Result<(&str, &TemplatePartial)> {
+        let resolved = resolve_partial(owner, name, |name| self.partials.contains_key(name));
+        self.partials
+            .get_key_value(&resolved)
+            .map(|(key, partial)| (key.as_str(), partial))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown partial '{}' referenced by '{}' at {}",
+                    name,
+                    owner,
+                    FormattedLocation(location)
+                )
+            })
+    }
+}
@@ -17,9 +17,14 @@ impl<T: Args> CliCommandProject<T> {
             .unwrap_or_else(Config::default_path)
     }
 
+    /// Load the project, or the user-global library when no project path is given and
+    /// there's no config in the current or parent directories.
     pub fn load_project(&self) -> Result<Project> {
-        let path = self.resolve_project_path();
-        Project::load(Some(path))
+        let profile = self.global.profile.as_deref();
+        match self.global.project.clone().or_else(Config::locate) {
+            Some(path) => Project::load(Some(path), profile),
+            None => Project::load_global(profile),
+        }
     }
 }
 
@@ -28,4 +33,8 @@ pub struct CliCommandProjectArgs {
     /// Path to the project config file or directory containing it.
     #[arg(short, long, value_name = "PROJECT_PATH")]
     pub project: Option<PathBuf>,
+
+    /// Config profile to apply, ahead of `NMCR_PROFILE` and the config's `profile` key.
+    #[arg(long, value_name = "PROFILE")]
+    pub profile: Option<String>,
 }
@@ -1,12 +1,19 @@
 use crate::prelude::*;
 use anyhow::{Context, anyhow, bail};
+use clap::ValueEnum;
 use nmcr_catalog::{CatalogTree, FileRef as CatalogFileRef, TemplateCatalog};
-use nmcr_template::TemplateRenderer;
-use nmcr_types::{OutputFile, OutputTree, TemplateFile};
+use nmcr_template::{
+    MergeFormat, Normalizers, OutputFormatter, RendererOptions, TemplateRenderer, apply_defaults,
+    apply_patch, merge_document, output_bytes, push_hooks, shell_command,
+};
+use nmcr_types::{
+    Location, OutputFile, OutputTree, TemplateMerge, TemplateMergeArrays,
+    TemplateMergeArraysAppendUnique,
+};
 use serde_json::{Map as JsonMap, Number as JsonNumber, Value as JsonValue};
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf as FsPathBuf;
+use std::path::{Path, PathBuf as FsPathBuf};
 
 #[derive(Args, Debug)]
 pub struct GenArgs {
@@ -22,22 +29,52 @@ pub struct GenArgs {
     #[arg(long)]
     pub print: bool,
 
+    /// What to do with files that already exist (files marked `merge:` always merge)
+    #[arg(long, value_enum, default_value_t = Conflict::Overwrite)]
+    pub conflict: Conflict,
+
+    /// Fail unless template sources match an up-to-date ncmr.lock
+    #[arg(long)]
+    pub locked: bool,
+
+    /// Do not run post-generation hooks from templates or the project config
+    #[arg(long)]
+    pub no_hooks: bool,
+
     /// Template arguments in key=value form (or a positional output path followed by args)
     #[arg(index = 2, value_name = "key=value", num_args = 0.., allow_hyphen_values = true)]
     pub pairs: Vec<String>,
 }
 
+/// Policy for generated files whose target already exists.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file
+    Skip,
+    /// Deep-merge JSON, TOML and YAML files, appending unique array items; overwrite others
+    Merge,
+    /// Stop with an error
+    Fail,
+}
+
 #[derive(Args)]
 pub struct GenCmd {}
 
 impl GenCmd {
     pub async fn run(args: &CliCommandProject<GenArgs>) -> Result<()> {
         let project = args.load_project()?;
-        let paths = project.template_paths()?;
-        let catalog = TemplateCatalog::load(&paths)?;
+        if let Some(warning) = project.verify_lock(args.local.locked)?.warning() {
+            UiMessage::warn(&warning);
+        }
+        let sources = project.template_sources()?;
+        let helper_paths = project.helper_paths()?;
+        let catalog = TemplateCatalog::load_sources_with_helpers(&sources, &helper_paths)?;
 
         let id = &args.local.id;
         let print = args.local.print;
+        let conflict = args.local.conflict;
 
         let mut positional_pairs = args.local.pairs.clone();
         let positional_out = extract_positional_out(&mut positional_pairs);
@@ -49,15 +86,52 @@ impl GenCmd {
         }
 
         let out_dir = args.local.out.clone().or(positional_out);
-        let args_map = build_context_map(&positional_pairs)?;
-        let renderer = TemplateRenderer::new();
+        let mut args_map = build_context_map(&positional_pairs)?;
+        let defaults = project.defaults()?;
+        let options = RendererOptions {
+            env: project.render_env(),
+            ..Default::default()
+        };
+        let mut renderer = catalog.renderer_with_options(options, &helper_paths)?;
+        let formatter = output_formatter(&project);
+        let hooks = HookRunner {
+            project: &project,
+            enabled: !args.local.no_hooks,
+        };
 
-        if let Some(file_ref) = catalog.get_file(id) {
-            return handle_file(&renderer, file_ref, out_dir, print, &args_map);
+        if let Some(file_ref) = catalog.get_file(id)? {
+            let (template, location) = match &file_ref {
+                CatalogFileRef::Standalone(file) => (*file, &file.location),
+                CatalogFileRef::TreeMember { tree, file } => (*file, tree.location()),
+            };
+            apply_defaults(&mut args_map, &defaults, &template.args);
+            let out_dir = match out_dir {
+                Some(dir) => Some(dir),
+                None => default_out(&project, &sources, location),
+            };
+            renderer.set_out_dir(out_dir.as_deref().filter(|_| !print));
+            let root = handle_file(
+                &renderer, &formatter, file_ref, out_dir, print, conflict, &args_map,
+            )?;
+            return hooks.run(root);
         }
 
-        if let Some(tree) = catalog.get_tree(id) {
-            return handle_tree(&renderer, tree, out_dir, print, &args_map);
+        if let Some(tree) = catalog.get_tree(id)? {
+            let files = tree.all_files();
+            apply_defaults(
+                &mut args_map,
+                &defaults,
+                files.iter().flat_map(|file| &file.args),
+            );
+            let out_dir = match out_dir {
+                Some(dir) => Some(dir),
+                None => default_out(&project, &sources, tree.location()),
+            };
+            renderer.set_out_dir(out_dir.as_deref().filter(|_| !print));
+            let root = handle_tree(
+                &renderer, &formatter, tree, out_dir, print, conflict, &args_map,
+            )?;
+            return hooks.run(root);
         }
 
         let mut available: Vec<String> = catalog
@@ -65,7 +139,7 @@ impl GenCmd {
             .iter()
             .map(|f| f.id.clone())
             .collect();
-        for tree in catalog.tree_templates() {
+        for tree in catalog.all_trees() {
             available.push(tree.id().to_string());
             available.extend(tree.files().iter().map(|f| f.id.clone()));
         }
@@ -80,24 +154,79 @@ impl GenCmd {
     }
 }
 
+/// Output directory when none is passed: the template's named root, then the config's
+/// `out`, then the project root when it's a workspace member.
+fn default_out(
+    project: &Project,
+    sources: &[TemplateSource],
+    location: &Location,
+) -> Option<FsPathBuf> {
+    root_out(sources, location)
+        .or_else(|| project.out_dir())
+        .or_else(|| Workspace::is_member(project).then(|| project.path()))
+}
+
+/// Output directory of the named root whose globs matched the template's markdown file.
+fn root_out(sources: &[TemplateSource], location: &Location) -> Option<FsPathBuf> {
+    let path = Path::new(&location.path);
+    sources
+        .iter()
+        .find(|source| source.paths.iter().any(|source_path| source_path == path))
+        .and_then(|source| source.out.clone())
+}
+
+/// Formatter for generated files as configured in the project's `[format]` table.
+fn output_formatter(project: &Project) -> OutputFormatter {
+    let format = &project.config.user.format;
+    OutputFormatter::new(
+        Normalizers {
+            trim_trailing_whitespace: format.trim_trailing_whitespace,
+            collapse_blank_lines: format.collapse_blank_lines,
+            final_newline: format.final_newline,
+        },
+        format.formatters.clone(),
+    )
+}
+
+/// Where files were written and the rendered hooks of the templates that produced them.
+struct Generated {
+    root: FsPathBuf,
+    hooks: Vec<String>,
+    /// Markdown files that declared the hooks.
+    sources: Vec<String>,
+}
+
+/// Render and write a file template; returns `None` when printing instead.
 fn handle_file(
     renderer: &TemplateRenderer,
+    formatter: &OutputFormatter,
     file_ref: CatalogFileRef<'_>,
     out_dir: Option<FsPathBuf>,
     print: bool,
+    conflict: Conflict,
     context: &JsonMap<String, JsonValue>,
-) -> Result<()> {
+) -> Result<Option<Generated>> {
     let template = match file_ref {
         CatalogFileRef::Standalone(t) => t,
         CatalogFileRef::TreeMember { file, .. } => file,
     };
 
-    let rendered = render_template_file(renderer, template, context)?;
+    if let Some(each) = &template.each {
+        bail!(
+            "Template '{}' repeats for each element of '{}'; generate its tree instead.",
+            template.id,
+            each.arg
+        );
+    }
+
+    let context = renderer.with_env(&template.id, &template.location, context)?;
+    let mut rendered = renderer.render_file(template, &context)?;
+    formatter.format_file(template, &mut rendered)?;
 
     if print {
-        print!("{}", rendered.content);
+        io::stdout().write_all(&output_bytes(&rendered)?)?;
         io::stdout().flush()?;
-        return Ok(());
+        return Ok(None);
     }
 
     let root = out_dir.clone().ok_or_else(|| {
@@ -112,34 +241,64 @@ fn handle_file(
             template.id
         )
     })?;
-    let target = root.join(rel);
-    if let Some(parent) = target.parent() {
-        fs::create_dir_all(parent)?;
-    }
-    fs::write(&target, rendered.content)?;
-    println!("Wrote {}", target.display());
-    Ok(())
+    write_output(&root.join(rel), &rendered, conflict)?;
+
+    let hooks = rendered.hooks.unwrap_or_default();
+    let sources = if hooks.is_empty() {
+        Vec::new()
+    } else {
+        vec![template.location.path.clone()]
+    };
+    Ok(Some(Generated {
+        root,
+        hooks,
+        sources,
+    }))
 }
 
 fn handle_tree(
     renderer: &TemplateRenderer,
+    formatter: &OutputFormatter,
     tree: &CatalogTree,
     out_dir: Option<FsPathBuf>,
     print: bool,
+    conflict: Conflict,
     context: &JsonMap<String, JsonValue>,
-) -> Result<()> {
-    let rendered_files: Vec<OutputFile> = tree
-        .files()
-        .iter()
-        .map(|file| render_template_file(renderer, file, context))
-        .collect::<Result<_>>()?;
+) -> Result<Option<Generated>> {
+    let mut rendered_files: Vec<OutputFile> = Vec::new();
+    let mut hooks: Vec<String> = Vec::new();
+    let mut sources: Vec<String> = Vec::new();
+    for file in tree.all_files() {
+        let mut rendered = renderer.render_tree_file(file, context)?;
+        for output in &mut rendered {
+            formatter.format_file(file, output)?;
+        }
+        for output in &rendered {
+            if let Some(file_hooks) = &output.hooks {
+                push_hooks(&mut hooks, file_hooks.iter().cloned());
+                push_hooks(&mut sources, [file.location.path.clone()]);
+            }
+        }
+        rendered_files.extend(rendered);
+    }
+    for subtree in tree.descendants() {
+        let context = renderer.with_env(subtree.id(), subtree.location(), context)?;
+        let tree_hooks = renderer
+            .render_hooks(subtree.id(), subtree.hooks(), &context)
+            .with_context(|| format!("Failed to render hooks for tree '{}'", subtree.id()))?;
+        if !tree_hooks.is_empty() {
+            push_hooks(&mut hooks, tree_hooks);
+            push_hooks(&mut sources, [subtree.location().path.clone()]);
+        }
+    }
 
     if print {
         let out = OutputTree {
             files: rendered_files.clone(),
+            hooks: (!hooks.is_empty()).then(|| hooks.clone()),
         };
         println!("{}", serde_json::to_string_pretty(&out)?);
-        return Ok(());
+        return Ok(None);
     }
 
     let root = out_dir.clone().ok_or_else(|| {
@@ -162,53 +321,150 @@ fn handle_tree(
                 tree.id()
             )
         })?;
-        let target = root.join(rel);
-        if let Some(parent) = target.parent() {
-            fs::create_dir_all(parent)?;
+        write_output(&root.join(rel), &rendered, conflict)?;
+    }
+
+    Ok(Some(Generated {
+        root,
+        hooks,
+        sources,
+    }))
+}
+
+/// Runs the hooks of generated templates and the project config in the output directory.
+struct HookRunner<'a> {
+    project: &'a Project,
+    enabled: bool,
+}
+
+impl HookRunner<'_> {
+    fn run(&self, generated: Option<Generated>) -> Result<()> {
+        let Some(generated) = generated else {
+            return Ok(());
+        };
+        let mut commands = generated.hooks;
+        let template_hooks = !commands.is_empty();
+        push_hooks(
+            &mut commands,
+            self.project.config.user.hooks.run.iter().cloned(),
+        );
+
+        if commands.is_empty() {
+            return Ok(());
+        }
+        if !self.enabled {
+            println!("Skipped {} hook(s) (--no-hooks)", commands.len());
+            return Ok(());
+        }
+        let untrusted: Vec<String> = generated
+            .sources
+            .iter()
+            .filter(|source| !self.project.is_trusted_source(Path::new(source)))
+            .cloned()
+            .collect();
+        // One answer covers every hook, so declining runs none of the project's either
+        if template_hooks
+            && !untrusted.is_empty()
+            && !UiHooks::confirm_untrusted(&untrusted, &commands)?
+        {
+            UiMessage::warn(&format!(
+                "Skipped {} hook(s) declared with templates from outside the project",
+                commands.len()
+            ));
+            return Ok(());
+        }
+        for command in &commands {
+            run_hook(command, &generated.root)?;
         }
-        fs::write(&target, rendered.content)?;
-        println!("Wrote {}", target.display());
+        Ok(())
     }
+}
 
+/// Run a hook through the platform shell in `dir`, failing when it exits unsuccessfully.
+fn run_hook(command: &str, dir: &Path) -> Result<()> {
+    println!("Running {command}");
+    let status = shell_command(command)
+        .current_dir(dir)
+        .status()
+        .with_context(|| format!("Failed to run hook '{command}'"))?;
+    if !status.success() {
+        bail!("Hook '{command}' failed with {status}");
+    }
     Ok(())
 }
 
-fn render_template_file(
-    renderer: &TemplateRenderer,
-    template: &TemplateFile,
-    context: &JsonMap<String, JsonValue>,
-) -> Result<OutputFile> {
-    let missing: Vec<String> = template
-        .args
-        .iter()
-        .filter(|arg| arg.required && !context.contains_key(&arg.name))
-        .map(|arg| arg.name.clone())
-        .collect();
-    if !missing.is_empty() {
-        bail!(
-            "Missing required argument(s) {} for template '{}'.",
-            missing.join(", "),
-            template.id
+/// Write a rendered file, patching or merging into the existing file when it has one.
+fn write_output(target: &Path, rendered: &OutputFile, conflict: Conflict) -> Result<()> {
+    if let Some(patch) = &rendered.patch {
+        let Some(existing) = read_existing(target, rendered)? else {
+            bail!(
+                "Cannot patch '{}': the file does not exist.",
+                target.display()
+            );
+        };
+        let patched = apply_patch(&existing, patch, &rendered.content)
+            .with_context(|| format!("Failed to patch '{}'", target.display()))?;
+        return write_changed(target, patched, "Patched");
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if target.exists() {
+        let merge = match (&rendered.merge, conflict) {
+            (Some(merge), _) => Some(merge.clone()),
+            (None, Conflict::Merge) => Some(TemplateMerge {
+                arrays: TemplateMergeArrays::AppendUnique(TemplateMergeArraysAppendUnique),
+            }),
+            (None, Conflict::Skip) => {
+                println!("Skipped {}", target.display());
+                return Ok(());
+            }
+            (None, Conflict::Fail) => bail!(
+                "'{}' already exists; pass --conflict to overwrite, skip or merge existing files.",
+                target.display()
+            ),
+            (None, Conflict::Overwrite) => None,
+        };
+        let format = MergeFormat::detect(
+            rendered.lang.as_deref(),
+            target.file_name().and_then(|name| name.to_str()),
         );
+        if let (Some(merge), Some(format)) = (merge, format) {
+            let existing = read_existing(target, rendered)?.unwrap_or_default();
+            let merged = merge_document(format, &existing, &rendered.content, &merge)
+                .with_context(|| format!("Failed to merge into '{}'", target.display()))?;
+            return write_changed(target, merged, "Merged");
+        }
     }
 
-    let content = renderer
-        .render_map(&template.id, &template.content, context)
-        .with_context(|| format!("Failed to render content for template '{}'", template.id))?;
-    let path = match &template.path {
-        Some(path_tpl) => Some(
-            renderer
-                .render_map(&format!("{}::path", template.id), path_tpl, context)
-                .with_context(|| format!("Failed to render path for template '{}'", template.id))?,
-        ),
-        None => None,
-    };
+    fs::write(target, output_bytes(rendered)?)?;
+    println!("Wrote {}", target.display());
+    Ok(())
+}
 
-    Ok(OutputFile {
-        path,
-        lang: template.lang.clone(),
-        content,
-    })
+/// Read the text of the file a patch or merge applies to, if it exists.
+fn read_existing(target: &Path, rendered: &OutputFile) -> Result<Option<String>> {
+    if rendered.encoding.is_some() {
+        bail!("Cannot update '{}' with binary content.", target.display());
+    }
+    match fs::read_to_string(target) {
+        Ok(existing) => Ok(Some(existing)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read '{}'", target.display())),
+    }
+}
+
+fn write_changed(target: &Path, content: Option<String>, verb: &str) -> Result<()> {
+    match content {
+        Some(content) => {
+            fs::write(target, content)?;
+            println!("{} {}", verb, target.display());
+        }
+        None => println!("Unchanged {}", target.display()),
+    }
+    Ok(())
 }
 
 fn build_context_map(pairs: &[String]) -> Result<JsonMap<String, JsonValue>> {
@@ -255,6 +511,10 @@ fn parse_value(raw: &str) -> JsonValue {
         JsonValue::Bool(false)
     } else if raw.eq_ignore_ascii_case("null") {
         JsonValue::Null
+    } else if (raw.starts_with('[') || raw.starts_with('{'))
+        && let Ok(value) = serde_json::from_str::<JsonValue>(raw)
+    {
+        value
     } else if let Ok(int) = raw.parse::<i64>() {
         JsonValue::Number(JsonNumber::from(int))
     } else if let Ok(float) = raw.parse::<f64>() {
@@ -269,6 +529,7 @@ fn parse_value(raw: &str) -> JsonValue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use nmcr_types::{TemplatePatch, TemplatePatchOp, TemplatePatchOpAfter, TemplatePatchOpAppend};
 
     #[test]
     fn parse_arg_pair_supports_scalars() {
@@ -286,6 +547,18 @@ mod tests {
         assert_eq!(null_value, JsonValue::Null);
     }
 
+    #[test]
+    fn parse_arg_pair_supports_json_collections() {
+        let (_, array) = parse_arg_pair(r#"items=["a", "b"]"#).expect("parse array");
+        assert_eq!(array, serde_json::json!(["a", "b"]));
+
+        let (_, object) = parse_arg_pair(r#"author={"name": "Sasha"}"#).expect("parse object");
+        assert_eq!(object, serde_json::json!({"name": "Sasha"}));
+
+        let (_, text) = parse_arg_pair("note=[draft").expect("parse string");
+        assert_eq!(text, JsonValue::String("[draft".into()));
+    }
+
     #[test]
     fn parse_arg_pair_requires_equals() {
         let err = parse_arg_pair("invalid").expect_err("should error");
@@ -299,4 +572,146 @@ mod tests {
         assert_eq!(out, FsPathBuf::from("./out"));
         assert_eq!(pairs, vec!["name=app".to_string()]);
     }
+
+    #[test]
+    fn write_output_fails_to_patch_a_missing_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let target = dir.path().join("src/lib.rs");
+        let rendered = OutputFile {
+            path: Some("./src/lib.rs".into()),
+            lang: Some("rust".into()),
+            content: "mod b;\n".into(),
+            encoding: None,
+            patch: Some(TemplatePatch {
+                op: TemplatePatchOp::Append(TemplatePatchOpAppend),
+                anchor: None,
+                start: None,
+                end: None,
+            }),
+            merge: None,
+            hooks: None,
+        };
+        let err = write_output(&target, &rendered, Conflict::Fail).expect_err("missing target");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Cannot patch '{}': the file does not exist.",
+                target.display()
+            )
+        );
+        assert!(!dir.path().join("src").exists());
+    }
+
+    #[test]
+    fn write_output_applies_patches_once() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let target = dir.path().join("src/lib.rs");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&target, "mod a;\n\npub fn run() {}\n").unwrap();
+
+        let rendered = OutputFile {
+            path: Some("./src/lib.rs".into()),
+            lang: Some("rust".into()),
+            content: "mod b;\n".into(),
+            encoding: None,
+            patch: Some(TemplatePatch {
+                op: TemplatePatchOp::After(TemplatePatchOpAfter),
+                anchor: Some("^mod ".into()),
+                start: None,
+                end: None,
+            }),
+            merge: None,
+            hooks: None,
+        };
+        write_output(&target, &rendered, Conflict::Fail).expect("patched");
+        write_output(&target, &rendered, Conflict::Fail).expect("unchanged");
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "mod a;\nmod b;\n\npub fn run() {}\n"
+        );
+    }
+
+    #[test]
+    fn write_output_follows_conflict_policy() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let target = dir.path().join("package.json");
+        fs::write(&target, "{\"name\": \"app\", \"files\": [\"dist\"]}").unwrap();
+        let rendered = OutputFile {
+            path: Some("./package.json".into()),
+            lang: Some("json".into()),
+            content: "{\"name\": \"web\", \"files\": [\"src\"]}".into(),
+            encoding: None,
+            patch: None,
+            merge: None,
+            hooks: None,
+        };
+
+        let err = write_output(&target, &rendered, Conflict::Fail).expect_err("exists");
+        assert!(err.to_string().contains("already exists"));
+        write_output(&target, &rendered, Conflict::Skip).expect("skipped");
+        assert!(fs::read_to_string(&target).unwrap().contains("\"app\""));
+
+        write_output(&target, &rendered, Conflict::Merge).expect("merged");
+        // Like the existing file, the merged one has no final newline
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            "{\n  \"name\": \"web\",\n  \"files\": [\n    \"dist\",\n    \"src\"\n  ]\n}"
+        );
+
+        write_output(&target, &rendered, Conflict::Overwrite).expect("overwritten");
+        assert_eq!(fs::read_to_string(&target).unwrap(), rendered.content);
+    }
+
+    #[test]
+    fn declining_untrusted_hooks_skips_project_hooks() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            Config::join_path(dir.path()),
+            "[hooks]\nrun = [\"echo project> project.txt\"]\n",
+        )
+        .unwrap();
+        let project = Project::from_config(Config::find(Some(dir.path()), None).unwrap());
+        let outside = tempfile::tempdir().expect("tempdir");
+        let source = outside.path().join("remote.md");
+        fs::write(&source, "# Remote\n").unwrap();
+        let runner = HookRunner {
+            project: &project,
+            enabled: true,
+        };
+        let generated = |sources: Vec<String>| Generated {
+            root: dir.path().to_path_buf(),
+            hooks: vec!["echo template> template.txt".into()],
+            sources,
+        };
+
+        // Without a terminal to confirm on, untrusted hooks count as declined
+        runner
+            .run(Some(generated(vec![source.display().to_string()])))
+            .expect("hooks skipped");
+        assert!(!dir.path().join("template.txt").exists());
+        assert!(!dir.path().join("project.txt").exists());
+
+        let local = dir.path().join("local.md");
+        fs::write(&local, "# Local\n").unwrap();
+        runner
+            .run(Some(generated(vec![local.display().to_string()])))
+            .expect("hooks run");
+        assert!(dir.path().join("template.txt").exists());
+        assert!(dir.path().join("project.txt").exists());
+    }
+
+    #[test]
+    fn run_hook_runs_in_the_output_directory() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        run_hook("echo done> hook.txt", dir.path()).expect("hook runs");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("hook.txt"))
+                .unwrap()
+                .trim(),
+            "done"
+        );
+
+        let err = run_hook("exit 3", dir.path()).expect_err("hook fails");
+        assert!(err.to_string().starts_with("Hook 'exit 3' failed"));
+    }
 }
@@ -24,7 +24,7 @@ impl InitCmd {
         let templates_glob = UiConfig::inquire_templates_glob()?;
 
         let mut config = Config::init(&path, args.local.force)?;
-        config.user.templates = templates_glob;
+        config.user.templates = ConfigGlobs::from(templates_glob);
 
         config.write()?;
 
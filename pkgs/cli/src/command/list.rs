@@ -1,6 +1,9 @@
 use crate::prelude::*;
-use nmcr_catalog::{CatalogTree, TemplateCatalog};
+use anyhow::Context;
+use nmcr_catalog::{CatalogTree, TemplateCatalog, TemplateOrigin};
+use nmcr_template::{describe_merge, describe_patch};
 use nmcr_types::{Arg, ArgKind, Location, TemplateFile};
+use nmcr_types_internal::TemplateLayer;
 use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
@@ -12,7 +15,11 @@ fn spaces(count: usize) -> String {
 }
 
 #[derive(Args, Debug)]
-pub struct ListArgs {}
+pub struct ListArgs {
+    /// List the templates of every workspace member, namespaced by member
+    #[arg(long)]
+    pub workspace: bool,
+}
 
 #[derive(Args)]
 pub struct ListCmd {}
@@ -20,9 +27,26 @@ pub struct ListCmd {}
 impl ListCmd {
     pub async fn run(args: &CliCommandProject<ListArgs>) -> Result<()> {
         let project = args.load_project()?;
-        let project_root = project.path();
-        let paths = project.template_paths()?;
-        let catalog = TemplateCatalog::load(&paths)?;
+        let (project_root, sources, helper_paths) = if args.local.workspace {
+            let workspace = Workspace::find(&project)?.with_context(|| {
+                format!(
+                    "{} is not part of a workspace; list its members under [workspace] in the root {CONFIG_FILENAME}",
+                    project.path().display()
+                )
+            })?;
+            (
+                workspace.root.path(),
+                workspace.template_sources()?,
+                workspace.helper_paths()?,
+            )
+        } else {
+            (
+                project.path(),
+                project.template_sources()?,
+                project.helper_paths()?,
+            )
+        };
+        let catalog = TemplateCatalog::load_sources_with_helpers(&sources, &helper_paths)?;
 
         if catalog.is_empty() {
             println!("No templates found.");
@@ -57,8 +81,8 @@ fn render_catalog_to_writer<W: Write>(
         }
 
         match entry {
-            RootEntry::Tree(tree) => render_tree(out, tree, &mut resolver)?,
-            RootEntry::File(file) => render_file_entry(out, file, &mut resolver, 0)?,
+            RootEntry::Tree(tree) => render_tree(out, catalog, tree, &mut resolver, 0)?,
+            RootEntry::File(file) => render_file_entry(out, catalog, file, &mut resolver, 0)?,
         }
     }
 
@@ -73,17 +97,29 @@ enum RootEntry<'a> {
 
 fn render_tree<W: Write>(
     out: &mut W,
+    catalog: &TemplateCatalog,
     tree: &CatalogTree,
     resolver: &mut LocationResolver,
+    indent: usize,
 ) -> io::Result<()> {
     let location = format_location(resolver, tree.location());
-    writeln!(out, "📁 {} ({})", tree.id(), location)?;
-
-    let base_indent = 1;
+    writeln!(
+        out,
+        "{}📁 {} ({}){}",
+        spaces(indent),
+        tree.id(),
+        location,
+        format_origin(catalog, tree.id())
+    )?;
+
+    let base_indent = indent + 1;
     let content_indent = spaces(base_indent + CONTENT_OFFSET);
     writeln!(out, "{}->", content_indent)?;
 
     let _ = print_tree_structure(out, tree, base_indent + CONTENT_OFFSET, resolver)?;
+    for hook in tree.hooks() {
+        writeln!(out, "{}run: {}", content_indent, hook)?;
+    }
     writeln!(out)?;
 
     let description = clean_description(tree.description());
@@ -102,31 +138,58 @@ fn render_tree<W: Write>(
     }
 
     if tree.files().is_empty() {
-        writeln!(out, "{}Files: (none)", content_indent)?;
+        if tree.subtrees().is_empty() {
+            writeln!(out, "{}Files: (none)", content_indent)?;
+        }
     } else {
         writeln!(out, "{}Files:", content_indent)?;
         writeln!(out)?;
         let child_indent = base_indent + CONTENT_OFFSET;
         for (idx, file) in tree.files().iter().enumerate() {
-            render_file_entry(out, file, resolver, child_indent)?;
+            render_file_entry(out, catalog, file, resolver, child_indent)?;
             if idx + 1 < tree.files().len() {
                 writeln!(out)?;
             }
         }
     }
 
+    if !tree.subtrees().is_empty() {
+        if !tree.files().is_empty() {
+            writeln!(out)?;
+        }
+        writeln!(out, "{}Subtrees:", content_indent)?;
+        for subtree in tree.subtrees() {
+            writeln!(out)?;
+            render_tree(
+                out,
+                catalog,
+                subtree,
+                resolver,
+                base_indent + CONTENT_OFFSET,
+            )?;
+        }
+    }
+
     Ok(())
 }
 
 fn render_file_entry<W: Write>(
     out: &mut W,
+    catalog: &TemplateCatalog,
     file: &TemplateFile,
     resolver: &mut LocationResolver,
     base_indent: usize,
 ) -> io::Result<()> {
     let indent = spaces(base_indent);
     let location = format_location(resolver, &file.location);
-    writeln!(out, "{}📄 {} ({})", indent, file.id, location)?;
+    writeln!(
+        out,
+        "{}📄 {} ({}){}",
+        indent,
+        file.id,
+        location,
+        format_origin(catalog, &file.id)
+    )?;
 
     let detail_indent = spaces(base_indent + CONTENT_OFFSET + 1);
     let path_display = match file.path.as_deref() {
@@ -138,6 +201,30 @@ fn render_file_entry<W: Write>(
         arrow_line.push_str(&format!(" ({})", lang));
     }
     writeln!(out, "{}", arrow_line)?;
+    if let Some(condition) = file.condition.as_deref() {
+        writeln!(out, "{}   when: {}", detail_indent, condition)?;
+    }
+    if let Some(each) = file.each.as_ref() {
+        writeln!(
+            out,
+            "{}   each: {} as {}",
+            detail_indent, each.arg, each.binding
+        )?;
+    }
+    if let Some(patch) = file.patch.as_ref() {
+        writeln!(out, "{}   patch: {}", detail_indent, describe_patch(patch))?;
+    }
+    if let Some(merge) = file.merge.as_ref() {
+        writeln!(out, "{}   merge: {}", detail_indent, describe_merge(merge))?;
+    }
+    if let Some(asset) = file.asset.as_deref() {
+        writeln!(out, "{}   asset: {}", detail_indent, asset)?;
+    } else if file.raw == Some(true) {
+        writeln!(out, "{}   verbatim", detail_indent)?;
+    }
+    for hook in &file.hooks {
+        writeln!(out, "{}   run: {}", detail_indent, hook)?;
+    }
 
     let description = clean_description(&file.description);
     let has_description = description
@@ -214,7 +301,7 @@ fn write_path_node<W: Write>(
 
 fn build_path_tree(tree: &CatalogTree) -> PathNode {
     let mut root = PathNode::default();
-    for file in tree.files() {
+    for file in tree.all_files() {
         if let Some(path) = file.path.as_deref() {
             let normalized = path.replace('\\', "/");
             let trimmed = normalized
@@ -263,14 +350,19 @@ fn format_argument(arg: &Arg) -> String {
         ArgKind::Boolean(_) => "boolean",
         ArgKind::String(_) => "string",
         ArgKind::Number(_) => "number",
+        ArgKind::Array(_) => "array",
+        ArgKind::Object(_) => "object",
         ArgKind::Any(_) => "string",
     };
 
-    if arg.description.trim().is_empty() {
-        format!("{} [{}]", arg.name, arg_type)
-    } else {
-        format!("{} [{}]: {}", arg.name, arg_type, arg.description.trim())
+    let mut line = format!("{} [{}]", arg.name, arg_type);
+    if let Some(expression) = &arg.derived {
+        line.push_str(&format!(" = {}", expression));
+    }
+    if !arg.description.trim().is_empty() {
+        line.push_str(&format!(": {}", arg.description.trim()));
     }
+    line
 }
 
 fn clean_description(input: &str) -> Option<String> {
@@ -321,6 +413,22 @@ fn is_inline_path_marker(line: &str) -> bool {
     false
 }
 
+/// Tag templates that don't come from the project itself or that replace a template from a
+/// lower layer.
+fn format_origin(catalog: &TemplateCatalog, id: &str) -> String {
+    match catalog.origin(id) {
+        Some(TemplateOrigin {
+            layer,
+            overrides: Some(replaced),
+        }) => format!(" [{layer}, overrides {replaced}]"),
+        Some(TemplateOrigin {
+            layer,
+            overrides: None,
+        }) if layer != TemplateLayer::Local => format!(" [{layer}]"),
+        _ => String::new(),
+    }
+}
+
 fn format_path(path: &str) -> String {
     if path.is_empty() {
         "[no path]".to_string()
@@ -421,6 +529,11 @@ struct LocationResolver {
 
 impl LocationResolver {
     fn new(root: PathBuf) -> Self {
+        let root = if root.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            root
+        };
         let canonical_root = root.canonicalize().unwrap_or(root);
         let cwd = std::env::current_dir().unwrap_or_else(|_| canonical_root.clone());
         let canonical_cwd = cwd.canonicalize().unwrap_or(cwd);
@@ -549,6 +662,7 @@ impl LocationResolver {
 mod tests {
     use super::*;
     use insta::assert_snapshot;
+    use nmcr_types_internal::TemplateSource;
     use std::path::PathBuf;
 
     #[test]
@@ -621,4 +735,44 @@ mod tests {
    - name [string]: Component name.
 "###);
     }
+
+    #[test]
+    fn tags_templates_from_other_layers() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let shared = dir.path().join("shared.md");
+        let local = dir.path().join("local.md");
+        std::fs::write(
+            &shared,
+            "# Readme\n\n```md\nshared\n```\n\n# License\n\n```text\nMIT\n```\n",
+        )
+        .expect("write shared");
+        std::fs::write(&local, "# Readme\n\n```md\nlocal\n```\n").expect("write local");
+        let catalog = TemplateCatalog::load_sources(&[
+            TemplateSource {
+                layer: TemplateLayer::Source,
+                namespace: None,
+                paths: vec![shared],
+                out: None,
+            },
+            TemplateSource {
+                layer: TemplateLayer::Local,
+                namespace: None,
+                paths: vec![local],
+                out: None,
+            },
+        ])
+        .expect("catalog loads");
+        let mut buffer: Vec<u8> = Vec::new();
+        render_catalog_to_writer(&catalog, dir.path(), &mut buffer).expect("render succeeds");
+
+        let output = String::from_utf8(buffer).expect("utf8 output");
+        assert!(
+            output.contains("📄 readme (./local.md:1) [local, overrides source]"),
+            "unexpected output: {output}"
+        );
+        assert!(
+            output.contains("📄 license (./shared.md:7) [source]"),
+            "unexpected output: {output}"
+        );
+    }
 }
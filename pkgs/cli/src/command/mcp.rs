@@ -2,7 +2,11 @@ use crate::prelude::*;
 use nmcr_mcp::prelude::*;
 
 #[derive(Args, Debug, Default)]
-pub struct McpArgs {}
+pub struct McpArgs {
+    /// Fail unless template sources match an up-to-date ncmr.lock
+    #[arg(long)]
+    pub locked: bool,
+}
 
 #[derive(Args)]
 pub struct McpCmd;
@@ -10,6 +14,23 @@ pub struct McpCmd;
 impl McpCmd {
     pub async fn run(args: &CliCommandProject<McpArgs>) -> Result<()> {
         let project = args.load_project()?;
-        McpProtocolStdio.run(&project).await
+        // stdout carries the protocol, so warn on stderr
+        let mut warnings: Vec<String> = project
+            .verify_lock(args.local.locked)?
+            .warning()
+            .into_iter()
+            .collect();
+        // A workspace root serves the templates of all its members
+        let workspace = project
+            .is_workspace()
+            .then(|| Workspace::load(project.clone()))
+            .transpose()?;
+        if let Some(workspace) = &workspace {
+            warnings.extend(workspace.lock_warnings(args.local.locked)?);
+        }
+        for warning in warnings {
+            eprintln!("{}", UiTheme::format_warn(&warning));
+        }
+        McpProtocolStdio.run(&project, workspace.as_ref()).await
     }
 }
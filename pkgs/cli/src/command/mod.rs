@@ -12,6 +12,9 @@ pub use mcp::*;
 mod list;
 pub use list::*;
 
+mod sources;
+pub use sources::*;
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Initialize a new nmcr project in an existing directory
@@ -25,6 +28,9 @@ pub enum Command {
 
     /// List available templates grouped by tree membership.
     List(CliCommandProject<ListArgs>),
+
+    /// Manage template sources fetched from git repositories.
+    Sources(CliCommandProject<SourcesArgs>),
 }
 
 impl Command {
@@ -38,6 +44,8 @@ impl Command {
 
             Some(Command::List(args)) => Ok(ListCmd::run(args).await?),
 
+            Some(Command::Sources(args)) => Ok(SourcesCmd::run(args).await?),
+
             None => unreachable!("No command was provided"),
         }
     }
@@ -0,0 +1,47 @@
+use crate::prelude::*;
+
+#[derive(Args, Debug)]
+pub struct SourcesArgs {
+    #[command(subcommand)]
+    pub command: SourcesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SourcesCommand {
+    /// Fetch git template sources into the local cache, check out their revisions and
+    /// refresh ncmr.lock.
+    Update {
+        /// Check out the commits pinned in ncmr.lock instead, failing if it is missing or
+        /// out of date
+        #[arg(long)]
+        locked: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct SourcesCmd {}
+
+impl SourcesCmd {
+    pub async fn run(args: &CliCommandProject<SourcesArgs>) -> Result<()> {
+        let project = args.load_project()?;
+        match args.local.command {
+            SourcesCommand::Update { locked } => {
+                let sources = &project.config.user.sources;
+                if sources.is_empty() {
+                    println!("No template sources configured.");
+                    return Ok(());
+                }
+                let commits = project.update_sources(locked)?;
+                for (source, commit) in sources.iter().zip(commits) {
+                    let rev = source.rev.as_deref().unwrap_or("HEAD");
+                    println!(
+                        "Updated {} ({rev}) to {}",
+                        source.git,
+                        commit.get(..12).unwrap_or(&commit)
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
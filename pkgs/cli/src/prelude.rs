@@ -6,7 +6,7 @@ pub use anyhow::Result;
 pub use clap::{Args, Parser, Subcommand};
 pub use console::{StyledObject, style};
 pub use dialoguer::{
-    Input,
+    Confirm, Input,
     theme::{ColorfulTheme, Theme},
 };
 pub use indicatif::{ProgressBar, ProgressStyle};
@@ -0,0 +1,25 @@
+use crate::prelude::*;
+
+pub struct UiHooks {}
+
+impl UiHooks {
+    /// Ask before running hooks declared by templates from outside the project. Declines
+    /// without asking when nobody is at the terminal.
+    pub fn confirm_untrusted(sources: &[String], commands: &[String]) -> Result<bool> {
+        if !console::user_attended() {
+            return Ok(false);
+        }
+        println!("Templates from outside the project declare hooks:");
+        for source in sources {
+            println!("  {}", style(source).dim());
+        }
+        for command in commands {
+            println!("  $ {command}");
+        }
+        let confirmed = Confirm::with_theme(UiTheme::for_dialoguer())
+            .with_prompt("Run these hooks?")
+            .default(false)
+            .interact()?;
+        Ok(confirmed)
+    }
+}
@@ -6,3 +6,6 @@ pub use theme::*;
 
 mod config;
 pub use config::*;
+
+mod hooks;
+pub use hooks::*;
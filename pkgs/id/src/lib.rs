@@ -1,5 +1,12 @@
 //! Deterministic identifier utilities for nmcr entities.
 
+/// Separator between a source namespace and a template id: `company::rust_crate`.
+pub const NAMESPACE_SEPARATOR: &str = "::";
+
+/// Separator replacing [`NAMESPACE_SEPARATOR`] in tool names. Ids never contain double
+/// underscores, so the tool name maps back to a single id.
+pub const TOOL_NAMESPACE_SEPARATOR: &str = "__";
+
 /// Generates deterministic identifiers for hierarchical entities.
 #[derive(Debug, Default, Clone, Copy)]
 pub struct EntityId;
@@ -56,6 +63,38 @@ impl EntityId {
 
         out
     }
+
+    /// Prefix an id with the namespace of the source that declares it.
+    pub fn qualify(namespace: &str, id: &str) -> String {
+        format!("{namespace}{NAMESPACE_SEPARATOR}{id}")
+    }
+
+    /// Split an id into its namespace, if qualified, and the id within that namespace. A
+    /// nested namespace stays whole: `web::acme::readme` splits into `web::acme` and `readme`.
+    pub fn split(id: &str) -> (Option<&str>, &str) {
+        match id.rsplit_once(NAMESPACE_SEPARATOR) {
+            Some((namespace, local)) => (Some(namespace), local),
+            None => (None, id),
+        }
+    }
+
+    /// Namespaces enclosing an id, innermost first: `web::acme`, then `web`.
+    pub fn namespaces(id: &str) -> impl Iterator<Item = &str> {
+        std::iter::successors(Self::split(id).0, |namespace| Self::split(namespace).0)
+    }
+
+    /// Whether `query` names the id in full or by its trailing segments, as `readme` and
+    /// `acme::readme` both name `web::acme::readme`.
+    pub fn matches(id: &str, query: &str) -> bool {
+        id.strip_suffix(query).is_some_and(|namespace| {
+            namespace.is_empty() || namespace.ends_with(NAMESPACE_SEPARATOR)
+        })
+    }
+
+    /// Sanitized form of an id for MCP tool names, which only allow `[A-Za-z0-9_-]`.
+    pub fn tool_name(id: &str) -> String {
+        id.replace(NAMESPACE_SEPARATOR, TOOL_NAMESPACE_SEPARATOR)
+    }
 }
 
 #[cfg(test)]
@@ -98,4 +137,27 @@ mod tests {
         assert_eq!(id, "api_client_http");
         assert!(!id.contains("__"));
     }
+
+    #[test]
+    fn qualifies_ids_with_namespaces() {
+        let id = EntityId::qualify("company", "rust_crate");
+        assert_eq!(id, "company::rust_crate");
+        assert_eq!(EntityId::split(&id), (Some("company"), "rust_crate"));
+        assert_eq!(EntityId::split("rust_crate"), (None, "rust_crate"));
+        assert_eq!(
+            EntityId::split("web::acme::readme"),
+            (Some("web::acme"), "readme")
+        );
+        assert_eq!(
+            EntityId::namespaces("web::acme::readme").collect::<Vec<_>>(),
+            ["web::acme", "web"]
+        );
+        assert!(EntityId::matches("web::acme::readme", "readme"));
+        assert!(EntityId::matches("web::acme::readme", "acme::readme"));
+        assert!(EntityId::matches("readme", "readme"));
+        assert!(!EntityId::matches("web::acme::readme", "me::readme"));
+        assert!(!EntityId::matches("web::acme::readme", "me"));
+        assert_eq!(EntityId::tool_name(&id), "company__rust_crate");
+        assert_eq!(EntityId::tool_name("rust_crate"), "rust_crate");
+    }
 }
@@ -1,6 +1,6 @@
 pub use crate::*;
 pub use anyhow::{Context, Result, anyhow};
-pub use nmcr_project::prelude::Project;
+pub use nmcr_project::prelude::{Project, Workspace};
 pub use nmcr_types::*;
 pub use rmcp::{
     ErrorData as McpError, ServiceExt,
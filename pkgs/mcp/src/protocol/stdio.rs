@@ -1,4 +1,6 @@
 use crate::prelude::*;
+use nmcr_project::prelude::TemplateSource;
+use nmcr_template::{Normalizers, OutputFormatter};
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct McpProtocolStdio;
@@ -8,15 +10,25 @@ impl McpProtocolStdio {
         Self
     }
 
-    pub async fn run(&self, project: &Project) -> Result<()> {
-        let template_paths = project
-            .template_paths()
-            .with_context(|| "Failed to collect template files from project")?;
-
-        let catalog = TemplateCatalog::load(&template_paths)?;
+    /// Serve the project's templates, or those of every member when `workspace` is the one
+    /// rooted at the project.
+    pub async fn run(&self, project: &Project, workspace: Option<&Workspace>) -> Result<()> {
+        let mut catalog = match workspace {
+            Some(workspace) => load_workspace_catalog(workspace)?,
+            None => {
+                let template_sources = project
+                    .template_sources()
+                    .with_context(|| "Failed to collect template files from project")?;
+                load_catalog(project, &template_sources, &project.helper_paths()?)?
+            }
+        };
         if catalog.is_empty() {
             return Err(anyhow!("No templates found in the project"));
         }
+        catalog.expose(&project.config.user.mcp);
+        if catalog.is_empty() {
+            return Err(anyhow!("No templates match the MCP expose patterns"));
+        }
 
         let mut tool_router = ToolRouter::new();
         for tool in catalog.file_tools() {
@@ -33,6 +45,43 @@ impl McpProtocolStdio {
     }
 }
 
+/// Tools of every member, each rendering with its member's defaults and formatting.
+fn load_workspace_catalog(workspace: &Workspace) -> Result<TemplateCatalog> {
+    let helper_paths = workspace.helper_paths()?;
+    let mut catalog = TemplateCatalog::default();
+    for member in &workspace.members {
+        let sources = member.template_sources()?;
+        catalog.extend(load_catalog(&member.project, &sources, &helper_paths)?);
+    }
+    Ok(catalog)
+}
+
+fn load_catalog(
+    project: &Project,
+    sources: &[TemplateSource],
+    helper_paths: &[PathBuf],
+) -> Result<TemplateCatalog> {
+    let format = &project.config.user.format;
+    let formatter = OutputFormatter::new(
+        Normalizers {
+            trim_trailing_whitespace: format.trim_trailing_whitespace,
+            collapse_blank_lines: format.collapse_blank_lines,
+            final_newline: format.final_newline,
+        },
+        format.formatters.clone(),
+    );
+    // Hooks are only ever returned to clients, never run by the server
+    let share_untrusted = project.config.user.hooks.mcp;
+    TemplateCatalog::load(
+        sources,
+        helper_paths,
+        |path| share_untrusted || project.is_trusted_source(path),
+        formatter,
+        project.defaults()?,
+        project.render_env(),
+    )
+}
+
 impl McpProtocol for McpProtocolStdio {
     fn protocol_name(&self) -> &'static str {
         "stdio"
@@ -57,3 +106,60 @@ impl McpProtocol for McpProtocolStdio {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmcr_project::prelude::Config;
+    use std::fs;
+    use std::path::Path;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn workspace_members_keep_their_own_defaults() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        write(
+            &Config::join_path(root),
+            "[workspace]\nmembers = [\"packages/*\"]\n\n[defaults]\nlicense = \"MIT\"\n",
+        );
+        for (member, license) in [("cli", "Apache-2.0"), ("web", "ISC")] {
+            let member = root.join("packages").join(member);
+            write(
+                &Config::join_path(&member),
+                &format!("templates = \"./tmpls/*.md\"\n\n[defaults]\nlicense = \"{license}\"\n"),
+            );
+            write(
+                &member.join("tmpls/license.md"),
+                "# License\n\n```\n{{license}}\n```\n",
+            );
+        }
+
+        let project = Project::load_with_global_dir(Some(root), None, None).unwrap();
+        let workspace = Workspace::load(project).unwrap();
+        let catalog = load_workspace_catalog(&workspace).unwrap();
+        let defaults: Vec<(String, JsonValue)> = catalog
+            .file_tools()
+            .iter()
+            .map(|tool| {
+                let route = tool.route::<TemplateServer>();
+                let schema = &route.attr.input_schema;
+                (
+                    tool.id().to_string(),
+                    schema["properties"]["license"]["default"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            defaults,
+            [
+                ("cli::license".to_string(), JsonValue::from("Apache-2.0")),
+                ("web::license".to_string(), JsonValue::from("ISC")),
+            ]
+        );
+    }
+}
@@ -1,25 +1,77 @@
 use super::{TreeTool, tool::TemplateTool};
 use crate::prelude::*;
 use nmcr_catalog::TemplateCatalog as SharedCatalog;
+use nmcr_project::prelude::{ConfigMcp, TemplateSource};
+use nmcr_template::{OutputFormatter, RenderEnv, RendererOptions};
+use std::collections::BTreeSet;
+use std::path::Path;
 
+#[derive(Default)]
 pub(crate) struct TemplateCatalog {
     file_tools: Vec<TemplateTool>,
     tree_tools: Vec<TreeTool>,
 }
 
 impl TemplateCatalog {
-    pub(crate) fn load(paths: &[PathBuf]) -> Result<Self> {
-        let catalog = SharedCatalog::load(paths)?;
+    /// Build the tools of the sources' templates. Tool results leave out the hooks of
+    /// templates whose source file `share_hooks` rejects.
+    pub(crate) fn load(
+        sources: &[TemplateSource],
+        helper_scripts: &[PathBuf],
+        share_hooks: impl Fn(&Path) -> bool,
+        formatter: OutputFormatter,
+        defaults: JsonMap<String, JsonValue>,
+        env: RenderEnv,
+    ) -> Result<Self> {
+        let formatter = Arc::new(formatter);
+        let defaults = Arc::new(defaults);
+        let catalog = SharedCatalog::load_sources_with_helpers(sources, helper_scripts)?;
+        let options = RendererOptions {
+            env,
+            ..Default::default()
+        };
+        let renderer = Arc::new(catalog.renderer_with_options(options, helper_scripts)?);
+        let trees = catalog.all_trees();
+        let withheld: BTreeSet<String> = catalog
+            .standalone_files()
+            .iter()
+            .chain(trees.iter().flat_map(|tree| tree.all_files()))
+            .map(|file| &file.location.path)
+            .chain(trees.iter().map(|tree| &tree.location().path))
+            .filter(|path| !share_hooks(Path::new(path)))
+            .cloned()
+            .collect();
+        let withheld = Arc::new(withheld);
 
+        // Repeated files only render as part of a tree
         let mut file_tools: Vec<TemplateTool> = Vec::new();
-        for file in catalog.standalone_files() {
-            file_tools.push(TemplateTool::from_template(file.clone()));
+        for file in catalog
+            .standalone_files()
+            .iter()
+            .filter(|file| file.each.is_none())
+        {
+            file_tools.push(
+                TemplateTool::from_template(file.clone(), renderer.clone())
+                    .withhold_hooks(withheld.clone())
+                    .formatter(formatter.clone())
+                    .defaults(defaults.clone()),
+            );
         }
         let mut tree_tools: Vec<TreeTool> = Vec::new();
-        for tree in catalog.tree_templates() {
-            tree_tools.push(TreeTool::from_tree(tree.clone()));
-            for file in tree.files() {
-                file_tools.push(TemplateTool::from_template(file.clone()));
+        for tree in trees {
+            tree_tools.push(
+                TreeTool::from_tree(tree.clone(), renderer.clone())
+                    .withhold_hooks(withheld.clone())
+                    .formatter(formatter.clone())
+                    .defaults(defaults.clone()),
+            );
+            for file in tree.files().iter().filter(|file| file.each.is_none()) {
+                file_tools.push(
+                    TemplateTool::from_template(file.clone(), renderer.clone())
+                        .withhold_hooks(withheld.clone())
+                        .formatter(formatter.clone())
+                        .defaults(defaults.clone()),
+                );
             }
         }
 
@@ -29,6 +81,18 @@ impl TemplateCatalog {
         })
     }
 
+    /// Add the tools of another catalog.
+    pub(crate) fn extend(&mut self, other: Self) {
+        self.file_tools.extend(other.file_tools);
+        self.tree_tools.extend(other.tree_tools);
+    }
+
+    /// Keep only the tools whose template id the MCP settings expose.
+    pub(crate) fn expose(&mut self, mcp: &ConfigMcp) {
+        self.file_tools.retain(|tool| mcp.exposes(tool.id()));
+        self.tree_tools.retain(|tool| mcp.exposes(tool.id()));
+    }
+
     pub(crate) fn is_empty(&self) -> bool {
         self.file_tools.is_empty() && self.tree_tools.is_empty()
     }
@@ -1,7 +1,5 @@
 mod catalog;
 pub(crate) use catalog::*;
-mod engine;
-pub(crate) use engine::*;
 mod tool;
 mod tree_tool;
 pub(crate) use tree_tool::*;
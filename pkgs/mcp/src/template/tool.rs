@@ -1,20 +1,27 @@
-use super::render_template;
 use crate::prelude::*;
-use anyhow::bail;
+use nmcr_id::EntityId;
+use nmcr_template::{
+    OutputFormatter, TemplateRenderer, apply_defaults, describe_merge, describe_patch,
+};
+use std::collections::BTreeSet;
 
 #[allow(dead_code)]
 #[derive(Clone)]
 pub(crate) struct TemplateTool {
     template: TemplateFile,
+    renderer: Arc<TemplateRenderer>,
     tool_name: String,
     display_name: String,
     description: String,
     schema: Arc<JsonMap<String, JsonValue>>,
+    withheld_hooks: Arc<BTreeSet<String>>,
+    formatter: Arc<OutputFormatter>,
+    defaults: Arc<JsonMap<String, JsonValue>>,
 }
 
 impl TemplateTool {
-    pub(crate) fn from_template(template: TemplateFile) -> Self {
-        let tool_name = template.id.clone();
+    pub(crate) fn from_template(template: TemplateFile, renderer: Arc<TemplateRenderer>) -> Self {
+        let tool_name = EntityId::tool_name(&template.id);
 
         let display_name = if template.name.trim().is_empty() {
             format!("{} (untitled)", tool_name)
@@ -22,23 +29,58 @@ impl TemplateTool {
             template.name.trim().to_string()
         };
 
-        let description = if template.description.trim().is_empty() {
+        let mut description = if template.description.trim().is_empty() {
             format!("Render the {} template.", display_name)
         } else {
             template.description.trim().to_string()
         };
+        if let Some(patch) = &template.patch {
+            description.push_str(&format!(
+                " Patches the existing file ({}) instead of replacing it.",
+                describe_patch(patch)
+            ));
+        }
+        if let Some(merge) = &template.merge {
+            description.push_str(&format!(
+                " Deep-merges into the existing file (arrays: {}) instead of replacing it.",
+                describe_merge(merge)
+            ));
+        }
 
         let schema = Arc::new(Self::args_schema(&template.args));
 
         Self {
             template,
+            renderer,
             tool_name,
             display_name,
             description,
             schema,
+            withheld_hooks: Arc::default(),
+            formatter: Arc::default(),
+            defaults: Arc::default(),
         }
     }
 
+    /// Leave the hooks out of results when the template comes from one of these files.
+    pub(crate) fn withhold_hooks(mut self, sources: Arc<BTreeSet<String>>) -> Self {
+        self.withheld_hooks = sources;
+        self
+    }
+
+    /// Format rendered files before returning them.
+    pub(crate) fn formatter(mut self, formatter: Arc<OutputFormatter>) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Fill args the client leaves out with configured defaults, advertised in the schema.
+    pub(crate) fn defaults(mut self, defaults: Arc<JsonMap<String, JsonValue>>) -> Self {
+        schema_defaults(Arc::make_mut(&mut self.schema), &defaults);
+        self.defaults = defaults;
+        self
+    }
+
     pub(crate) fn route<H>(&self) -> ToolRoute<H>
     where
         H: Clone + Send + Sync + 'static,
@@ -49,27 +91,32 @@ impl TemplateTool {
             self.schema.clone(),
         );
         let template = self.template.clone();
+        let renderer = self.renderer.clone();
+        let share_hooks = !self.withheld_hooks.contains(&self.template.location.path);
+        let formatter = self.formatter.clone();
+        let defaults = self.defaults.clone();
 
         ToolRoute::new_dyn(tool, move |mut context| {
             let template = template.clone();
+            let renderer = renderer.clone();
+            let formatter = formatter.clone();
+            let defaults = defaults.clone();
             Box::pin(async move {
-                let arguments = context.arguments.take().unwrap_or_default();
-                ensure_required_args(&template, &arguments)
+                let mut arguments = context.arguments.take().unwrap_or_default();
+                apply_defaults(&mut arguments, &defaults, &template.args);
+                let arguments = renderer
+                    .with_env(&template.id, &template.location, &arguments)
                     .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
-                let rendered = render_template(&template.id, &template.content, &arguments)
+                let mut out = renderer
+                    .render_file(&template, &arguments)
+                    .map_err(|err| McpError::invalid_params(format!("{err:#}"), None))?;
+                formatter
+                    .format_file(&template, &mut out)
                     .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
-                let rendered_path = match &template.path {
-                    Some(path_tpl) => Some(
-                        render_template(&format!("{}::path", template.id), path_tpl, &arguments)
-                            .map_err(|err| McpError::invalid_params(err.to_string(), None))?,
-                    ),
-                    None => None,
-                };
-                let out = nmcr_types::OutputFile {
-                    path: rendered_path,
-                    lang: template.lang.clone(),
-                    content: rendered,
-                };
+                // Hooks are returned for the client to run; the server never runs them
+                if !share_hooks {
+                    out.hooks = None;
+                }
                 let output_schema = Self::output_schema(&template);
                 Ok(CallToolResult::success(vec![
                     Content::json(out)?,
@@ -79,6 +126,10 @@ impl TemplateTool {
         })
     }
 
+    pub(crate) fn id(&self) -> &str {
+        &self.template.id
+    }
+
     #[allow(dead_code)]
     pub(crate) fn instructions_line(&self) -> String {
         let mut line = format!("- {} → {}", self.tool_name, self.display_name);
@@ -109,7 +160,7 @@ impl TemplateTool {
 
         let mut properties = JsonMap::new();
         let mut required = Vec::new();
-        for arg in args {
+        for arg in args.iter().filter(|arg| arg.derived.is_none()) {
             let mut prop = JsonMap::new();
             match &arg.kind {
                 ArgKind::Boolean(_) => {
@@ -121,6 +172,12 @@ impl TemplateTool {
                 ArgKind::Number(_) => {
                     prop.insert("type".into(), JsonValue::String("number".into()));
                 }
+                ArgKind::Array(_) => {
+                    prop.insert("type".into(), JsonValue::String("array".into()));
+                }
+                ArgKind::Object(_) => {
+                    prop.insert("type".into(), JsonValue::String("object".into()));
+                }
                 ArgKind::Any(_) => {}
             }
 
@@ -152,6 +209,18 @@ impl TemplateTool {
         if t.path.is_some() {
             properties.insert("path".into(), json_type("string"));
         }
+        if t.patch.is_some() {
+            properties.insert("patch".into(), patch_schema());
+        }
+        if t.merge.is_some() {
+            properties.insert("merge".into(), merge_schema());
+        }
+        if t.asset.is_some() {
+            properties.insert("encoding".into(), encoding_schema());
+        }
+        if !t.hooks.is_empty() {
+            properties.insert("hooks".into(), hooks_schema());
+        }
 
         let mut obj = JsonMap::new();
         obj.insert(
@@ -170,30 +239,37 @@ impl TemplateTool {
         if t.path.is_some() {
             required.push(JsonValue::String("path".into()));
         }
+        if t.patch.is_some() {
+            required.push(JsonValue::String("patch".into()));
+        }
+        if t.merge.is_some() {
+            required.push(JsonValue::String("merge".into()));
+        }
         obj.insert("required".into(), JsonValue::Array(required));
         obj
     }
 }
 
-pub(super) fn ensure_required_args(
-    template: &TemplateFile,
-    args: &JsonMap<String, JsonValue>,
-) -> Result<()> {
-    let missing: Vec<String> = template
-        .args
-        .iter()
-        .filter(|arg| arg.required && !args.contains_key(&arg.name))
-        .map(|arg| arg.name.clone())
-        .collect();
-
-    if missing.is_empty() {
-        Ok(())
-    } else {
-        bail!(
-            "Missing required argument(s) {} for template '{}'.",
-            missing.join(", "),
-            template.id
-        )
+/// Advertise configured defaults in an args schema, which makes those args optional.
+pub(super) fn schema_defaults(
+    schema: &mut JsonMap<String, JsonValue>,
+    defaults: &JsonMap<String, JsonValue>,
+) {
+    if let Some(JsonValue::Object(properties)) = schema.get_mut("properties") {
+        for (name, prop) in properties.iter_mut() {
+            if let (Some(value), JsonValue::Object(prop)) = (defaults.get(name), prop) {
+                prop.insert("default".into(), value.clone());
+            }
+        }
+    }
+    if let Some(JsonValue::Array(required)) = schema.get_mut("required") {
+        required.retain(|name| {
+            name.as_str()
+                .is_none_or(|name| !defaults.contains_key(name))
+        });
+        if required.is_empty() {
+            schema.remove("required");
+        }
     }
 }
 
@@ -203,6 +279,48 @@ pub(crate) fn json_type(t: &str) -> JsonValue {
     JsonValue::Object(m)
 }
 
+/// Schema of the `patch` description attached to patch output files.
+pub(crate) fn patch_schema() -> JsonValue {
+    let mut properties = JsonMap::new();
+    properties.insert(
+        "op".into(),
+        serde_json::json!({"enum": ["append", "prepend", "before", "after", "between"]}),
+    );
+    properties.insert("anchor".into(), json_type("string"));
+    properties.insert("start".into(), json_type("string"));
+    properties.insert("end".into(), json_type("string"));
+
+    let mut schema = JsonMap::new();
+    schema.insert("type".into(), JsonValue::String("object".into()));
+    schema.insert("properties".into(), JsonValue::Object(properties));
+    schema.insert(
+        "required".into(),
+        JsonValue::Array(vec![JsonValue::String("op".into())]),
+    );
+    JsonValue::Object(schema)
+}
+
+/// Schema of the `encoding` of asset files returned as base64.
+pub(crate) fn encoding_schema() -> JsonValue {
+    serde_json::json!({"const": "base64"})
+}
+
+/// Schema of the rendered `hooks` returned for the client to run.
+pub(crate) fn hooks_schema() -> JsonValue {
+    serde_json::json!({"type": "array", "items": {"type": "string"}})
+}
+
+/// Schema of the `merge` description attached to merged output files.
+pub(crate) fn merge_schema() -> JsonValue {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "arrays": {"enum": ["append-unique", "replace"]},
+        },
+        "required": ["arrays"],
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,6 +333,7 @@ mod tests {
             description: description.to_string(),
             kind,
             required: true,
+            derived: None,
         }
     }
 
@@ -225,33 +344,48 @@ mod tests {
         }
     }
 
-    #[test]
-    fn instructions_include_descriptions_and_args() {
-        let mut args = Vec::new();
-        args.push(make_arg(
-            "name",
-            "Name of the component",
-            ArgKind::String(ArgKindString),
-        ));
-        args.push(make_arg(
-            "with_css",
-            "Generate CSS module",
-            ArgKind::Boolean(ArgKindBoolean),
-        ));
-
-        let template = TemplateFile {
-            kind: nmcr_types::TemplateFileKindFile,
-            id: "component".into(),
-            name: "Component".into(),
-            description: "Create a React component".into(),
+    fn template_file(id: &str, name: &str, args: Vec<Arg>) -> TemplateFile {
+        TemplateFile {
+            kind: nmcr_types::TemplateFileKind::File(nmcr_types::TemplateFileKindFile),
+            id: id.into(),
+            name: name.into(),
+            description: String::new(),
             args,
             lang: None,
             content: String::new(),
             path: None,
+            raw: None,
+            asset: None,
+            condition: None,
+            each: None,
+            patch: None,
+            merge: None,
+            hooks: Vec::new(),
             location: empty_location(),
+        }
+    }
+
+    #[test]
+    fn instructions_include_descriptions_and_args() {
+        let args = vec![
+            make_arg(
+                "name",
+                "Name of the component",
+                ArgKind::String(ArgKindString),
+            ),
+            make_arg(
+                "with_css",
+                "Generate CSS module",
+                ArgKind::Boolean(ArgKindBoolean),
+            ),
+        ];
+
+        let template = TemplateFile {
+            description: "Create a React component".into(),
+            ..template_file("component", "Component", args)
         };
 
-        let tool = TemplateTool::from_template(template);
+        let tool = TemplateTool::from_template(template, Arc::default());
         let instructions = tool.instructions_line();
 
         assert_eq!(
@@ -262,19 +396,9 @@ mod tests {
 
     #[test]
     fn tool_name_uses_template_id() {
-        let template = TemplateFile {
-            kind: nmcr_types::TemplateFileKindFile,
-            id: "rust_package_gitignore".into(),
-            name: "Package Gitignore".into(),
-            description: String::new(),
-            args: Vec::new(),
-            lang: None,
-            content: String::new(),
-            path: None,
-            location: empty_location(),
-        };
+        let template = template_file("rust_package_gitignore", "Package Gitignore", Vec::new());
 
-        let tool = TemplateTool::from_template(template.clone());
+        let tool = TemplateTool::from_template(template.clone(), Arc::default());
         assert_eq!(tool.tool_name, template.id);
     }
 
@@ -286,29 +410,115 @@ mod tests {
                 description: String::new(),
                 kind: ArgKind::String(ArgKindString),
                 required: true,
+                derived: None,
             },
             Arg {
                 name: "suffix".into(),
                 description: String::new(),
                 kind: ArgKind::String(ArgKindString),
                 required: false,
+                derived: None,
             },
         ];
 
-        let template = TemplateFile {
-            kind: nmcr_types::TemplateFileKindFile,
-            id: "example".into(),
-            name: "Example".into(),
-            description: String::new(),
-            args,
-            lang: None,
-            content: String::new(),
-            path: None,
-            location: empty_location(),
-        };
+        let template = template_file("example", "Example", args);
 
-        let tool = TemplateTool::from_template(template);
+        let tool = TemplateTool::from_template(template, Arc::default());
         let instructions = tool.instructions_line();
         assert!(instructions.contains("suffix?"));
     }
+
+    #[test]
+    fn derived_args_excluded_from_schema() {
+        let mut derived = make_arg("crate_ident", "", ArgKind::String(ArgKindString));
+        derived.derived = Some("{{pkg_name}}_core".into());
+        let args = vec![
+            make_arg("pkg_name", "", ArgKind::String(ArgKindString)),
+            derived,
+        ];
+
+        let schema = TemplateTool::args_schema(&args);
+        let properties = schema["properties"].as_object().expect("properties");
+        assert!(properties.contains_key("pkg_name"));
+        assert!(!properties.contains_key("crate_ident"));
+        assert_eq!(
+            schema["required"],
+            JsonValue::Array(vec![JsonValue::String("pkg_name".into())])
+        );
+    }
+
+    #[test]
+    fn patch_templates_describe_the_patch() {
+        let template = TemplateFile {
+            kind: nmcr_types::TemplateFileKind::Patch(nmcr_types::TemplateFileKindPatch),
+            description: "Declare a module.".into(),
+            lang: Some("rust".into()),
+            content: "mod api;".into(),
+            path: Some("./src/lib.rs".into()),
+            patch: Some(nmcr_types::TemplatePatch {
+                op: nmcr_types::TemplatePatchOp::After(nmcr_types::TemplatePatchOpAfter),
+                anchor: Some("^mod".into()),
+                start: None,
+                end: None,
+            }),
+            ..template_file("add_module", "Add module", Vec::new())
+        };
+
+        let schema = TemplateTool::output_schema(&template);
+        let tool = TemplateTool::from_template(template, Arc::default());
+        assert_eq!(
+            tool.description,
+            "Declare a module. Patches the existing file (after ^mod) instead of replacing it."
+        );
+        assert_eq!(schema["properties"]["patch"], patch_schema());
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["content", "path", "patch"])
+        );
+    }
+
+    #[test]
+    fn output_schema_lists_encoding_and_hooks() {
+        let template = TemplateFile {
+            path: Some("./logo.png".into()),
+            asset: Some("./logo.png".into()),
+            hooks: vec!["git add logo.png".into()],
+            ..template_file("logo", "Logo", Vec::new())
+        };
+
+        let schema = TemplateTool::output_schema(&template);
+        assert_eq!(
+            schema["properties"]["encoding"],
+            serde_json::json!({"const": "base64"})
+        );
+        assert_eq!(
+            schema["properties"]["hooks"],
+            serde_json::json!({"type": "array", "items": {"type": "string"}})
+        );
+        assert_eq!(schema["required"], serde_json::json!(["content", "path"]));
+    }
+
+    #[test]
+    fn defaults_are_advertised_and_make_args_optional() {
+        let args = vec![
+            make_arg("pkg_name", "", ArgKind::String(ArgKindString)),
+            make_arg("license", "", ArgKind::String(ArgKindString)),
+        ];
+        let template = template_file("manifest", "Manifest", args);
+
+        let defaults = serde_json::json!({"license": "MIT", "author": "Sasha"});
+        let tool = TemplateTool::from_template(template, Arc::default())
+            .defaults(Arc::new(defaults.as_object().cloned().unwrap()));
+        assert_eq!(
+            tool.schema["properties"]["license"],
+            serde_json::json!({"type": "string", "default": "MIT"})
+        );
+        assert_eq!(tool.schema["required"], serde_json::json!(["pkg_name"]));
+        assert!(
+            !tool.schema["properties"]
+                .as_object()
+                .unwrap()
+                .contains_key("author")
+        );
+    }
 }
@@ -1,24 +1,34 @@
-use super::render_template;
-use super::tool::{ensure_required_args, json_type};
+use super::tool::{
+    encoding_schema, hooks_schema, json_type, merge_schema, patch_schema, schema_defaults,
+};
 use crate::prelude::*;
 use nmcr_catalog::CatalogTree;
-use nmcr_types::{Arg, ArgKind};
+use nmcr_id::EntityId;
+use nmcr_template::{
+    OutputFormatter, PlaceholderShape, TemplateRenderer, apply_defaults, discover_placeholders,
+    push_hooks,
+};
+use nmcr_types::ArgKind;
 use std::collections::BTreeSet;
 
 #[allow(dead_code)]
 #[derive(Clone)]
 pub(crate) struct TreeTool {
     tree: CatalogTree,
+    renderer: Arc<TemplateRenderer>,
     tool_name: String,
     #[allow(dead_code)]
     display_name: String,
     description: String,
     schema: Arc<JsonMap<String, JsonValue>>,
+    withheld_hooks: Arc<BTreeSet<String>>,
+    formatter: Arc<OutputFormatter>,
+    defaults: Arc<JsonMap<String, JsonValue>>,
 }
 
 impl TreeTool {
-    pub(crate) fn from_tree(tree: CatalogTree) -> Self {
-        let tool_name = tree.id().to_string();
+    pub(crate) fn from_tree(tree: CatalogTree, renderer: Arc<TemplateRenderer>) -> Self {
+        let tool_name = EntityId::tool_name(tree.id());
         let display_name = if tree.name().trim().is_empty() {
             format!("{} (tree)", tool_name)
         } else {
@@ -35,13 +45,36 @@ impl TreeTool {
 
         Self {
             tree,
+            renderer,
             tool_name,
             display_name,
             description,
             schema,
+            withheld_hooks: Arc::default(),
+            formatter: Arc::default(),
+            defaults: Arc::default(),
         }
     }
 
+    /// Leave out the hooks of the tree's files and subtrees that come from these files.
+    pub(crate) fn withhold_hooks(mut self, sources: Arc<BTreeSet<String>>) -> Self {
+        self.withheld_hooks = sources;
+        self
+    }
+
+    /// Format rendered files before returning them.
+    pub(crate) fn formatter(mut self, formatter: Arc<OutputFormatter>) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Fill args the client leaves out with configured defaults, advertised in the schema.
+    pub(crate) fn defaults(mut self, defaults: Arc<JsonMap<String, JsonValue>>) -> Self {
+        schema_defaults(Arc::make_mut(&mut self.schema), &defaults);
+        self.defaults = defaults;
+        self
+    }
+
     pub(crate) fn route<H>(&self) -> ToolRoute<H>
     where
         H: Clone + Send + Sync + 'static,
@@ -52,31 +85,42 @@ impl TreeTool {
             self.schema.clone(),
         );
         let tree = self.tree.clone();
+        let renderer = self.renderer.clone();
+        let withheld = self.withheld_hooks.clone();
+        let formatter = self.formatter.clone();
+        let defaults = self.defaults.clone();
 
         ToolRoute::new_dyn(tool, move |mut context| {
             let tree = tree.clone();
+            let renderer = renderer.clone();
+            let withheld = withheld.clone();
+            let formatter = formatter.clone();
+            let defaults = defaults.clone();
             Box::pin(async move {
-                let args = context.arguments.take().unwrap_or_default();
+                let mut args = context.arguments.take().unwrap_or_default();
+                apply_defaults(
+                    &mut args,
+                    &defaults,
+                    tree.all_files().into_iter().flat_map(|file| &file.args),
+                );
                 let mut files: Vec<nmcr_types::OutputFile> = Vec::new();
-                for file in tree.files() {
-                    ensure_required_args(file, &args)
-                        .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
-                    let rendered = render_template(&file.id, &file.content, &args)
+                for file in tree.all_files() {
+                    let mut rendered = Self::render_file(&renderer, file, &args, &withheld)
                         .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
-                    let rendered_path = match &file.path {
-                        Some(path_tpl) => Some(
-                            render_template(&format!("{}::path", file.id), path_tpl, &args)
-                                .map_err(|err| McpError::invalid_params(err.to_string(), None))?,
-                        ),
-                        None => None,
-                    };
-                    files.push(nmcr_types::OutputFile {
-                        path: rendered_path,
-                        lang: file.lang.clone(),
-                        content: rendered,
-                    });
+                    for output in &mut rendered {
+                        formatter
+                            .format_file(file, output)
+                            .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                    }
+                    files.extend(rendered);
                 }
-                let out = nmcr_types::OutputTree { files };
+                // Hooks are returned for the client to run; the server never runs them
+                let hooks = Self::render_hooks(&renderer, &tree, &files, &args, &withheld)
+                    .map_err(|err| McpError::invalid_params(err.to_string(), None))?;
+                let out = nmcr_types::OutputTree {
+                    files,
+                    hooks: (!hooks.is_empty()).then_some(hooks),
+                };
                 let output_schema = Self::output_schema(&tree);
                 Ok(CallToolResult::success(vec![
                     Content::json(out)?,
@@ -86,20 +130,67 @@ impl TreeTool {
         })
     }
 
+    /// Hooks of the rendered files followed by those of the tree and its subtrees, except
+    /// subtrees from `withheld` files.
+    fn render_hooks(
+        renderer: &TemplateRenderer,
+        tree: &CatalogTree,
+        files: &[nmcr_types::OutputFile],
+        args: &JsonMap<String, JsonValue>,
+        withheld: &BTreeSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut hooks = Vec::new();
+        for file in files {
+            push_hooks(&mut hooks, file.hooks.iter().flatten().cloned());
+        }
+        for subtree in tree.descendants() {
+            if withheld.contains(&subtree.location().path) {
+                continue;
+            }
+            let args = renderer.with_env(subtree.id(), subtree.location(), args)?;
+            push_hooks(
+                &mut hooks,
+                renderer.render_hooks(subtree.id(), subtree.hooks(), &args)?,
+            );
+        }
+        Ok(hooks)
+    }
+
+    /// Render a file like `nmcr gen`, leaving out its hooks when it comes from a `withheld`
+    /// file.
+    fn render_file(
+        renderer: &TemplateRenderer,
+        file: &TemplateFile,
+        args: &JsonMap<String, JsonValue>,
+        withheld: &BTreeSet<String>,
+    ) -> Result<Vec<nmcr_types::OutputFile>> {
+        let mut rendered = renderer.render_tree_file(file, args)?;
+        if withheld.contains(&file.location.path) {
+            for output in &mut rendered {
+                output.hooks = None;
+            }
+        }
+        Ok(rendered)
+    }
+
+    pub(crate) fn id(&self) -> &str {
+        self.tree.id()
+    }
+
     #[allow(dead_code)]
     pub(crate) fn instructions_line(&self) -> String {
         format!("- {} → {} (tree)", self.tool_name, self.display_name)
     }
 
     fn args_schema(tree: &CatalogTree) -> JsonMap<String, JsonValue> {
-        // Union of args across files in the tree
+        // Union of args across files at every level of the tree
         let mut schema = JsonMap::new();
         schema.insert("type".into(), JsonValue::String("object".into()));
 
         let mut properties = JsonMap::new();
         let mut required = BTreeSet::new();
-        for file in tree.files() {
-            for arg in &file.args {
+        for file in tree.all_files() {
+            for arg in file.args.iter().filter(|arg| arg.derived.is_none()) {
                 properties.entry(arg.name.clone()).or_insert_with(|| {
                     let mut prop = JsonMap::new();
                     match &arg.kind {
@@ -112,6 +203,15 @@ impl TreeTool {
                         ArgKind::Number(_) => {
                             prop.insert("type".into(), JsonValue::String("number".into()));
                         }
+                        ArgKind::Array(_) => {
+                            prop.insert("type".into(), JsonValue::String("array".into()));
+                            if let Some(items) = element_schema(file, &arg.name) {
+                                prop.insert("items".into(), items);
+                            }
+                        }
+                        ArgKind::Object(_) => {
+                            prop.insert("type".into(), JsonValue::String("object".into()));
+                        }
                         ArgKind::Any(_) => {}
                     }
                     if !arg.description.trim().is_empty() {
@@ -122,11 +222,14 @@ impl TreeTool {
                     }
                     JsonValue::Object(prop)
                 });
-                if arg.required {
+                // Args of conditional files are only needed when the condition holds
+                if arg.required && file.condition.is_none() {
                     required.insert(arg.name.clone());
                 }
             }
         }
+        // List args by name, whichever file declares them first
+        properties.sort_keys();
         schema.insert("properties".into(), JsonValue::Object(properties));
         schema.insert("additionalProperties".into(), JsonValue::Bool(false));
         if !required.is_empty() {
@@ -138,13 +241,26 @@ impl TreeTool {
 
     fn output_schema(tree: &CatalogTree) -> JsonMap<String, JsonValue> {
         // Build item schema; require path only if every file has a path
-        let all_have_path = tree.files().iter().all(|f| f.path.is_some());
+        let all_have_path = tree.all_files().iter().all(|f| f.path.is_some());
         let mut item_props = JsonMap::new();
         item_props.insert("content".into(), json_type("string"));
         item_props.insert("lang".into(), json_type("string"));
         if all_have_path {
             item_props.insert("path".into(), json_type("string"));
         }
+        if tree.all_files().iter().any(|f| f.patch.is_some()) {
+            item_props.insert("patch".into(), patch_schema());
+        }
+        if tree.all_files().iter().any(|f| f.merge.is_some()) {
+            item_props.insert("merge".into(), merge_schema());
+        }
+        if tree.all_files().iter().any(|f| f.asset.is_some()) {
+            item_props.insert("encoding".into(), encoding_schema());
+        }
+        let file_hooks = tree.all_files().iter().any(|f| !f.hooks.is_empty());
+        if file_hooks {
+            item_props.insert("hooks".into(), hooks_schema());
+        }
         let mut item = JsonMap::new();
         item.insert("type".into(), JsonValue::String("object".into()));
         item.insert("properties".into(), JsonValue::Object(item_props));
@@ -159,6 +275,9 @@ impl TreeTool {
         files.insert("type".into(), JsonValue::String("array".into()));
         files.insert("items".into(), JsonValue::Object(item));
         props.insert("files".into(), JsonValue::Object(files));
+        if file_hooks || tree.descendants().iter().any(|t| !t.hooks().is_empty()) {
+            props.insert("hooks".into(), hooks_schema());
+        }
 
         let mut schema = JsonMap::new();
         schema.insert(
@@ -178,3 +297,252 @@ impl TreeTool {
         schema
     }
 }
+
+/// Schema of the elements a repeated file binds from `arg`, based on how it uses them.
+fn element_schema(file: &TemplateFile, arg: &str) -> Option<JsonValue> {
+    let each = file.each.as_ref().filter(|each| each.arg == arg)?;
+    let mut shape = PlaceholderShape::Scalar;
+    let sources = [Some(file.content.as_str()), file.path.as_deref()];
+    for source in sources.into_iter().flatten() {
+        for placeholder in discover_placeholders(source) {
+            if placeholder.name == each.binding {
+                shape = shape.merge(placeholder.shape);
+            }
+        }
+    }
+    Some(shape_schema(&shape))
+}
+
+fn shape_schema(shape: &PlaceholderShape) -> JsonValue {
+    let mut schema = JsonMap::new();
+    match shape {
+        PlaceholderShape::Scalar => {}
+        PlaceholderShape::Iterable(element) => {
+            schema.insert("type".into(), JsonValue::String("array".into()));
+            schema.insert("items".into(), shape_schema(element));
+        }
+        PlaceholderShape::Object(fields) => {
+            let properties: JsonMap<String, JsonValue> = fields
+                .iter()
+                .map(|(name, field)| (name.clone(), shape_schema(field)))
+                .collect();
+            let required = fields.keys().cloned().map(JsonValue::String).collect();
+            schema.insert("type".into(), JsonValue::String("object".into()));
+            schema.insert("properties".into(), JsonValue::Object(properties));
+            schema.insert("required".into(), JsonValue::Array(required));
+        }
+    }
+    JsonValue::Object(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmcr_catalog::TemplateCatalog;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn args_schema_unions_args_across_subtrees() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../examples/basic/tmpls/monorepo.md");
+        let catalog = TemplateCatalog::load(&[path]).expect("catalog loads");
+        let tool = TreeTool::from_tree(
+            catalog
+                .get_tree("monorepo")
+                .unwrap()
+                .expect("root tree")
+                .clone(),
+            Arc::default(),
+        );
+
+        let properties: Vec<&String> = tool.schema["properties"]
+            .as_object()
+            .expect("properties")
+            .keys()
+            .collect();
+        assert_eq!(properties, ["greeting", "name"]);
+        assert_eq!(
+            tool.schema["required"],
+            JsonValue::Array(vec!["greeting".into(), "name".into()])
+        );
+    }
+
+    #[test]
+    fn skips_conditional_files_and_empty_paths() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("app.md");
+        std::fs::write(
+            &path,
+            "# App\n\n## `./src/lib.rs`\n\n```rust\n```\n\n## `./src/bin/main.rs`\n\nwhen: binary\n\n```rust\n{{bin_name}}\n```\n\n## `{{#if with_css}}./app.css{{/if}}`\n\n```css\n```\n",
+        )
+        .expect("write fixture");
+        let catalog = TemplateCatalog::load(&[path]).expect("catalog loads");
+        let tree = catalog.get_tree("app").unwrap().expect("tree").clone();
+        let renderer = TemplateRenderer::new();
+        let tool = TreeTool::from_tree(tree.clone(), Arc::new(TemplateRenderer::new()));
+        assert!(tool.schema.get("required").is_none());
+        let properties: Vec<&String> = tool.schema["properties"]
+            .as_object()
+            .expect("properties")
+            .keys()
+            .collect();
+        assert_eq!(properties, ["bin_name", "binary", "with_css"]);
+
+        let render = |args: JsonValue| -> Result<Vec<String>> {
+            let args = args.as_object().cloned().unwrap_or_default();
+            let mut paths = Vec::new();
+            for file in tree.all_files() {
+                for out in TreeTool::render_file(&renderer, file, &args, &BTreeSet::new())? {
+                    paths.push(out.path.unwrap_or_default());
+                }
+            }
+            Ok(paths)
+        };
+        assert_eq!(render(serde_json::json!({})).unwrap(), ["./src/lib.rs"]);
+        assert_eq!(
+            render(serde_json::json!({"binary": true, "bin_name": "app", "with_css": true}))
+                .unwrap(),
+            ["./src/lib.rs", "./src/bin/main.rs", "./app.css"]
+        );
+        let err = render(serde_json::json!({"binary": true})).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Missing required argument(s) bin_name")
+        );
+    }
+
+    #[test]
+    fn copies_verbatim_files_and_encodes_binary_assets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir(dir.path().join("assets")).expect("assets dir");
+        std::fs::write(dir.path().join("assets/favicon.ico"), [0u8, 0, 1, 0, 0xff])
+            .expect("write asset");
+        let path = dir.path().join("site.md");
+        std::fs::write(
+            &path,
+            "# Site\n\n## `./ci.yml`\n\n```yaml raw\nrun: ${{ github.sha }}\n```\n\n## `./favicon.ico`\n\n![favicon](./assets/favicon.ico)\n",
+        )
+        .expect("write fixture");
+        let catalog = TemplateCatalog::load(&[path]).expect("catalog loads");
+        let tree = catalog.get_tree("site").unwrap().expect("tree").clone();
+        let tool = TreeTool::from_tree(tree.clone(), Arc::new(TemplateRenderer::new()));
+        assert_eq!(tool.schema["properties"], serde_json::json!({}));
+        let schema = TreeTool::output_schema(&tree);
+        assert_eq!(
+            schema["properties"]["files"]["items"]["properties"]["encoding"],
+            encoding_schema()
+        );
+        assert!(schema["properties"].get("hooks").is_none());
+
+        let renderer = TemplateRenderer::new();
+        let files: Vec<JsonValue> = tree
+            .all_files()
+            .into_iter()
+            .flat_map(|file| {
+                TreeTool::render_file(&renderer, file, &JsonMap::new(), &BTreeSet::new()).unwrap()
+            })
+            .map(|out| serde_json::to_value(out).unwrap())
+            .collect();
+        assert_eq!(
+            files,
+            [
+                serde_json::json!({"path": "./ci.yml", "lang": "yaml", "content": "run: ${{ github.sha }}"}),
+                serde_json::json!({"path": "./favicon.ico", "content": "AAABAP8=", "encoding": "base64"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn withheld_sources_contribute_no_hooks() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("app.md");
+        std::fs::write(
+            &path,
+            "# App\n\n```sh hook\ngit init\n```\n\n## `./Cargo.toml`\n\n```sh hook\ncargo add {{dependency}}\n```\n\n```toml\n```\n\n## `./README.md`\n\n```md\n```\n",
+        )
+        .expect("write fixture");
+        let catalog = TemplateCatalog::load(std::slice::from_ref(&path)).expect("catalog loads");
+        let tree = catalog.get_tree("app").unwrap().expect("tree").clone();
+        let renderer = TemplateRenderer::new();
+        let args = serde_json::json!({"dependency": "serde"})
+            .as_object()
+            .cloned()
+            .unwrap();
+        let hooks = |withheld: &BTreeSet<String>| {
+            let files: Vec<nmcr_types::OutputFile> = tree
+                .all_files()
+                .into_iter()
+                .flat_map(|file| TreeTool::render_file(&renderer, file, &args, withheld).unwrap())
+                .collect();
+            TreeTool::render_hooks(&renderer, &tree, &files, &args, withheld).unwrap()
+        };
+
+        assert_eq!(hooks(&BTreeSet::new()), ["cargo add serde", "git init"]);
+        let schema = TreeTool::output_schema(&tree);
+        assert_eq!(schema["properties"]["hooks"], hooks_schema());
+        assert_eq!(
+            schema["properties"]["files"]["items"]["properties"]["hooks"],
+            hooks_schema()
+        );
+        let withheld = BTreeSet::from([path.display().to_string()]);
+        assert!(hooks(&withheld).is_empty());
+    }
+
+    #[test]
+    fn repeats_files_per_element_with_array_schema() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("crate.md");
+        std::fs::write(
+            &path,
+            "# Crate\n\n## `./src/lib.rs`\n\n```rust\n```\n\n## `./src/{{module.name}}.rs`\n\neach: modules as module\n\n```rust\n//! {{module.doc}}\n```\n",
+        )
+        .expect("write fixture");
+        let catalog = TemplateCatalog::load(&[path]).expect("catalog loads");
+        let tree = catalog.get_tree("crate").unwrap().expect("tree").clone();
+        let tool = TreeTool::from_tree(tree.clone(), Arc::new(TemplateRenderer::new()));
+        assert_eq!(
+            tool.schema["properties"]["modules"],
+            serde_json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {"doc": {}, "name": {}},
+                    "required": ["doc", "name"]
+                }
+            })
+        );
+
+        let renderer = TemplateRenderer::new();
+        let render = |args: JsonValue| -> Result<Vec<(String, String)>> {
+            let args = args.as_object().cloned().unwrap_or_default();
+            let mut out = Vec::new();
+            for file in tree.all_files() {
+                for rendered in TreeTool::render_file(&renderer, file, &args, &BTreeSet::new())? {
+                    out.push((rendered.path.unwrap_or_default(), rendered.content));
+                }
+            }
+            Ok(out)
+        };
+        let modules = serde_json::json!({"modules": [
+            {"name": "users", "doc": "Users"},
+            {"name": "api", "doc": "API"}
+        ]});
+        assert_eq!(
+            render(modules).unwrap(),
+            [
+                ("./src/lib.rs".to_string(), String::new()),
+                ("./src/users.rs".to_string(), "//! Users".to_string()),
+                ("./src/api.rs".to_string(), "//! API".to_string()),
+            ]
+        );
+        let duplicate = serde_json::json!({"modules": [
+            {"name": "api", "doc": "A"},
+            {"name": "api", "doc": "B"}
+        ]});
+        let err = render(duplicate).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("renders the path './src/api.rs' more than once")
+        );
+    }
+}
@@ -1,9 +1,12 @@
 pub mod markdown;
 pub mod prelude;
 
+/// Short-lived parse result, so the template variants are kept inline.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum ParsedMarkdown {
     Template(nmcr_types::Template),
     Tree(nmcr_types::TemplateTree),
     Collection(nmcr_types::TemplateCollection),
+    Partials(Vec<nmcr_types::TemplatePartial>),
 }
@@ -1,6 +1,9 @@
 use crate::prelude::*;
 use nmcr_id::EntityId;
-use nmcr_template::discover_placeholders;
+use nmcr_template::{
+    DerivedArg, MergeFormat, Placeholder, PlaceholderShape, RESERVED_KEY, anchor_regex,
+    condition_template, derived_order, discover_placeholders_with_helpers,
+};
 use nmcr_types_internal::FormattedLocation;
 use relative_path::RelativePathBuf;
 use std::collections::{BTreeMap, BTreeSet};
@@ -17,21 +20,56 @@ struct Section {
 
 const ALLOWED_SUBHEADS: &[&str] = &["args", "arguments", "template"];
 
+/// File stem suffix marking a markdown file that only declares partials.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+const INCLUDE_PREFIX: &str = "include:";
+
+const CONDITION_PREFIX: &str = "when:";
+
+const EACH_PREFIX: &str = "each:";
+
+const PATCH_PREFIX: &str = "patch:";
+
+const MERGE_PREFIX: &str = "merge:";
+
+/// Info string word marking a fenced code block as post-generation commands, as in
+/// ```` ```sh hook ````.
+const HOOK_FENCE: &str = "hook";
+
+const ASSET_PREFIX: &str = "asset:";
+
 pub fn parse_file(path: &Path) -> Result<ParsedMarkdown> {
+    parse_file_with_helpers(path, &BTreeSet::new())
+}
+
+/// Parse a file whose templates may call the given script helpers, so a bare `{{name}}` of
+/// one of them is a helper call rather than an arg.
+pub fn parse_file_with_helpers(path: &Path, helpers: &BTreeSet<String>) -> Result<ParsedMarkdown> {
     let content = std::fs::read_to_string(path)
         .with_context(|| format!("Failed to read markdown file: {}", path.display()))?;
     let stem = path.file_stem().and_then(|s| s.to_str());
-    parse_str_with_path(Some(path), stem, &content)
+    parse_str_with_path(Some(path), stem, &content, helpers)
 }
 
 pub fn parse_str(file_stem: Option<&str>, input: &str) -> Result<ParsedMarkdown> {
-    parse_str_with_path(None, file_stem, input)
+    parse_str_with_helpers(file_stem, input, &BTreeSet::new())
+}
+
+/// Parse a string like [`parse_file_with_helpers`].
+pub fn parse_str_with_helpers(
+    file_stem: Option<&str>,
+    input: &str,
+    helpers: &BTreeSet<String>,
+) -> Result<ParsedMarkdown> {
+    parse_str_with_path(None, file_stem, input, helpers)
 }
 
 fn parse_str_with_path(
     path: Option<&Path>,
     file_stem: Option<&str>,
     input: &str,
+    helpers: &BTreeSet<String>,
 ) -> Result<ParsedMarkdown> {
     let root = to_mdast(input, &ParseOptions::default())
         .map_err(|e| anyhow!("Failed to parse markdown: {e}"))?;
@@ -43,7 +81,51 @@ fn parse_str_with_path(
 
     let root_span = root.position.as_ref().map(position_to_span);
 
-    let sections = make_sections(&root.children);
+    if let Some(stem) = file_stem.and_then(|stem| stem.strip_suffix(PARTIAL_SUFFIX)) {
+        let partials = parse_partials(&root.children, path, stem)?;
+        if partials.is_empty() {
+            bail!("No partials found in markdown");
+        }
+        return Ok(ParsedMarkdown::Partials(partials));
+    }
+
+    let (children, partials) = take_partials_sections(&root.children, path)?;
+    if partials.is_empty() {
+        return parse_templates(&children, path, file_stem, root_span, helpers);
+    }
+    if !contains_code(&children) {
+        return Ok(ParsedMarkdown::Partials(partials));
+    }
+
+    let collection = match parse_templates(&children, path, file_stem, root_span.clone(), helpers)?
+    {
+        ParsedMarkdown::Collection(collection) => TemplateCollection {
+            partials,
+            ..collection
+        },
+        ParsedMarkdown::Template(template) => {
+            wrap_collection(vec![template], partials, path, file_stem, root_span)
+        }
+        ParsedMarkdown::Tree(tree) => wrap_collection(
+            vec![Template::TemplateTree(tree)],
+            partials,
+            path,
+            file_stem,
+            root_span,
+        ),
+        ParsedMarkdown::Partials(_) => unreachable!("templates never parse into partials"),
+    };
+    Ok(ParsedMarkdown::Collection(collection))
+}
+
+fn parse_templates(
+    nodes: &[mdast::Node],
+    path: Option<&Path>,
+    file_stem: Option<&str>,
+    root_span: Option<Span>,
+    helpers: &BTreeSet<String>,
+) -> Result<ParsedMarkdown> {
+    let sections = make_sections(nodes);
 
     // First pass: find min level of allowed subheads anywhere
     let min_allowed_level = sections
@@ -59,6 +141,7 @@ fn parse_str_with_path(
         let mut templates: Vec<Template> = Vec::new();
         // Track mapping from parent section index to its direct child file templates
         let mut parent_children: Vec<(usize, Vec<TemplateFile>)> = Vec::new();
+        let mut base_files: Vec<(Vec<String>, TemplateFile)> = Vec::new();
 
         // Build an index of sections by their original order
         for (idx, parent) in sections
@@ -67,7 +150,8 @@ fn parse_str_with_path(
             .filter(|(_, s)| s.level == base_level)
         {
             if section_contains_allowed(parent) {
-                if let Some(t) = parse_template_from_section(parent, path)? {
+                if let Some(t) = parse_template_from_section(parent, path, helpers)? {
+                    base_files.push((parent.path.clone(), t.clone()));
                     templates.push(Template::TemplateFile(t.clone()));
                 }
 
@@ -80,30 +164,40 @@ fn parse_str_with_path(
                 let mut child_templates: Vec<TemplateFile> = Vec::new();
                 for child in &children {
                     if section_contains_allowed(child) {
-                        if let Some(t) = parse_template_from_section(child, path)? {
+                        if let Some(t) = parse_template_from_section(child, path, helpers)? {
                             child_templates.push(t);
                         }
-                    } else if let Some((lang, content)) =
-                        collect_code_blocks(&child.nodes).into_iter().next()
+                    } else if let Some(body) =
+                        collect_file_bodies(&child.nodes, &section_location(child, path))?
+                            .into_iter()
+                            .next()
                     {
                         // Support simple child with a single code block (no subheads)
                         let id =
                             EntityId::new().from_segments(child.path.iter().map(|s| s.as_str()));
                         if !id.is_empty() {
+                            let patch = parse_patch(&child.nodes, &section_location(child, path))?;
                             let mut t = TemplateFile {
-                                kind: TemplateFileKindFile,
+                                kind: file_kind(&patch),
                                 id,
                                 name: child.title.clone(),
                                 description: collect_description(child),
                                 args: Vec::new(),
-                                lang,
-                                content,
+                                lang: body.lang,
+                                content: body.content,
                                 location: section_location(child, path),
                                 path: None,
+                                raw: body.raw.then_some(true),
+                                asset: body.asset,
+                                condition: extract_directive(&child.nodes, CONDITION_PREFIX),
+                                each: parse_each(&child.nodes, &section_location(child, path))?,
+                                patch,
+                                merge: parse_merge(&child.nodes, &section_location(child, path))?,
+                                hooks: extract_hooks(&child.nodes),
                             };
                             // Attempt inline path capture
                             t.path = extract_inline_path_before_code(&child.nodes);
-                            enrich_template_args(&mut t)?;
+                            enrich_template_args(&mut t, helpers)?;
                             child_templates.push(t);
                         }
                     }
@@ -116,7 +210,7 @@ fn parse_str_with_path(
         }
 
         // Build trees: a section becomes a tree if all of its direct child templates have a path
-        let mut trees: Vec<TemplateTree> = Vec::new();
+        let mut trees: Vec<(Vec<String>, TemplateTree)> = Vec::new();
         for (idx, files) in parent_children.into_iter() {
             if files.iter().all(|t| t.path.is_some()) {
                 let parent = &sections[idx];
@@ -130,11 +224,14 @@ fn parse_str_with_path(
                     name: parent.title.clone(),
                     description: collect_tree_description(parent),
                     files: files.into_iter().map(Template::TemplateFile).collect(),
+                    includes: parse_includes(parent, path)?,
+                    hooks: extract_hooks(&parent.nodes),
                     location: section_location(parent, path),
                 };
-                trees.push(tree);
+                trees.push((parent.path.clone(), tree));
             }
         }
+        let mut trees = nest_trees(&sections, trees, &base_files, path)?;
 
         // Decide which top-level variant to return
         let collection_meta = sections.iter().find(|s| s.level < base_level);
@@ -161,6 +258,7 @@ fn parse_str_with_path(
                     name,
                     description,
                     templates: all_templates,
+                    partials: Vec::new(),
                     location,
                 }));
             }
@@ -169,13 +267,14 @@ fn parse_str_with_path(
 
     // Second pass: no allowed subheads — use code-block heuristic
     let mut standalones: Vec<(TemplateFile, Vec<String>)> = Vec::new();
+    let mut file_sections: Vec<(Vec<String>, TemplateFile)> = Vec::new();
     for sec in &sections {
         let subsections = make_sections(&sec.nodes);
         let has_subheads = subsections.iter().any(|s| s.level > sec.level);
         if !has_subheads {
-            let mut codes = collect_code_blocks(&sec.nodes);
-            if codes.len() == 1 {
-                let (lang, content) = codes.remove(0);
+            let mut bodies = collect_file_bodies(&sec.nodes, &section_location(sec, path))?;
+            if bodies.len() == 1 {
+                let body = bodies.remove(0);
                 let id = EntityId::new().from_segments(sec.path.iter().map(|s| s.as_str()));
                 if id.is_empty() {
                     bail!(
@@ -184,20 +283,29 @@ fn parse_str_with_path(
                     );
                 }
 
+                let patch = parse_patch(&sec.nodes, &section_location(sec, path))?;
                 let mut tmpl = TemplateFile {
-                    kind: TemplateFileKindFile,
+                    kind: file_kind(&patch),
                     id,
                     name: sec.title.clone(),
                     description: collect_description(sec),
                     args: Vec::new(),
-                    lang,
-                    content,
+                    lang: body.lang,
+                    content: body.content,
                     location: section_location(sec, path),
                     path: None,
+                    raw: body.raw.then_some(true),
+                    asset: body.asset,
+                    condition: extract_directive(&sec.nodes, CONDITION_PREFIX),
+                    each: parse_each(&sec.nodes, &section_location(sec, path))?,
+                    patch,
+                    merge: parse_merge(&sec.nodes, &section_location(sec, path))?,
+                    hooks: extract_hooks(&sec.nodes),
                 };
                 tmpl.path = extract_inline_path_before_code(&sec.nodes)
                     .or_else(|| extract_inline_path_from_heading(sec));
-                enrich_template_args(&mut tmpl)?;
+                enrich_template_args(&mut tmpl, helpers)?;
+                file_sections.push((sec.path.clone(), tmpl.clone()));
                 // Parent path (all but last segment)
                 let parent_path = if sec.path.len() > 1 {
                     sec.path[..(sec.path.len() - 1)].to_vec()
@@ -222,7 +330,7 @@ fn parse_str_with_path(
             }
 
             let mut templates: Vec<Template> = Vec::new();
-            let mut trees: Vec<TemplateTree> = Vec::new();
+            let mut trees: Vec<(Vec<String>, TemplateTree)> = Vec::new();
 
             // Helper to find a section by its full path
             let find_section =
@@ -246,27 +354,215 @@ fn parse_str_with_path(
                                 .into_iter()
                                 .map(Template::TemplateFile)
                                 .collect(),
+                            includes: parse_includes(parent_sec, path)?,
+                            hooks: extract_hooks(&parent_sec.nodes),
                             location: section_location(parent_sec, path),
                         };
-                        trees.push(tree);
+                        trees.push((parent_path.clone(), tree));
                     }
                 }
                 templates.extend(files.into_iter().map(Template::TemplateFile));
             }
 
-            for tr in trees {
+            for tr in nest_trees(&sections, trees, &file_sections, path)? {
                 templates.push(Template::TemplateTree(tr));
             }
             Ok(ParsedMarkdown::Collection(TemplateCollection {
                 name: file_stem.unwrap_or("Untitled").to_string(),
                 description: String::new(),
                 templates,
+                partials: Vec::new(),
                 location: make_location(path, root_span),
             }))
         }
     }
 }
 
+/// Fold trees into their parent section when that section has at least two direct child
+/// sections, every one of them a tree or a file with a path, and at least one of them a
+/// tree. Repeats bottom up so trees can nest at any depth.
+fn nest_trees(
+    sections: &[Section],
+    mut trees: Vec<(Vec<String>, TemplateTree)>,
+    files: &[(Vec<String>, TemplateFile)],
+    path: Option<&Path>,
+) -> Result<Vec<TemplateTree>> {
+    let mut parents: Vec<&Section> = sections.iter().collect();
+    parents.sort_by_key(|section| std::cmp::Reverse(section.path.len()));
+
+    'parents: for parent in parents {
+        let children: Vec<&Section> = sections
+            .iter()
+            .filter(|section| {
+                section.path.len() == parent.path.len() + 1
+                    && section.path.starts_with(&parent.path)
+            })
+            .collect();
+        let has_subtree = children
+            .iter()
+            .any(|child| trees.iter().any(|(tree_path, _)| tree_path == &child.path));
+        if children.len() < 2 || !has_subtree {
+            continue;
+        }
+
+        let mut members: Vec<Template> = Vec::new();
+        for child in children {
+            if let Some(idx) = trees
+                .iter()
+                .position(|(tree_path, _)| tree_path == &child.path)
+            {
+                members.push(Template::TemplateTree(trees[idx].1.clone()));
+            } else if let Some((_, file)) = files
+                .iter()
+                .find(|(file_path, file)| file_path == &child.path && file.path.is_some())
+            {
+                members.push(Template::TemplateFile(file.clone()));
+            } else {
+                continue 'parents;
+            }
+        }
+
+        let id = EntityId::new().from_segments(parent.path.iter().map(|s| s.as_str()));
+        if id.is_empty() {
+            continue;
+        }
+        let tree = TemplateTree {
+            kind: TemplateTreeKindTree,
+            id,
+            name: parent.title.clone(),
+            description: collect_tree_description(parent),
+            files: members,
+            includes: parse_includes(parent, path)?,
+            hooks: extract_hooks(&parent.nodes),
+            location: section_location(parent, path),
+        };
+
+        // The parent replaces its subtrees (and any flat tree of its own files) in place.
+        let absorbed = |tree_path: &Vec<String>| tree_path.starts_with(&parent.path);
+        let insert_at = trees
+            .iter()
+            .position(|(tree_path, _)| absorbed(tree_path))
+            .unwrap_or(trees.len());
+        trees.retain(|(tree_path, _)| !absorbed(tree_path));
+        trees.insert(insert_at.min(trees.len()), (parent.path.clone(), tree));
+    }
+
+    Ok(trees.into_iter().map(|(_, tree)| tree).collect())
+}
+
+fn wrap_collection(
+    templates: Vec<Template>,
+    partials: Vec<TemplatePartial>,
+    path: Option<&Path>,
+    file_stem: Option<&str>,
+    root_span: Option<Span>,
+) -> TemplateCollection {
+    TemplateCollection {
+        name: file_stem.unwrap_or("Untitled").to_string(),
+        description: String::new(),
+        templates,
+        partials,
+        location: make_location(path, root_span),
+    }
+}
+
+/// Split `Partials` sections out of the document, returning the remaining nodes and the
+/// partials declared in those sections.
+fn take_partials_sections(
+    nodes: &[mdast::Node],
+    path: Option<&Path>,
+) -> Result<(Vec<mdast::Node>, Vec<TemplatePartial>)> {
+    let mut remaining = Vec::new();
+    let mut partials = Vec::new();
+    let mut idx = 0;
+    while idx < nodes.len() {
+        let depth = match &nodes[idx] {
+            mdast::Node::Heading(h)
+                if matches_subhead(&inline_text(&h.children), &["partials"]) =>
+            {
+                h.depth
+            }
+            node => {
+                remaining.push(node.clone());
+                idx += 1;
+                continue;
+            }
+        };
+        let end = nodes[(idx + 1)..]
+            .iter()
+            .position(|node| matches!(node, mdast::Node::Heading(h) if h.depth <= depth))
+            .map(|offset| idx + 1 + offset)
+            .unwrap_or(nodes.len());
+        partials.extend(parse_partials(&nodes[(idx + 1)..end], path, "")?);
+        idx = end;
+    }
+    Ok((remaining, partials))
+}
+
+/// Parse every heading with a single code block (and no subheadings) as a partial. A lone
+/// code block without headings is named after the file.
+fn parse_partials(
+    nodes: &[mdast::Node],
+    path: Option<&Path>,
+    fallback_name: &str,
+) -> Result<Vec<TemplatePartial>> {
+    let sections = make_sections(nodes);
+    if sections.is_empty() {
+        let mut codes = collect_code_blocks(nodes);
+        let name = EntityId::normalize_segment(fallback_name);
+        if codes.len() != 1 || name.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut span = None;
+        extend_span_with_nodes(&mut span, nodes);
+        return Ok(vec![TemplatePartial {
+            name,
+            description: String::new(),
+            content: codes.remove(0).content,
+            location: make_location(path, span),
+        }]);
+    }
+
+    let mut partials = Vec::new();
+    for sec in &sections {
+        let has_subheads = make_sections(&sec.nodes)
+            .iter()
+            .any(|s| s.level > sec.level);
+        if has_subheads {
+            continue;
+        }
+        let mut codes = collect_code_blocks(&sec.nodes);
+        if codes.len() != 1 {
+            continue;
+        }
+        let name = extract_inline_path_from_heading(sec)
+            .unwrap_or_else(|| EntityId::normalize_segment(&sec.title));
+        if name.is_empty() {
+            bail!(
+                "Unable to derive partial name from heading '{}' at {}.",
+                sec.title,
+                FormattedLocation(&section_location(sec, path))
+            );
+        }
+        partials.push(TemplatePartial {
+            name,
+            description: collect_description(sec),
+            content: codes.remove(0).content,
+            location: section_location(sec, path),
+        });
+    }
+    Ok(partials)
+}
+
+fn contains_code(nodes: &[mdast::Node]) -> bool {
+    nodes.iter().any(|node| match node {
+        mdast::Node::Code(code) => !is_hook_block(code),
+        other => other
+            .children()
+            .is_some_and(|children| contains_code(children)),
+    })
+}
+
 fn make_sections(nodes: &[mdast::Node]) -> Vec<Section> {
     // Collect indices of headings
     let mut heads: Vec<(usize, u8, String, Option<Span>)> = Vec::new();
@@ -330,12 +626,14 @@ fn is_allowed(title: &str) -> bool {
 fn parse_template_from_section(
     section: &Section,
     path: Option<&Path>,
+    helpers: &BTreeSet<String>,
 ) -> Result<Option<TemplateFile>> {
     let subsections = make_sections(&section.nodes);
 
     // Name/description from section itself
+    let patch = parse_patch(&section.nodes, &section_location(section, path))?;
     let mut tmpl = TemplateFile {
-        kind: TemplateFileKindFile,
+        kind: file_kind(&patch),
         id: EntityId::new().from_segments(section.path.iter().map(|s| s.as_str())),
         name: section.title.clone(),
         description: collect_description(section),
@@ -344,6 +642,13 @@ fn parse_template_from_section(
         content: String::new(),
         location: section_location(section, path),
         path: None,
+        raw: None,
+        asset: None,
+        condition: extract_directive(&section.nodes, CONDITION_PREFIX),
+        each: parse_each(&section.nodes, &section_location(section, path))?,
+        patch,
+        merge: parse_merge(&section.nodes, &section_location(section, path))?,
+        hooks: extract_hooks(&section.nodes),
     };
 
     if tmpl.id.is_empty() {
@@ -365,19 +670,18 @@ fn parse_template_from_section(
     if let Some(tpl_sec) = subsections
         .iter()
         .find(|s| matches_subhead(&s.title, &["template"]))
-        && let Some((lang, content)) = collect_code_blocks(&tpl_sec.nodes).into_iter().next()
+        && let Some(body) = collect_file_bodies(&tpl_sec.nodes, &tmpl.location)?
+            .into_iter()
+            .next()
     {
-        tmpl.lang = lang;
-        tmpl.content = content;
+        body.apply(&mut tmpl);
     }
 
-    // Fallback: single code block anywhere in the section
-    if tmpl.content.is_empty() {
-        let mut codes = collect_code_blocks(&section.nodes);
-        if codes.len() == 1 {
-            let (lang, content) = codes.remove(0);
-            tmpl.lang = lang;
-            tmpl.content = content;
+    // Fallback: single code block or asset anywhere in the section
+    if !has_body(&tmpl) {
+        let mut bodies = collect_file_bodies(&section.nodes, &tmpl.location)?;
+        if bodies.len() == 1 {
+            bodies.remove(0).apply(&mut tmpl);
         }
     }
 
@@ -385,12 +689,12 @@ fn parse_template_from_section(
     tmpl.path = extract_inline_path_before_code(&section.nodes)
         .or_else(|| extract_inline_path_from_heading(section));
 
-    if tmpl.content.is_empty() {
+    if !has_body(&tmpl) {
         // No content yet — this section is not a complete template
         return Ok(None);
     }
 
-    enrich_template_args(&mut tmpl)?;
+    enrich_template_args(&mut tmpl, helpers)?;
 
     Ok(Some(tmpl))
 }
@@ -408,12 +712,26 @@ fn collect_description(section: &Section) -> String {
     let mut out = String::new();
     for node in &section.nodes {
         match node {
+            mdast::Node::Paragraph(p) if asset_url(p).is_some() => {}
             mdast::Node::Paragraph(p) => {
+                let text = inline_text(&p.children);
+                let prose: Vec<&str> = text
+                    .lines()
+                    .filter(|line| {
+                        [CONDITION_PREFIX, EACH_PREFIX, PATCH_PREFIX, MERGE_PREFIX]
+                            .iter()
+                            .all(|prefix| line_directive(line, prefix).is_none())
+                    })
+                    .collect();
+                if prose.is_empty() {
+                    continue;
+                }
                 if !out.is_empty() {
                     out.push_str("\n\n");
                 }
-                out.push_str(&inline_text(&p.children));
+                out.push_str(&prose.join("\n"));
             }
+            mdast::Node::Code(code) if is_hook_block(code) => {}
             mdast::Node::Heading(_) | mdast::Node::Code(_) => break,
             _ => {}
         }
@@ -428,10 +746,18 @@ fn collect_tree_description(section: &Section) -> String {
         match node {
             mdast::Node::Heading(_) => break,
             mdast::Node::Paragraph(p) => {
+                let text = inline_text(&p.children);
+                let prose: Vec<&str> = text
+                    .lines()
+                    .filter(|line| line_directive(line, INCLUDE_PREFIX).is_none())
+                    .collect();
+                if prose.is_empty() {
+                    continue;
+                }
                 if !out.is_empty() {
                     out.push_str("\n\n");
                 }
-                out.push_str(&inline_text(&p.children));
+                out.push_str(&prose.join("\n"));
             }
             _ => {}
         }
@@ -439,11 +765,362 @@ fn collect_tree_description(section: &Section) -> String {
     out.trim().to_string()
 }
 
-fn collect_code_blocks(nodes: &[mdast::Node]) -> Vec<(Option<String>, String)> {
-    let mut acc: Vec<(Option<String>, String)> = Vec::new();
+/// Parse `include: <id> [at <path>] [with <arg>=<tree_arg>, ...]` lines written in
+/// paragraphs or list items before the first child heading of a tree section.
+fn parse_includes(section: &Section, path: Option<&Path>) -> Result<Vec<TemplateInclude>> {
+    tree_directives(section, path, INCLUDE_PREFIX)
+        .into_iter()
+        .map(|(spec, location)| parse_include(&spec, &location))
+        .collect()
+}
+
+/// Values of `prefix:` lines written in paragraphs or list items before the first child
+/// heading of a tree section, with the location of the block holding each.
+fn tree_directives(
+    section: &Section,
+    path: Option<&Path>,
+    prefix: &str,
+) -> Vec<(String, Location)> {
+    let mut directives = Vec::new();
+    for node in &section.nodes {
+        let items: Vec<&mdast::Node> = match node {
+            mdast::Node::Heading(_) => break,
+            mdast::Node::Paragraph(_) => vec![node],
+            mdast::Node::List(list) => list.children.iter().collect(),
+            _ => continue,
+        };
+        for item in items {
+            let text = match item {
+                mdast::Node::ListItem(list_item) => inline_text(&list_item.children),
+                other => inline_text(std::slice::from_ref(other)),
+            };
+            let location = make_location(path, item.position().map(position_to_span));
+            directives.extend(
+                text.lines()
+                    .filter_map(|line| line_directive(line, prefix))
+                    .filter(|value| !value.is_empty())
+                    .map(|value| (value.to_string(), location.clone())),
+            );
+        }
+    }
+    directives
+}
+
+/// Return the rest of a `prefix:` metadata line such as `include: ...` or `when: ...`.
+fn line_directive<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let line = line.trim();
+    let head = line.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| line[prefix.len()..].trim())
+}
+
+/// Read a metadata line such as `when: <expression>` from the prose before a file's code
+/// block.
+fn extract_directive(nodes: &[mdast::Node], prefix: &str) -> Option<String> {
+    extract_directives(nodes, prefix).into_iter().next()
+}
+
+/// Read every metadata line with `prefix` before a file's code block.
+fn extract_directives(nodes: &[mdast::Node], prefix: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    for node in nodes {
+        match node {
+            mdast::Node::Code(code) if is_hook_block(code) => {}
+            mdast::Node::Code(_) | mdast::Node::Heading(_) => break,
+            mdast::Node::Paragraph(p) => {
+                let text = inline_text(&p.children);
+                values.extend(
+                    text.lines()
+                        .filter_map(|line| line_directive(line, prefix))
+                        .filter(|value| !value.is_empty())
+                        .map(str::to_string),
+                );
+            }
+            _ => {}
+        }
+    }
+    values
+}
+
+/// Read the commands of the ```` ```sh hook ```` blocks before the first subheading of a
+/// file or tree, one per non-empty line.
+fn extract_hooks(nodes: &[mdast::Node]) -> Vec<String> {
+    let mut hooks = Vec::new();
+    for node in nodes {
+        match node {
+            mdast::Node::Heading(_) => break,
+            mdast::Node::Code(code) if is_hook_block(code) => hooks.extend(
+                code.value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string),
+            ),
+            _ => {}
+        }
+    }
+    hooks
+}
+
+/// Whether a fenced code block holds hooks rather than file content.
+fn is_hook_block(code: &mdast::Code) -> bool {
+    code.lang
+        .iter()
+        .chain(&code.meta)
+        .flat_map(|info| info.split_whitespace())
+        .any(|word| word.eq_ignore_ascii_case(HOOK_FENCE))
+}
+
+/// Parse the `each: <array_arg> [as <name>]` line of a file; the element binding defaults
+/// to `item`.
+fn parse_each(nodes: &[mdast::Node], location: &Location) -> Result<Option<TemplateEach>> {
+    let Some(spec) = extract_directive(nodes, EACH_PREFIX) else {
+        return Ok(None);
+    };
+    let (arg, binding) = match spec.split_once(" as ") {
+        Some((arg, binding)) => (arg.trim(), binding.trim()),
+        None => (spec.trim(), "item"),
+    };
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    };
+    if !is_name(arg) || !is_name(binding) || arg == binding {
+        bail!(
+            "Invalid each '{spec}' at {}: expected `each: <array_arg> [as <name>]`",
+            FormattedLocation(location)
+        );
+    }
+    Ok(Some(TemplateEach {
+        arg: arg.to_string(),
+        binding: binding.to_string(),
+    }))
+}
+
+/// Parse the `patch: <op> [...]` line of a file:
+/// `append`, `prepend`, `before <regex>`, `after <regex>` or `between <start> and <end>`.
+fn parse_patch(nodes: &[mdast::Node], location: &Location) -> Result<Option<TemplatePatch>> {
+    let Some(spec) = extract_directive(nodes, PATCH_PREFIX) else {
+        return Ok(None);
+    };
+    let invalid = || {
+        anyhow!(
+            "Invalid patch '{spec}' at {}: expected `patch: append|prepend`, `patch: before|after <regex>` or `patch: between <start> and <end>`",
+            FormattedLocation(location)
+        )
+    };
+    let (op, rest) = match spec.split_once(char::is_whitespace) {
+        Some((op, rest)) => (op, rest.trim()),
+        None => (spec.as_str(), ""),
+    };
+    let mut patch = TemplatePatch {
+        op: TemplatePatchOp::Append(TemplatePatchOpAppend),
+        anchor: None,
+        start: None,
+        end: None,
+    };
+    match op.to_ascii_lowercase().as_str() {
+        "append" | "prepend" if !rest.is_empty() => return Err(invalid()),
+        "append" => {}
+        "prepend" => patch.op = TemplatePatchOp::Prepend(TemplatePatchOpPrepend),
+        "before" | "after" => {
+            if rest.is_empty() {
+                return Err(invalid());
+            }
+            if let Err(err) = anchor_regex(rest) {
+                bail!("{err:#} at {}", FormattedLocation(location));
+            }
+            patch.op = if op.eq_ignore_ascii_case("before") {
+                TemplatePatchOp::Before(TemplatePatchOpBefore)
+            } else {
+                TemplatePatchOp::After(TemplatePatchOpAfter)
+            };
+            patch.anchor = Some(rest.to_string());
+        }
+        "between" => {
+            let (start, end) = rest.split_once(" and ").ok_or_else(invalid)?;
+            let (start, end) = (start.trim(), end.trim());
+            if start.is_empty() || end.is_empty() {
+                return Err(invalid());
+            }
+            patch.op = TemplatePatchOp::Between(TemplatePatchOpBetween);
+            patch.start = Some(start.to_string());
+            patch.end = Some(end.to_string());
+        }
+        _ => return Err(invalid()),
+    }
+    Ok(Some(patch))
+}
+
+/// Parse the `merge: append-unique|replace` line of a file, naming how arrays are merged.
+fn parse_merge(nodes: &[mdast::Node], location: &Location) -> Result<Option<TemplateMerge>> {
+    let Some(spec) = extract_directive(nodes, MERGE_PREFIX) else {
+        return Ok(None);
+    };
+    let arrays = match spec.to_ascii_lowercase().as_str() {
+        "append-unique" => TemplateMergeArrays::AppendUnique(TemplateMergeArraysAppendUnique),
+        "replace" => TemplateMergeArrays::Replace(TemplateMergeArraysReplace),
+        _ => bail!(
+            "Invalid merge '{spec}' at {}: expected `merge: append-unique` or `merge: replace`",
+            FormattedLocation(location)
+        ),
+    };
+    Ok(Some(TemplateMerge { arrays }))
+}
+
+fn file_kind(patch: &Option<TemplatePatch>) -> TemplateFileKind {
+    match patch {
+        Some(_) => TemplateFileKind::Patch(TemplateFileKindPatch),
+        None => TemplateFileKind::File(TemplateFileKindFile),
+    }
+}
+
+fn parse_include(spec: &str, location: &Location) -> Result<TemplateInclude> {
+    let invalid = || {
+        anyhow!(
+            "Invalid include '{spec}' at {}: expected `include: <id> [at <path>] [with <arg>=<tree_arg>, ...]`",
+            FormattedLocation(location)
+        )
+    };
+
+    let (head, with) = match spec.split_once(" with ") {
+        Some((head, with)) => (head, Some(with)),
+        None => (spec, None),
+    };
+    let (id, at) = match head.split_once(" at ") {
+        Some((id, at)) => (id.trim(), Some(at.trim())),
+        None => (head.trim(), None),
+    };
+    if id.is_empty() || id.contains(char::is_whitespace) || at.is_some_and(str::is_empty) {
+        return Err(invalid());
+    }
+
+    let mut args = BTreeMap::new();
+    for pair in with
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|pair| !pair.is_empty())
+    {
+        let (name, from) = pair.split_once('=').ok_or_else(invalid)?;
+        if name.is_empty() || from.is_empty() {
+            return Err(invalid());
+        }
+        args.insert(name.to_string(), from.to_string());
+    }
+    if with.is_some() && args.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(TemplateInclude {
+        id: id.to_string(),
+        path: at.map(str::to_string),
+        args,
+        location: location.clone(),
+    })
+}
+
+/// Content of a file template: a fenced code block or a referenced asset.
+struct FileBody {
+    lang: Option<String>,
+    content: String,
+    /// The fence is marked `raw` or `verbatim`.
+    raw: bool,
+    asset: Option<String>,
+}
+
+impl FileBody {
+    fn apply(self, template: &mut TemplateFile) {
+        template.lang = self.lang;
+        template.content = self.content;
+        template.raw = self.raw.then_some(true);
+        template.asset = self.asset;
+    }
+}
+
+fn has_body(template: &TemplateFile) -> bool {
+    !template.content.is_empty() || template.asset.is_some()
+}
+
+/// Code blocks of a section, falling back to its asset references when it has none.
+fn collect_file_bodies(nodes: &[mdast::Node], location: &Location) -> Result<Vec<FileBody>> {
+    let codes = collect_code_blocks(nodes);
+    if !codes.is_empty() {
+        return Ok(codes);
+    }
+    let mut bodies = Vec::new();
+    for node in nodes {
+        let mdast::Node::Paragraph(paragraph) = node else {
+            continue;
+        };
+        let Some(url) = asset_url(paragraph) else {
+            continue;
+        };
+        if !stays_inside(&url) {
+            bail!(
+                "Asset '{url}' at {} is outside the directory of its markdown file.",
+                FormattedLocation(location)
+            );
+        }
+        bodies.push(FileBody {
+            lang: None,
+            content: String::new(),
+            raw: false,
+            asset: Some(url),
+        });
+    }
+    Ok(bodies)
+}
+
+/// Relative url of a paragraph made up of a single image, e.g. `![logo](./logo.png)`, or of
+/// an `asset: ./report.pdf` line, where the path may also be a link or inline code. Plain
+/// links are prose.
+fn asset_url(paragraph: &mdast::Paragraph) -> Option<String> {
+    let url = match paragraph.children.as_slice() {
+        [mdast::Node::Image(image)] => image.url.clone(),
+        [mdast::Node::Text(text), mdast::Node::Link(link)]
+            if text.value.trim().eq_ignore_ascii_case(ASSET_PREFIX) =>
+        {
+            link.url.clone()
+        }
+        children => inline_text(children)
+            .lines()
+            .find_map(|line| line_directive(line, ASSET_PREFIX))?
+            .to_string(),
+    };
+    (url.starts_with("./") || url.starts_with("../")).then_some(url)
+}
+
+/// Whether a relative url never climbs above the directory it is relative to.
+fn stays_inside(url: &str) -> bool {
+    let mut depth = 0usize;
+    for component in Path::new(url).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::ParentDir if depth > 0 => depth -= 1,
+            _ => return false,
+        }
+    }
+    true
+}
+
+fn collect_code_blocks(nodes: &[mdast::Node]) -> Vec<FileBody> {
+    let mut acc: Vec<FileBody> = Vec::new();
     for node in nodes {
         match node {
-            mdast::Node::Code(code) => acc.push((code.lang.clone(), code.value.clone())),
+            mdast::Node::Code(code) if is_hook_block(code) => {}
+            mdast::Node::Code(code) => acc.push(FileBody {
+                lang: code.lang.clone(),
+                content: code.value.clone(),
+                raw: code.meta.as_deref().is_some_and(|meta| {
+                    meta.split_whitespace().any(|word| {
+                        word.eq_ignore_ascii_case("raw") || word.eq_ignore_ascii_case("verbatim")
+                    })
+                }),
+                asset: None,
+            }),
             // Recurse into list and list items
             mdast::Node::List(list) => {
                 acc.extend(collect_code_blocks(&list.children));
@@ -459,6 +1136,7 @@ fn collect_code_blocks(nodes: &[mdast::Node]) -> Vec<(Option<String>, String)> {
 fn extract_inline_path_before_code(nodes: &[mdast::Node]) -> Option<String> {
     for node in nodes {
         match node {
+            mdast::Node::Code(code) if is_hook_block(code) => {}
             mdast::Node::Code(_) => break,
             mdast::Node::Paragraph(p) => {
                 // Heuristic: paragraph text ends with ':' and contains at least one inline code
@@ -547,6 +1225,7 @@ fn parse_arg_item(item: &mdast::ListItem) -> Option<Arg> {
 
     let mut remainder = tail.trim_start().to_string();
     let kind = extract_kind(&mut remainder);
+    let derived = extract_derived(&mut remainder);
     let description = normalize_description(&remainder);
 
     Some(Arg {
@@ -554,27 +1233,56 @@ fn parse_arg_item(item: &mdast::ListItem) -> Option<Arg> {
         description,
         kind,
         required,
+        derived,
     })
 }
 
 fn extract_kind(remainder: &mut String) -> ArgKind {
-    if remainder.starts_with('[') {
-        if let Some(end) = remainder.find(']') {
-            let kind_text = remainder[1..end].trim().to_string();
-            remainder.drain(..=end);
-            return match kind_text.to_ascii_lowercase().as_str() {
-                "boolean" => ArgKind::Boolean(ArgKindBoolean),
-                "string" => ArgKind::String(ArgKindString),
-                "number" => ArgKind::Number(ArgKindNumber),
-                "any" => ArgKind::Any(ArgKindAny),
-                _ => ArgKind::Any(ArgKindAny),
-            };
-        }
+    if remainder.starts_with('[')
+        && let Some(end) = remainder.find(']')
+    {
+        let kind_text = remainder[1..end].trim().to_string();
+        remainder.drain(..=end);
+        return match kind_text.to_ascii_lowercase().as_str() {
+            "boolean" => ArgKind::Boolean(ArgKindBoolean),
+            "string" => ArgKind::String(ArgKindString),
+            "number" => ArgKind::Number(ArgKindNumber),
+            "array" => ArgKind::Array(ArgKindArray),
+            "object" => ArgKind::Object(ArgKindObject),
+            "any" => ArgKind::Any(ArgKindAny),
+            _ => ArgKind::Any(ArgKindAny),
+        };
     }
 
     ArgKind::Any(ArgKindAny)
 }
 
+fn extract_derived(remainder: &mut String) -> Option<String> {
+    // Derived args follow the name (and kind) with `= expression`, optionally
+    // followed by `: description`. Colons inside `{{...}}` belong to the expression.
+    let rest = remainder.trim_start().strip_prefix('=')?.trim_start();
+    let mut depth = 0usize;
+    let mut end = rest.len();
+    let mut chars = rest.char_indices().peekable();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 && chars.peek().is_none_or(|(_, next)| next.is_whitespace()) => {
+                end = idx;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let expression = rest[..end].trim().to_string();
+    if expression.is_empty() {
+        return None;
+    }
+    *remainder = rest[end..].to_string();
+    Some(expression)
+}
+
 fn normalize_description(input: &str) -> String {
     let trimmed = input.trim_start();
     let stripped = trimmed
@@ -587,6 +1295,9 @@ fn normalize_description(input: &str) -> String {
 enum PlaceholderSource {
     Content,
     Path,
+    Condition,
+    Each,
+    Derived,
 }
 
 impl PlaceholderSource {
@@ -594,15 +1305,19 @@ impl PlaceholderSource {
         match self {
             PlaceholderSource::Content => "content",
             PlaceholderSource::Path => "path",
+            PlaceholderSource::Condition => "condition",
+            PlaceholderSource::Each => "each",
+            PlaceholderSource::Derived => "derived args",
         }
     }
 }
 
 fn describe_sources(sources: &BTreeSet<PlaceholderSource>) -> String {
-    match sources.len() {
-        0 => String::new(),
-        1 => format!("in {}", sources.iter().next().unwrap().label()),
-        _ => "in content and path".to_string(),
+    let labels: Vec<&str> = sources.iter().map(|source| source.label()).collect();
+    match labels.split_last() {
+        None => String::new(),
+        Some((last, [])) => format!("in {}", last),
+        Some((last, rest)) => format!("in {} and {}", rest.join(", "), last),
     }
 }
 
@@ -684,74 +1399,222 @@ fn inline_text(nodes: &[mdast::Node]) -> String {
     out
 }
 
-fn enrich_template_args(template: &mut TemplateFile) -> Result<()> {
-    let mut discovered: BTreeMap<String, BTreeSet<PlaceholderSource>> = BTreeMap::new();
+/// How a placeholder is used across the template content, path and derived args.
+struct PlaceholderUsage {
+    sources: BTreeSet<PlaceholderSource>,
+    /// Sources that use the placeholder outside of `#if` guards.
+    unguarded: BTreeSet<PlaceholderSource>,
+    shape: PlaceholderShape,
+    condition_only: bool,
+}
+
+impl PlaceholderUsage {
+    fn new() -> Self {
+        Self {
+            sources: BTreeSet::new(),
+            unguarded: BTreeSet::new(),
+            shape: PlaceholderShape::Scalar,
+            condition_only: true,
+        }
+    }
 
-    for placeholder in discover_placeholders(&template.content) {
+    fn add(&mut self, placeholder: Placeholder, source: PlaceholderSource) {
+        self.sources.insert(source);
+        if !placeholder.guarded {
+            self.unguarded.insert(source);
+        }
+        self.condition_only &= placeholder.condition_only;
+        self.shape = std::mem::take(&mut self.shape).merge(placeholder.shape);
+    }
+
+    fn inferred_kind(&self) -> ArgKind {
+        match self.shape {
+            PlaceholderShape::Iterable(_) => ArgKind::Array(ArgKindArray),
+            PlaceholderShape::Object(_) => ArgKind::Object(ArgKindObject),
+            PlaceholderShape::Scalar if self.condition_only => ArgKind::Boolean(ArgKindBoolean),
+            PlaceholderShape::Scalar => ArgKind::Any(ArgKindAny),
+        }
+    }
+
+    /// Describe the usage when it can't work with a value of the documented kind.
+    fn kind_conflict(&self, documented: &ArgKind) -> Option<&'static str> {
+        match (documented, &self.shape) {
+            (ArgKind::Any(_), _) | (_, PlaceholderShape::Scalar) => None,
+            (ArgKind::Array(_), PlaceholderShape::Iterable(_))
+            | (ArgKind::Object(_), PlaceholderShape::Iterable(_) | PlaceholderShape::Object(_)) => {
+                None
+            }
+            (_, PlaceholderShape::Iterable(_)) => Some("iterated with #each"),
+            (_, PlaceholderShape::Object(_)) => Some("accessed by field"),
+        }
+    }
+}
+
+fn kind_label(kind: &ArgKind) -> &'static str {
+    match kind {
+        ArgKind::Any(_) => "any",
+        ArgKind::Boolean(_) => "boolean",
+        ArgKind::String(_) => "string",
+        ArgKind::Number(_) => "number",
+        ArgKind::Array(_) => "array",
+        ArgKind::Object(_) => "object",
+    }
+}
+
+fn enrich_template_args(template: &mut TemplateFile, helpers: &BTreeSet<String>) -> Result<()> {
+    if template.args.iter().any(|arg| arg.name == RESERVED_KEY) {
+        bail!(
+            "Template '{}' at {} declares the argument '{RESERVED_KEY}', which is reserved for nmcr.",
+            template.id,
+            FormattedLocation(&template.location)
+        );
+    }
+    if template.patch.is_some() && template.path.is_none() {
+        bail!(
+            "Patch template '{}' at {} has no path to patch.",
+            template.id,
+            FormattedLocation(&template.location)
+        );
+    }
+    if template.merge.is_some() {
+        let mergeable = template.patch.is_none()
+            && MergeFormat::detect(template.lang.as_deref(), template.path.as_deref()).is_some();
+        if !mergeable {
+            bail!(
+                "Template '{}' at {} merges but is not a JSON, TOML or YAML file.",
+                template.id,
+                FormattedLocation(&template.location)
+            );
+        }
+    }
+
+    let mut discovered: BTreeMap<String, PlaceholderUsage> = BTreeMap::new();
+
+    // Verbatim content is never rendered, so it declares no args
+    let content = if template.raw == Some(true) {
+        ""
+    } else {
+        &template.content
+    };
+    for placeholder in discover_placeholders_with_helpers(content, helpers) {
         discovered
-            .entry(placeholder.name)
-            .or_default()
-            .insert(PlaceholderSource::Content);
+            .entry(placeholder.name.clone())
+            .or_insert_with(PlaceholderUsage::new)
+            .add(placeholder, PlaceholderSource::Content);
     }
 
     if let Some(path_tpl) = &template.path {
-        for placeholder in discover_placeholders(path_tpl) {
+        for placeholder in discover_placeholders_with_helpers(path_tpl, helpers) {
             discovered
-                .entry(placeholder.name)
-                .or_default()
-                .insert(PlaceholderSource::Path);
+                .entry(placeholder.name.clone())
+                .or_insert_with(PlaceholderUsage::new)
+                .add(placeholder, PlaceholderSource::Path);
         }
     }
 
-    if discovered.is_empty() {
-        return Ok(());
+    if let Some(condition) = &template.condition {
+        for placeholder in
+            discover_placeholders_with_helpers(&condition_template(condition), helpers)
+        {
+            discovered
+                .entry(placeholder.name.clone())
+                .or_insert_with(PlaceholderUsage::new)
+                .add(placeholder, PlaceholderSource::Condition);
+        }
     }
 
-    let manual_args = template.args.clone();
-    let mut manual_lookup: BTreeMap<String, Arg> = BTreeMap::new();
-    for arg in &manual_args {
-        manual_lookup.insert(arg.name.clone(), arg.clone());
+    if let Some(each) = &template.each {
+        // The element is bound per file, so it feeds the shape of the array arg instead
+        let element = discovered
+            .remove(&each.binding)
+            .map(|usage| usage.shape)
+            .unwrap_or_default();
+        let placeholder = Placeholder {
+            name: each.arg.clone(),
+            shape: PlaceholderShape::Iterable(Box::new(element)),
+            condition_only: false,
+            guarded: false,
+            occurrences: Vec::new(),
+        };
+        discovered
+            .entry(each.arg.clone())
+            .or_insert_with(PlaceholderUsage::new)
+            .add(placeholder, PlaceholderSource::Each);
     }
 
-    for (name, sources) in &discovered {
-        if let Some(arg) = manual_lookup.get(name) {
-            if !arg.required {
-                let location = FormattedLocation(&template.location);
-                let where_str = describe_sources(sources);
-                let detail = if where_str.is_empty() {
-                    String::new()
-                } else {
-                    format!(" {}", where_str)
-                };
-                bail!(
-                    "Argument '{}' is documented as optional but used{} for template '{}' at {}.",
-                    name,
-                    detail,
-                    template.id,
-                    location
-                );
-            }
+    let derived = DerivedArg::from_args(&template.args);
+    if let Err(err) = derived_order(&derived) {
+        bail!(
+            "{} for template '{}' at {}.",
+            err,
+            template.id,
+            FormattedLocation(&template.location)
+        );
+    }
+    for arg in &derived {
+        for placeholder in discover_placeholders_with_helpers(arg.expression, helpers) {
+            // Derived args are computed on every render, so their inputs are never guarded
+            let placeholder = Placeholder {
+                guarded: false,
+                ..placeholder
+            };
+            discovered
+                .entry(placeholder.name.clone())
+                .or_insert_with(PlaceholderUsage::new)
+                .add(placeholder, PlaceholderSource::Derived);
         }
     }
 
-    let mut final_args = Vec::new();
-    let mut seen = BTreeSet::new();
-    for arg in manual_args {
-        seen.insert(arg.name.clone());
-        final_args.push(arg);
+    if discovered.is_empty() {
+        return Ok(());
+    }
+
+    for arg in template.args.iter_mut() {
+        let Some(usage) = discovered.get(&arg.name) else {
+            continue;
+        };
+        if arg.derived.is_some() {
+            continue;
+        }
+
+        let location = FormattedLocation(&template.location);
+        if !arg.required && !usage.unguarded.is_empty() {
+            bail!(
+                "Argument '{}' is documented as optional but used {} without an #if guard for template '{}' at {}.",
+                arg.name,
+                describe_sources(&usage.unguarded),
+                template.id,
+                location
+            );
+        }
+        if let Some(conflict) = usage.kind_conflict(&arg.kind) {
+            bail!(
+                "Argument '{}' is documented as {} but {} {} for template '{}' at {}.",
+                arg.name,
+                kind_label(&arg.kind),
+                conflict,
+                describe_sources(&usage.sources),
+                template.id,
+                location
+            );
+        }
+        if let ArgKind::Any(_) = arg.kind {
+            arg.kind = usage.inferred_kind();
+        }
     }
 
-    for name in discovered.keys() {
+    let mut seen: BTreeSet<String> = template.args.iter().map(|arg| arg.name.clone()).collect();
+    for (name, usage) in &discovered {
         if seen.insert(name.clone()) {
-            final_args.push(Arg {
+            template.args.push(Arg {
                 name: name.clone(),
                 description: String::new(),
-                kind: ArgKind::Any(ArgKindAny),
-                required: true,
+                kind: usage.inferred_kind(),
+                required: !usage.unguarded.is_empty(),
+                derived: None,
             });
         }
     }
 
-    template.args = final_args;
     Ok(())
 }
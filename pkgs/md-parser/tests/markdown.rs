@@ -1,7 +1,8 @@
 use indoc::indoc;
 use nmcr_md_parser::ParsedMarkdown;
-use nmcr_md_parser::markdown::parse_str;
-use nmcr_types::{ArgKind, Template};
+use nmcr_md_parser::markdown::{parse_str, parse_str_with_helpers};
+use nmcr_types::{ArgKind, ArgKindAny, ArgKindArray, ArgKindBoolean, ArgKindObject, Template};
+use std::collections::BTreeSet;
 
 #[test]
 fn tree_detection() {
@@ -35,7 +36,9 @@ fn tree_detection() {
             templates: [
                 TemplateFile(
                     TemplateFile {
-                        kind: "file",
+                        kind: File(
+                            "file",
+                        ),
                         id: "package_lib_cargo_toml",
                         name: "./Cargo.toml",
                         description: "",
@@ -47,6 +50,13 @@ fn tree_detection() {
                         path: Some(
                             "./Cargo.toml",
                         ),
+                        raw: None,
+                        asset: None,
+                        condition: None,
+                        each: None,
+                        patch: None,
+                        merge: None,
+                        hooks: [],
                         location: Location {
                             path: "",
                             span: Span {
@@ -58,7 +68,9 @@ fn tree_detection() {
                 ),
                 TemplateFile(
                     TemplateFile {
-                        kind: "file",
+                        kind: File(
+                            "file",
+                        ),
                         id: "package_lib_src_lib_rs",
                         name: "./src/lib.rs",
                         description: "",
@@ -70,6 +82,13 @@ fn tree_detection() {
                         path: Some(
                             "./src/lib.rs",
                         ),
+                        raw: None,
+                        asset: None,
+                        condition: None,
+                        each: None,
+                        patch: None,
+                        merge: None,
+                        hooks: [],
                         location: Location {
                             path: "",
                             span: Span {
@@ -88,7 +107,9 @@ fn tree_detection() {
                         files: [
                             TemplateFile(
                                 TemplateFile {
-                                    kind: "file",
+                                    kind: File(
+                                        "file",
+                                    ),
                                     id: "package_lib_cargo_toml",
                                     name: "./Cargo.toml",
                                     description: "",
@@ -100,6 +121,13 @@ fn tree_detection() {
                                     path: Some(
                                         "./Cargo.toml",
                                     ),
+                                    raw: None,
+                                    asset: None,
+                                    condition: None,
+                                    each: None,
+                                    patch: None,
+                                    merge: None,
+                                    hooks: [],
                                     location: Location {
                                         path: "",
                                         span: Span {
@@ -111,7 +139,9 @@ fn tree_detection() {
                             ),
                             TemplateFile(
                                 TemplateFile {
-                                    kind: "file",
+                                    kind: File(
+                                        "file",
+                                    ),
                                     id: "package_lib_src_lib_rs",
                                     name: "./src/lib.rs",
                                     description: "",
@@ -123,6 +153,13 @@ fn tree_detection() {
                                     path: Some(
                                         "./src/lib.rs",
                                     ),
+                                    raw: None,
+                                    asset: None,
+                                    condition: None,
+                                    each: None,
+                                    patch: None,
+                                    merge: None,
+                                    hooks: [],
                                     location: Location {
                                         path: "",
                                         span: Span {
@@ -133,6 +170,8 @@ fn tree_detection() {
                                 },
                             ),
                         ],
+                        includes: [],
+                        hooks: [],
                         location: Location {
                             path: "",
                             span: Span {
@@ -143,6 +182,7 @@ fn tree_detection() {
                     },
                 ),
             ],
+            partials: [],
             location: Location {
                 path: "",
                 span: Span {
@@ -172,7 +212,9 @@ fn single() {
     Template(
         TemplateFile(
             TemplateFile {
-                kind: "file",
+                kind: File(
+                    "file",
+                ),
                 id: "hello_world",
                 name: "Hello World",
                 description: "This template prints a greeting.",
@@ -182,6 +224,13 @@ fn single() {
                 ),
                 content: "print(\"Hello, world!\")",
                 path: None,
+                raw: None,
+                asset: None,
+                condition: None,
+                each: None,
+                patch: None,
+                merge: None,
+                hooks: [],
                 location: Location {
                     path: "",
                     span: Span {
@@ -219,7 +268,9 @@ fn single_details() {
     Template(
         TemplateFile(
             TemplateFile {
-                kind: "file",
+                kind: File(
+                    "file",
+                ),
                 id: "personalized_greeting",
                 name: "Personalized Greeting",
                 description: "Generates a personalized greeting.",
@@ -231,6 +282,7 @@ fn single_details() {
                             "any",
                         ),
                         required: true,
+                        derived: None,
                     },
                 ],
                 lang: Some(
@@ -238,6 +290,13 @@ fn single_details() {
                 ),
                 content: "Hello, {{ name }}!",
                 path: None,
+                raw: None,
+                asset: None,
+                condition: None,
+                each: None,
+                patch: None,
+                merge: None,
+                hooks: [],
                 location: Location {
                     path: "",
                     span: Span {
@@ -290,6 +349,517 @@ fn argument_types_and_descriptions() {
     assert!(!second.required);
 }
 
+#[test]
+fn nested_trees() {
+    let input = indoc! {r#"
+        # Monorepo
+
+        ## `./README.md`
+
+        ```md
+        # {{name}}
+        ```
+
+        ## Crate
+
+        ### `./crate/Cargo.toml`
+
+        ```toml
+        name = "{{name}}"
+        ```
+
+        ### `./crate/src/lib.rs`
+
+        ```rust
+        pub fn hello() {}
+        ```
+
+        ## Web
+
+        ### `./web/package.json`
+
+        ```json
+        {}
+        ```
+    "#};
+
+    let parsed = parse_str(Some("monorepo"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let trees: Vec<_> = collection
+        .templates
+        .iter()
+        .filter_map(|template| match template {
+            Template::TemplateTree(tree) => Some(tree),
+            Template::TemplateFile(_) => None,
+        })
+        .collect();
+    assert_eq!(trees.len(), 1, "subtrees are folded into the root tree");
+
+    fn outline(template: &Template, depth: usize, out: &mut Vec<String>) {
+        match template {
+            Template::TemplateFile(file) => out.push(format!("{}{}", "  ".repeat(depth), file.id)),
+            Template::TemplateTree(tree) => {
+                out.push(format!("{}{}/", "  ".repeat(depth), tree.id));
+                for member in &tree.files {
+                    outline(member, depth + 1, out);
+                }
+            }
+        }
+    }
+    let mut lines = Vec::new();
+    outline(&Template::TemplateTree(trees[0].clone()), 0, &mut lines);
+    insta::assert_snapshot!(lines.join("\n"), @r"
+    monorepo/
+      monorepo_readme_md
+      monorepo_crate/
+        monorepo_crate_crate_cargo_toml
+        monorepo_crate_crate_src_lib_rs
+      monorepo_web/
+        monorepo_web_web_package_json
+    ");
+}
+
+#[test]
+fn tree_includes() {
+    let input = indoc! {r#"
+        # Crate
+
+        Library crate.
+
+        include: `rust_package_gitignore` at ./
+        include: license at ./legal with holder=author, year=current_year
+
+        ## `./Cargo.toml`
+
+        ```toml
+        name = "{{name}}"
+        ```
+
+        ## `./src/lib.rs`
+
+        ```rust
+        ```
+    "#};
+
+    let parsed = parse_str(Some("crate"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateTree(tree)) = collection.templates.last() else {
+        panic!("expected a tree");
+    };
+    assert_eq!(tree.description, "Library crate.");
+    let includes: Vec<String> = tree
+        .includes
+        .iter()
+        .map(|include| format!("{} {:?} {:?}", include.id, include.path, include.args))
+        .collect();
+    insta::assert_snapshot!(includes.join("\n"), @r#"
+    rust_package_gitignore Some("./") {}
+    license Some("./legal") {"holder": "author", "year": "current_year"}
+    "#);
+
+    let err = parse_str(Some("crate"), &input.replace(" at ./legal", " at")).expect_err("invalid");
+    assert!(
+        err.to_string()
+            .starts_with("Invalid include 'license at with holder=author, year=current_year'"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn conditional_files() {
+    let input = indoc! {r#"
+        # App
+
+        ## `./src/lib.rs`
+
+        ```rust
+        pub fn run() {}
+        ```
+
+        ## `./src/bin/main.rs`
+
+        Binary entry point.
+
+        when: `binary`
+
+        ```rust
+        fn main() { println!("{{bin_name}}"); }
+        ```
+    "#};
+
+    let parsed = parse_str(Some("app"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateFile(main)) = collection.templates.get(1) else {
+        panic!("expected the binary file");
+    };
+    assert_eq!(main.condition.as_deref(), Some("binary"));
+    assert_eq!(main.description, "Binary entry point.");
+    let args: Vec<String> = main
+        .args
+        .iter()
+        .map(|arg| format!("{} {:?} required={}", arg.name, arg.kind, arg.required))
+        .collect();
+    insta::assert_snapshot!(args.join("\n"), @r#"
+    bin_name Any("any") required=true
+    binary Boolean("boolean") required=false
+    "#);
+}
+
+#[test]
+fn repeated_files() {
+    let input = indoc! {r#"
+        # Crate
+
+        ## `./src/lib.rs`
+
+        ```rust
+        pub mod api;
+        ```
+
+        ## `./src/{{module.name}}.rs`
+
+        each: modules as module
+
+        ```rust
+        //! {{module.doc}} for {{crate_name}}
+        ```
+    "#};
+
+    let parsed = parse_str(Some("crate"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateFile(module)) = collection.templates.get(1) else {
+        panic!("expected the module file");
+    };
+    let each = module.each.as_ref().expect("each directive");
+    assert_eq!(
+        (each.arg.as_str(), each.binding.as_str()),
+        ("modules", "module")
+    );
+    assert_eq!(module.description, "");
+    let args: Vec<String> = module
+        .args
+        .iter()
+        .map(|arg| format!("{} {:?} required={}", arg.name, arg.kind, arg.required))
+        .collect();
+    insta::assert_snapshot!(args.join("\n"), @r#"
+    crate_name Any("any") required=true
+    modules Array("array") required=true
+    "#);
+
+    let err = parse_str(Some("crate"), &input.replace(" as module", " as modules"))
+        .expect_err("binding equal to the array");
+    assert!(
+        err.to_string()
+            .starts_with("Invalid each 'modules as modules'"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn verbatim_and_asset_files() {
+    let input = indoc! {r#"
+        # Site
+
+        ## `./.github/workflows/ci.yml`
+
+        ```yaml raw
+        run: echo ${{ github.sha }} for {{name}}
+        ```
+
+        ## `./public/favicon.png`
+
+        The site icon.
+
+        ![favicon](./assets/favicon.png)
+
+        ## `./README.md`
+
+        ```md
+        # {{name}}
+        ```
+    "#};
+
+    let parsed = parse_str(Some("site"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateTree(tree)) = collection.templates.last() else {
+        panic!("expected the site tree");
+    };
+    let files: Vec<String> = tree
+        .files
+        .iter()
+        .map(|template| {
+            let Template::TemplateFile(file) = template else {
+                panic!("expected files only");
+            };
+            let args: Vec<&str> = file.args.iter().map(|arg| arg.name.as_str()).collect();
+            format!(
+                "{} raw={:?} asset={:?} args=[{}] description={:?}",
+                file.id,
+                file.raw,
+                file.asset,
+                args.join(", "),
+                file.description
+            )
+        })
+        .collect();
+    insta::assert_snapshot!(files.join("\n"), @r#"
+    site_github_workflows_ci_yml raw=Some(true) asset=None args=[] description=""
+    site_public_favicon_png raw=None asset=Some("./assets/favicon.png") args=[] description="The site icon."
+    site_readme_md raw=None asset=None args=[name] description=""
+    "#);
+}
+
+#[test]
+fn asset_markers() {
+    let input = indoc! {r#"
+        # Docs
+
+        ## `./README.md`
+
+        See [docs](./README.md) for details.
+
+        ## `./LINKED.md`
+
+        [readme](./README.md)
+
+        ## `./report.pdf`
+
+        asset: [report](./files/report.pdf)
+
+        ## `./logo.svg`
+
+        asset: `./files/logo.svg`
+    "#};
+
+    let parsed = parse_str(Some("docs"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateTree(tree)) = collection.templates.last() else {
+        panic!("expected the docs tree");
+    };
+    let assets: Vec<(&str, Option<&str>)> = tree
+        .files
+        .iter()
+        .map(|template| {
+            let Template::TemplateFile(file) = template else {
+                panic!("expected files only");
+            };
+            (file.id.as_str(), file.asset.as_deref())
+        })
+        .collect();
+    assert_eq!(
+        assets,
+        vec![
+            ("docs_report_pdf", Some("./files/report.pdf")),
+            ("docs_logo_svg", Some("./files/logo.svg")),
+        ]
+    );
+}
+
+#[test]
+fn assets_outside_the_markdown_directory_error() {
+    let input = indoc! {r#"
+        # Leak
+
+        ![secret](../../etc/passwd)
+    "#};
+
+    let err = parse_str(Some("leak"), input).expect_err("escaping asset");
+    assert!(
+        err.to_string().starts_with("Asset '../../etc/passwd' at")
+            && err
+                .to_string()
+                .ends_with("is outside the directory of its markdown file."),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn patch_files() {
+    let input = indoc! {r#"
+        # Module
+
+        ## `./src/lib.rs`
+
+        Declares the module.
+
+        patch: after `^pub mod`
+
+        ```rust
+        pub mod {{name}};
+        ```
+
+        ## `./Cargo.toml`
+
+        patch: between `# nmcr:deps:start` and `# nmcr:deps:end`
+
+        ```toml
+        {{name}} = { path = "{{name}}" }
+        ```
+    "#};
+
+    let parsed = parse_str(Some("module"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateFile(lib)) = collection.templates.first() else {
+        panic!("expected the lib patch");
+    };
+    assert_eq!(lib.description, "Declares the module.");
+    insta::assert_debug_snapshot!((&lib.kind, &lib.patch), @r#"
+    (
+        Patch(
+            "patch",
+        ),
+        Some(
+            TemplatePatch {
+                op: After(
+                    "after",
+                ),
+                anchor: Some(
+                    "^pub mod",
+                ),
+                start: None,
+                end: None,
+            },
+        ),
+    )
+    "#);
+    let Some(Template::TemplateFile(manifest)) = collection.templates.get(1) else {
+        panic!("expected the manifest patch");
+    };
+    let patch = manifest.patch.as_ref().expect("patch");
+    assert_eq!(
+        (patch.start.as_deref(), patch.end.as_deref()),
+        (Some("# nmcr:deps:start"), Some("# nmcr:deps:end"))
+    );
+
+    let err = parse_str(
+        Some("module"),
+        &input.replace("after `^pub mod`", "after `(`"),
+    )
+    .expect_err("invalid anchor");
+    assert!(
+        err.to_string().starts_with("Invalid patch anchor '('"),
+        "unexpected error: {err}"
+    );
+    let err = parse_str(
+        Some("module"),
+        &input.replace("patch: after", "patch: around"),
+    )
+    .expect_err("unknown op");
+    assert!(
+        err.to_string()
+            .starts_with("Invalid patch 'around ^pub mod'"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn merge_files() {
+    let input = indoc! {r#"
+        # Package `./package.json`
+
+        Adds the test script.
+
+        merge: replace
+
+        ```json
+        { "scripts": { "test": "vitest" } }
+        ```
+    "#};
+
+    let parsed = parse_str(Some("package"), input).expect("parse markdown");
+    let ParsedMarkdown::Template(Template::TemplateFile(file)) = parsed else {
+        panic!("expected a single file, got {parsed:?}");
+    };
+    assert_eq!(file.description, "Adds the test script.");
+    insta::assert_debug_snapshot!(file.merge, @r#"
+    Some(
+        TemplateMerge {
+            arrays: Replace(
+                "replace",
+            ),
+        },
+    )
+    "#);
+
+    let err = parse_str(
+        Some("package"),
+        &input
+            .replace("package.json", "index.js")
+            .replace("json", "js"),
+    )
+    .expect_err("not a document");
+    assert!(
+        err.to_string()
+            .contains("merges but is not a JSON, TOML or YAML file"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn hooks() {
+    let input = indoc! {r#"
+        # Crate
+
+        Library crate.
+
+        ```sh hook
+        git init
+        cargo fmt
+        ```
+
+        ## `./Cargo.toml`
+
+        Manifest.
+
+        run: rm -rf ~
+
+        ```toml
+        name = "{{name}}"
+        ```
+
+        ```sh hook
+        cargo add {{dependency}}
+
+        cargo check
+        ```
+
+        ## `./src/lib.rs`
+
+        ```rust
+        ```
+    "#};
+
+    let parsed = parse_str(Some("crate"), input).expect("parse markdown");
+    let ParsedMarkdown::Collection(collection) = parsed else {
+        panic!("expected a collection, got {parsed:?}");
+    };
+    let Some(Template::TemplateTree(tree)) = collection.templates.last() else {
+        panic!("expected a tree");
+    };
+    assert_eq!(tree.description, "Library crate.");
+    assert_eq!(tree.hooks, vec!["git init", "cargo fmt"]);
+    let Some(Template::TemplateFile(file)) = tree.files.first() else {
+        panic!("expected a file");
+    };
+    assert_eq!(file.description, "Manifest.\n\nrun: rm -rf ~");
+    assert_eq!(file.content, "name = \"{{name}}\"");
+    assert_eq!(file.hooks, vec!["cargo add {{dependency}}", "cargo check"]);
+}
+
 #[test]
 fn collection() {
     let input = indoc! {r#"
@@ -328,7 +898,9 @@ fn collection() {
     Template(
         TemplateFile(
             TemplateFile {
-                kind: "file",
+                kind: File(
+                    "file",
+                ),
                 id: "greeting_templates_friendly_greeting",
                 name: "Friendly Greeting",
                 description: "",
@@ -340,6 +912,7 @@ fn collection() {
                             "any",
                         ),
                         required: true,
+                        derived: None,
                     },
                 ],
                 lang: Some(
@@ -347,6 +920,13 @@ fn collection() {
                 ),
                 content: "Hey, {{ name }}!",
                 path: None,
+                raw: None,
+                asset: None,
+                condition: None,
+                each: None,
+                patch: None,
+                merge: None,
+                hooks: [],
                 location: Location {
                     path: "",
                     span: Span {
@@ -359,3 +939,268 @@ fn collection() {
     )
     "#);
 }
+
+#[test]
+fn derived_arguments() {
+    let input = indoc! {r#"
+        # Crate Module
+
+        ## Args
+
+        - `pkg_name` [string]: Package name.
+        - `crate_ident` = {{pkg_name}}_core: Identifier used in `use` statements.
+
+        ## Template
+
+        ```rust
+        use {{crate_ident}}::prelude::*;
+        ```
+    "#};
+
+    let parsed = parse_str(Some("crate_module"), input).expect("parse markdown");
+
+    let file = match parsed {
+        ParsedMarkdown::Template(Template::TemplateFile(file)) => file,
+        other => panic!("unexpected parser result: {other:?}"),
+    };
+
+    let names: Vec<_> = file.args.iter().map(|arg| arg.name.as_str()).collect();
+    assert_eq!(names, vec!["pkg_name", "crate_ident"]);
+
+    let derived = &file.args[1];
+    assert_eq!(derived.derived.as_deref(), Some("{{pkg_name}}_core"));
+    assert_eq!(derived.description, "Identifier used in use statements.");
+    assert!(file.args[0].derived.is_none());
+}
+
+#[test]
+fn script_helpers_called_without_params_are_not_arguments() {
+    let input = indoc! {r#"
+        # Changelog
+
+        ```markdown
+        ## {{version}} ({{timestamp}})
+        ```
+    "#};
+
+    let names = |parsed: ParsedMarkdown| -> Vec<String> {
+        match parsed {
+            ParsedMarkdown::Template(Template::TemplateFile(file)) => {
+                file.args.into_iter().map(|arg| arg.name).collect()
+            }
+            other => panic!("unexpected parser result: {other:?}"),
+        }
+    };
+
+    let helpers = BTreeSet::from(["timestamp".to_string()]);
+    let parsed =
+        parse_str_with_helpers(Some("changelog"), input, &helpers).expect("parse markdown");
+    assert_eq!(names(parsed), vec!["version"]);
+
+    let parsed = parse_str(Some("changelog"), input).expect("parse markdown");
+    assert_eq!(names(parsed), vec!["timestamp", "version"]);
+}
+
+#[test]
+fn derived_argument_cycles_error() {
+    let input = indoc! {r#"
+        # Cycle
+
+        ## Args
+
+        - `left` = {{right}}
+        - `right` = {{left}}
+
+        ## Template
+
+        ```text
+        {{left}}
+        ```
+    "#};
+
+    let err = parse_str(Some("cycle"), input).expect_err("cycle should fail");
+    assert!(
+        err.to_string()
+            .contains("Derived arguments form a cycle: left -> right -> left"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn reserved_context_argument_errors() {
+    let input = indoc! {r#"
+        # Readme
+
+        ## Args
+
+        - `_ctx` Render context
+
+        ## Template
+
+        ```md
+        {{_ctx.date}}
+        ```
+    "#};
+
+    let err = parse_str(Some("readme"), input).expect_err("reserved arg should fail");
+    assert_eq!(
+        err.to_string(),
+        "Template 'readme' at <memory>:0-80 declares the argument '_ctx', which is reserved for nmcr."
+    );
+}
+
+#[test]
+fn partials_section() {
+    let input = indoc! {r#"
+        # Readme
+
+        ```md
+        # {{name}}
+
+        {{> license}}
+        ```
+
+        ## Partials
+
+        ### `license`
+
+        Shared license footer.
+
+        ```md
+        Licensed under {{license}}.
+        ```
+    "#};
+
+    let parsed = parse_str(Some("readme"), input).expect("parse markdown");
+
+    let collection = match parsed {
+        ParsedMarkdown::Collection(collection) => collection,
+        other => panic!("unexpected parser result: {other:?}"),
+    };
+
+    assert_eq!(collection.templates.len(), 1);
+    assert_eq!(collection.partials.len(), 1);
+    let partial = &collection.partials[0];
+    assert_eq!(partial.name, "license");
+    assert_eq!(partial.description, "Shared license footer.");
+    assert_eq!(partial.content, "Licensed under {{license}}.");
+}
+
+#[test]
+fn partial_files() {
+    let input = indoc! {r#"
+        # License
+
+        ```md
+        Licensed under {{license}}.
+        ```
+    "#};
+
+    let parsed = parse_str(Some("license.partial"), input).expect("parse markdown");
+
+    let partials = match parsed {
+        ParsedMarkdown::Partials(partials) => partials,
+        other => panic!("unexpected parser result: {other:?}"),
+    };
+
+    let names: Vec<_> = partials.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, vec!["license"]);
+}
+
+#[test]
+fn infers_argument_kinds_from_usage() {
+    let input = indoc! {r#"
+        # Package
+
+        ## Args
+
+        - `description?`: Short package description.
+        - `keywords`: Search keywords.
+
+        ## Template
+
+        ```json
+        {
+          "name": "{{name}}",
+          {{#if description}}"description": "{{description}}",{{/if}}
+          {{#if private}}"private": true,{{/if}}
+          "author": "{{author.name}} <{{author.email}}>",
+          "keywords": [{{#each keywords}}"{{this}}"{{#unless @last}}, {{/unless}}{{/each}}]
+        }
+        ```
+    "#};
+
+    let parsed = parse_str(Some("package"), input).expect("parse markdown");
+
+    let file = match parsed {
+        ParsedMarkdown::Template(Template::TemplateFile(file)) => file,
+        other => panic!("unexpected parser result: {other:?}"),
+    };
+
+    let args: Vec<_> = file
+        .args
+        .iter()
+        .map(|arg| (arg.name.as_str(), arg.kind.clone(), arg.required))
+        .collect();
+    assert_eq!(
+        args,
+        vec![
+            ("description", ArgKind::Any(ArgKindAny), false),
+            ("keywords", ArgKind::Array(ArgKindArray), true),
+            ("author", ArgKind::Object(ArgKindObject), true),
+            ("name", ArgKind::Any(ArgKindAny), true),
+            ("private", ArgKind::Boolean(ArgKindBoolean), false),
+        ]
+    );
+}
+
+#[test]
+fn argument_kind_conflicts_error() {
+    let input = indoc! {r#"
+        # List
+
+        ## Args
+
+        - `items` [string]: Items to render.
+
+        ## Template
+
+        ```text
+        {{#each items}}- {{this}}
+        {{/each}}
+        ```
+    "#};
+
+    let err = parse_str(Some("list"), input).expect_err("conflict should fail");
+    assert!(
+        err.to_string().contains(
+            "Argument 'items' is documented as string but iterated with #each in content"
+        ),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn optional_arguments_require_guards() {
+    let input = indoc! {r#"
+        # Greeting
+
+        ## Args
+
+        - `suffix?`: Appended to the greeting.
+
+        ## Template
+
+        ```text
+        Hello{{suffix}}
+        ```
+    "#};
+
+    let err = parse_str(Some("greeting"), input).expect_err("unguarded optional should fail");
+    assert!(
+        err.to_string().contains(
+            "Argument 'suffix' is documented as optional but used in content without an #if guard"
+        ),
+        "unexpected error: {err}"
+    );
+}
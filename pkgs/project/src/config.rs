@@ -1,10 +1,21 @@
 use crate::prelude::*;
+use nmcr_types_internal::PlainJson;
+use std::collections::BTreeMap;
+use std::env;
 use std::fs;
 
 pub const CONFIG_FILENAME: &str = "ncmr.toml";
 
 pub const DEFAULT_TEMPLATES_GLOB: &str = "./tmpls/**/*.md";
 
+pub const DEFAULT_SOURCE_GLOB: &str = "**/*.md";
+
+/// Environment variable overriding the user-global config directory.
+pub const HOME_ENV: &str = "NMCR_HOME";
+
+/// Environment variable selecting the config profile.
+pub const PROFILE_ENV: &str = "NMCR_PROFILE";
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Config {
     /// Config path.
@@ -38,7 +49,9 @@ impl Config {
         Ok(Self::new(path, Default::default()))
     }
 
-    pub fn find<P: AsRef<Path>>(path: Option<P>) -> Result<Config> {
+    /// Find and read the project config, applying `profile` or the one set in the
+    /// environment or the config itself.
+    pub fn find<P: AsRef<Path>>(path: Option<P>, profile: Option<&str>) -> Result<Config> {
         match path {
             // If there's a path, locate the config file there
             Some(path) => {
@@ -48,32 +61,54 @@ impl Config {
                 } else {
                     path.as_ref().to_path_buf()
                 };
-                Self::read(path.clone())
+                Self::read(path.clone(), profile)
             }
 
             // Locate the config file in the current or parent directories
-            None => {
-                let initial: PathBuf = ".".into();
-                let mut cur_path: Option<&Path> = Some(&initial);
-                while let Some(path) = cur_path {
-                    let file = Self::join_path(path);
-                    if file.is_file() {
-                        return Self::read(file);
-                    }
-                    cur_path = path.parent();
-                }
-
-                Err(anyhow!(
+            None => match Self::locate() {
+                Some(file) => Self::read(file, profile),
+                None => Err(anyhow!(
                     "No config file found in current or parent directories"
-                ))
-            }
+                )),
+            },
         }
     }
 
-    fn read(path: PathBuf) -> Result<Config> {
+    /// Path of the config file in the current directory or the nearest parent that has one.
+    pub fn locate() -> Option<PathBuf> {
+        let current = env::current_dir().ok()?;
+        current
+            .ancestors()
+            .map(Self::join_path)
+            .find(|file| file.is_file())
+    }
+
+    fn read(path: PathBuf, profile: Option<&str>) -> Result<Config> {
+        Self::read_with_env(path, profile, None)
+    }
+
+    /// Read the config with `env` in place of the process environment when given. The
+    /// profile comes from `profile`, then `NMCR_PROFILE`, then the config's `profile` key.
+    fn read_with_env(
+        path: PathBuf,
+        profile: Option<&str>,
+        env: Option<config::Map<String, String>>,
+    ) -> Result<Config> {
+        let mut config = Self::read_file(path, true, profile, env)?;
+        config.user.apply_profile()?;
+        Ok(config)
+    }
+
+    fn read_file(
+        path: PathBuf,
+        required: bool,
+        profile: Option<&str>,
+        env: Option<config::Map<String, String>>,
+    ) -> Result<Config> {
         let settings = config::Config::builder()
-            .add_source(config::File::from(path.clone()))
-            .add_source(config::Environment::with_prefix("NMCR"))
+            .add_source(config::File::from(path.clone()).required(required))
+            .add_source(config::Environment::with_prefix("NMCR").source(env))
+            .set_override_option("profile", profile)?
             .build()?;
 
         let user = settings.try_deserialize::<ConfigUser>()?;
@@ -81,6 +116,43 @@ impl Config {
         Ok(Self::new(path, user))
     }
 
+    /// Directory of the user-global config and template library: `NMCR_HOME` when set,
+    /// otherwise `nmcr` in the XDG config directory.
+    pub fn global_dir() -> Option<PathBuf> {
+        let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+        if let Some(home) = var(HOME_ENV) {
+            return Some(home.into());
+        }
+        let config_dir = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| var("APPDATA").map(PathBuf::from))?;
+        Some(config_dir.join("nmcr"))
+    }
+
+    /// Read the user-global config in `dir`, where the config file itself is optional.
+    /// The selected profile applies only when the config defines it. Returns `None` when
+    /// the directory doesn't exist.
+    pub fn read_global(dir: &Path, profile: Option<&str>) -> Result<Option<Config>> {
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        Self::read_lenient(Self::join_path(dir), false, profile).map(Some)
+    }
+
+    /// Read a config that applies the selected profile only when it defines it.
+    pub(crate) fn read_lenient(
+        path: PathBuf,
+        required: bool,
+        profile: Option<&str>,
+    ) -> Result<Config> {
+        let mut config = Self::read_file(path, required, profile, None)?;
+        if config.user.has_profile() {
+            config.user.apply_profile()?;
+        }
+        Ok(config)
+    }
+
     pub fn write(&self) -> Result<()> {
         let parent = self
             .path
@@ -107,6 +179,14 @@ impl Config {
         DEFAULT_TEMPLATES_GLOB.to_string()
     }
 
+    pub fn default_templates() -> ConfigGlobs {
+        ConfigGlobs::from(Self::default_templates_glob())
+    }
+
+    pub fn default_source_glob() -> String {
+        DEFAULT_SOURCE_GLOB.to_string()
+    }
+
     pub fn default_path() -> PathBuf {
         PathBuf::from(CONFIG_FILENAME)
     }
@@ -114,28 +194,423 @@ impl Config {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConfigUser {
-    /// Glob pattern to find template files
-    #[serde(default = "Config::default_templates_glob")]
-    pub templates: String,
+    /// Glob patterns to find template files
+    #[serde(default = "Config::default_templates")]
+    pub templates: ConfigGlobs,
+    /// Glob patterns of files to skip; a pattern without `/` matches file names anywhere
+    #[serde(default, skip_serializing_if = "ConfigGlobs::is_empty")]
+    pub exclude: ConfigGlobs,
+    /// Named groups of templates with their own output directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub roots: BTreeMap<String, ConfigRoot>,
+    /// Rhai scripts registered as custom helpers, named after the file stem
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub helpers: Vec<String>,
+    /// Additional template sources fetched from git repositories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<ConfigSource>,
+    /// Post-generation hooks
+    #[serde(default, skip_serializing_if = "ConfigHooks::is_default")]
+    pub hooks: ConfigHooks,
+    /// Formatting of generated files
+    #[serde(default, skip_serializing_if = "ConfigFormat::is_default")]
+    pub format: ConfigFormat,
+    /// Arg values used when an invocation doesn't set them; strings may reference
+    /// `${env:NAME}` and `${git:key}`
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_defaults"
+    )]
+    pub defaults: BTreeMap<String, serde_json::Value>,
+    /// Default output directory of `nmcr gen`, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<String>,
+    /// MCP server settings
+    #[serde(default, skip_serializing_if = "ConfigMcp::is_default")]
+    pub mcp: ConfigMcp,
+    /// Profile applied when neither `--profile` nor `NMCR_PROFILE` selects one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Named sets of overrides, such as for internal and open-source generation
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ConfigProfile>,
+    /// Member projects of a monorepo rooted at this config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<ConfigWorkspace>,
+}
+
+impl ConfigUser {
+    /// Whether the selected profile, if any, is defined.
+    pub fn has_profile(&self) -> bool {
+        self.profile
+            .as_ref()
+            .is_none_or(|name| self.profiles.contains_key(name))
+    }
+
+    /// Error when a profile is selected but not defined.
+    pub fn ensure_profile(&self) -> Result<()> {
+        if let Some(name) = self.profile.as_ref().filter(|_| !self.has_profile()) {
+            let defined: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            bail!(
+                "Unknown profile '{name}'; defined profiles: {}",
+                if defined.is_empty() {
+                    "none".to_string()
+                } else {
+                    defined.join(", ")
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Overlay the selected profile: its defaults are added to the top-level ones, while
+    /// the sources, output directory and MCP settings it sets replace them.
+    pub fn apply_profile(&mut self) -> Result<()> {
+        self.ensure_profile()?;
+        let Some(profile) = self
+            .profile
+            .as_ref()
+            .and_then(|name| self.profiles.get(name))
+        else {
+            return Ok(());
+        };
+        let profile = profile.clone();
+        self.defaults.extend(profile.defaults);
+        if let Some(sources) = profile.sources {
+            self.sources = sources;
+        }
+        if let Some(out) = profile.out {
+            self.out = Some(out);
+        }
+        if let Some(mcp) = profile.mcp {
+            self.mcp = mcp;
+        }
+        Ok(())
+    }
 }
 
 impl Default for ConfigUser {
     fn default() -> Self {
         Self {
-            templates: Config::default_templates_glob(),
+            templates: Config::default_templates(),
+            exclude: ConfigGlobs::default(),
+            roots: BTreeMap::new(),
+            helpers: Vec::new(),
+            sources: Vec::new(),
+            hooks: ConfigHooks::default(),
+            format: ConfigFormat::default(),
+            defaults: BTreeMap::new(),
+            out: None,
+            mcp: ConfigMcp::default(),
+            profile: None,
+            profiles: BTreeMap::new(),
+            workspace: None,
         }
     }
 }
 
-impl ConfigUser {
-    pub fn normalized_templates(&self) -> String {
-        let mut pattern = self.templates.trim().to_string();
-        if let Some(stripped) = pattern
-            .strip_prefix("./")
-            .or_else(|| pattern.strip_prefix(".\\"))
-        {
-            pattern = stripped.to_string();
-        }
-        pattern
+/// Glob patterns, written either as a single string or as a list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigGlobs(pub Vec<String>);
+
+impl ConfigGlobs {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.0.iter()
+    }
+}
+
+impl From<String> for ConfigGlobs {
+    fn from(pattern: String) -> Self {
+        Self(vec![pattern])
+    }
+}
+
+impl Serialize for ConfigGlobs {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [pattern] => serializer.serialize_str(pattern),
+            patterns => patterns.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigGlobs {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Globs {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        Ok(match Globs::deserialize(deserializer)? {
+            Globs::One(pattern) => Self(vec![pattern]),
+            Globs::Many(patterns) => Self(patterns),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConfigRoot {
+    /// Glob patterns to find the group's template files
+    pub templates: ConfigGlobs,
+    /// Glob patterns of files to skip in addition to the top-level ones
+    #[serde(default, skip_serializing_if = "ConfigGlobs::is_empty")]
+    pub exclude: ConfigGlobs,
+    /// Default output directory of `nmcr gen`, relative to the project root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConfigSource {
+    /// Git repository URL
+    pub git: String,
+    /// Branch, tag or commit to check out; the default branch when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Glob pattern to find template files within the repository
+    #[serde(default = "Config::default_source_glob")]
+    pub path: String,
+    /// Prefix for the ids of the source's templates, as in `company::rust_crate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ConfigHooks {
+    /// Shell commands run in the output directory after `nmcr gen` writes files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub run: Vec<String>,
+    /// Let MCP tool results include the hooks of templates from outside the project; the
+    /// server never runs hooks itself
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mcp: bool,
+}
+
+impl ConfigHooks {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ConfigMcp {
+    /// Patterns of template ids served as tools, where `*` matches any run of characters,
+    /// such as `company::*`; every template when empty
+    #[serde(default, skip_serializing_if = "ConfigGlobs::is_empty")]
+    pub expose: ConfigGlobs,
+}
+
+impl ConfigMcp {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether the template with the given id is served as a tool.
+    pub fn exposes(&self, id: &str) -> bool {
+        self.expose.is_empty() || self.expose.iter().any(|pattern| matches_id(pattern, id))
+    }
+}
+
+/// Match an id against a pattern where `*` stands for any run of characters.
+fn matches_id(pattern: &str, id: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = id.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ConfigWorkspace {
+    /// Glob patterns of member directories, each with its own config, relative to the
+    /// workspace root
+    pub members: ConfigGlobs,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ConfigProfile {
+    /// Arg defaults added to the top-level ones
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_defaults"
+    )]
+    pub defaults: BTreeMap<String, serde_json::Value>,
+    /// Template sources replacing the top-level ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sources: Option<Vec<ConfigSource>>,
+    /// Output directory replacing the top-level one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<String>,
+    /// MCP settings replacing the top-level ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp: Option<ConfigMcp>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConfigFormat {
+    /// Strip whitespace at the end of lines
+    #[serde(default = "default_true")]
+    pub trim_trailing_whitespace: bool,
+    /// Collapse runs of blank lines into one
+    #[serde(default = "default_true")]
+    pub collapse_blank_lines: bool,
+    /// End files with a single newline
+    #[serde(default = "default_true")]
+    pub final_newline: bool,
+    /// Shell commands that format content piped to stdin, keyed by code block language
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formatters: BTreeMap<String, String>,
+}
+
+impl Default for ConfigFormat {
+    fn default() -> Self {
+        Self {
+            trim_trailing_whitespace: true,
+            collapse_blank_lines: true,
+            final_newline: true,
+            formatters: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigFormat {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl ConfigGlobs {
+    /// Patterns without the leading `./`.
+    pub fn normalized(&self) -> Vec<String> {
+        self.iter().map(|pattern| normalize_glob(pattern)).collect()
+    }
+
+    /// Patterns without the leading `./`, where one without `/` matches file names in any
+    /// directory.
+    pub fn normalized_excludes(&self) -> Vec<String> {
+        self.normalized()
+            .into_iter()
+            .map(|pattern| {
+                if pattern.contains('/') {
+                    pattern
+                } else {
+                    format!("**/{pattern}")
+                }
+            })
+            .collect()
+    }
+}
+
+impl ConfigSource {
+    pub fn normalized_path(&self) -> String {
+        normalize_glob(&self.path)
+    }
+}
+
+/// Strip the leading `./` that wax globs do not accept.
+fn normalize_glob(pattern: &str) -> String {
+    let pattern = pattern.trim();
+    pattern
+        .strip_prefix("./")
+        .or_else(|| pattern.strip_prefix(".\\"))
+        .unwrap_or(pattern)
+        .to_string()
+}
+
+/// Write arg defaults with plain TOML numbers.
+fn serialize_defaults<S: serde::Serializer>(
+    defaults: &BTreeMap<String, serde_json::Value>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_map(
+        defaults
+            .iter()
+            .map(|(name, value)| (name, PlainJson(value))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_numeric_defaults_as_toml_numbers() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = Config::join_path(dir.path());
+        fs::write(&path, "[defaults]\njobs = 4\nratio = 0.5\n").unwrap();
+        let config = Config::read_with_env(path.clone(), None, Some(Default::default())).unwrap();
+        config.write().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "templates = \"./tmpls/**/*.md\"\n\n[defaults]\njobs = 4\nratio = 0.5\n"
+        );
+    }
+
+    #[test]
+    fn profile_flag_beats_env_beats_config_key() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = Config::join_path(dir.path());
+        fs::write(
+            &path,
+            r#"
+profile = "internal"
+
+[profiles.internal]
+out = "./internal"
+
+[profiles.oss]
+out = "./oss"
+
+[profiles.ci]
+out = "./ci"
+"#,
+        )
+        .unwrap();
+        let out = |profile: Option<&str>, env: &[(&str, &str)]| {
+            let env = env
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let config = Config::read_with_env(path.clone(), profile, Some(env)).unwrap();
+            config.user.out.unwrap()
+        };
+
+        assert_eq!(out(None, &[]), "./internal");
+        assert_eq!(out(None, &[(PROFILE_ENV, "oss")]), "./oss");
+        assert_eq!(out(Some("ci"), &[(PROFILE_ENV, "oss")]), "./ci");
+
+        let err = Config::read_with_env(
+            path.clone(),
+            None,
+            Some([(PROFILE_ENV.into(), "staging".into())].into()),
+        )
+        .expect_err("unknown profile");
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'staging'; defined profiles: ci, internal, oss"
+        );
     }
 }
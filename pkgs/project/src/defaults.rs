@@ -0,0 +1,131 @@
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::prelude::*;
+
+impl Project {
+    /// Default arg values of the user-global config overlaid with those of the project, with
+    /// `${env:NAME}` and `${git:key}` references resolved. A default referencing an unset
+    /// variable or git key is left out, so the arg has to be passed as usual.
+    pub fn defaults(&self) -> Result<JsonMap<String, JsonValue>> {
+        let root = self.path();
+        let mut defaults = JsonMap::new();
+        let global = self.global.iter().filter(|_| !self.is_global());
+        for config in global.chain(std::iter::once(&self.config)) {
+            for (name, value) in &config.user.defaults {
+                let resolved = resolve_value(value, &root)
+                    .with_context(|| format!("Invalid default for '{name}'"))?;
+                match resolved {
+                    Some(value) => defaults.insert(name.clone(), value),
+                    None => defaults.remove(name),
+                };
+            }
+        }
+        Ok(defaults)
+    }
+}
+
+fn resolve_value(value: &JsonValue, root: &Path) -> Result<Option<JsonValue>> {
+    Ok(match value {
+        JsonValue::String(text) => interpolate(text, root)?.map(JsonValue::String),
+        JsonValue::Array(items) => items
+            .iter()
+            .map(|item| resolve_value(item, root))
+            .collect::<Result<Option<Vec<_>>>>()?
+            .map(JsonValue::Array),
+        JsonValue::Object(entries) => entries
+            .iter()
+            .map(|(key, item)| Ok(resolve_value(item, root)?.map(|item| (key.clone(), item))))
+            .collect::<Result<Option<JsonMap<_, _>>>>()?
+            .map(JsonValue::Object),
+        other => Some(other.clone()),
+    })
+}
+
+/// Replace every `${env:NAME}` and `${git:key}` in the text, or `None` when one of them
+/// isn't set.
+fn interpolate(text: &str, root: &Path) -> Result<Option<String>> {
+    let mut resolved = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        resolved.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .with_context(|| format!("Unclosed reference in '{text}'"))?;
+        let value = match reference[..end].split_once(':') {
+            Some(("env", name)) => std::env::var(name.trim()).ok(),
+            Some(("git", key)) => git(root, &["config", "--get", key.trim()]).ok(),
+            _ => bail!(
+                "Unknown reference '${{{}}}' in '{text}': expected `${{env:NAME}}` or `${{git:key}}`",
+                &reference[..end]
+            ),
+        };
+        let Some(value) = value else {
+            return Ok(None);
+        };
+        resolved.push_str(&value);
+        rest = &reference[end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(Some(resolved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn resolves_env_and_git_references() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        git(dir.path(), &["init", "--quiet"]).unwrap();
+        git(dir.path(), &["config", "user.name", "Sasha"]).unwrap();
+
+        let mut global = ConfigUser::default();
+        global
+            .defaults
+            .insert("license".into(), json!("Apache-2.0"));
+        global.defaults.insert("year".into(), json!(2025));
+        let mut user = ConfigUser::default();
+        user.defaults.insert("license".into(), json!("MIT"));
+        user.defaults.insert(
+            "author".into(),
+            json!("${git:user.name} <${env:CARGO_PKG_NAME}>"),
+        );
+        user.defaults
+            .insert("keywords".into(), json!(["cli", "${git:nmcr.missing}"]));
+        let project =
+            Project::from_config(Config::new(Config::join_path(dir.path()), user)).with_global(
+                Some(Config::new(PathBuf::from("/global/ncmr.toml"), global)),
+            );
+
+        let defaults = project.defaults().unwrap();
+        assert_eq!(
+            JsonValue::Object(defaults),
+            json!({"author": "Sasha <nmcr_project>", "license": "MIT", "year": 2025})
+        );
+
+        assert_eq!(
+            interpolate("${unknown}", dir.path())
+                .expect_err("unknown reference")
+                .to_string(),
+            "Unknown reference '${unknown}' in '${unknown}': expected `${env:NAME}` or `${git:key}`"
+        );
+        assert!(interpolate("${env:HOME", dir.path()).is_err());
+    }
+
+    #[test]
+    fn invalid_defaults_name_the_arg() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut user = ConfigUser::default();
+        user.defaults
+            .insert("authors".into(), json!([{"name": "${home:user}"}]));
+        let project = Project::from_config(Config::new(Config::join_path(dir.path()), user));
+        let err = project.defaults().expect_err("unknown reference");
+        assert_eq!(
+            format!("{err:#}"),
+            "Invalid default for 'authors': Unknown reference '${home:user}' in '${home:user}': \
+             expected `${env:NAME}` or `${git:key}`"
+        );
+    }
+}
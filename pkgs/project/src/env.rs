@@ -0,0 +1,65 @@
+use std::fs;
+
+use crate::prelude::*;
+pub use nmcr_types_internal::{GitEnv, GitUserEnv, ProjectEnv, RenderEnv};
+
+impl Project {
+    /// Run details for the `_ctx` render namespace: the project root and name, and the git
+    /// user and branch read from the local config and checkout without touching the network.
+    /// The user-global library has no project, so git is read from the working directory.
+    pub fn render_env(&self) -> RenderEnv {
+        let (project, git_dir) = if self.is_global() {
+            (None, PathBuf::from("."))
+        } else {
+            let root = self.path();
+            let root = fs::canonicalize(&root).unwrap_or(root);
+            let name = root
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let project = ProjectEnv {
+                root: root.display().to_string(),
+                name,
+            };
+            (Some(project), root)
+        };
+        let read = |args: &[&str]| git(&git_dir, args).ok().filter(|value| !value.is_empty());
+        RenderEnv {
+            project,
+            out: None,
+            git: GitEnv {
+                user: GitUserEnv {
+                    name: read(&["config", "--get", "user.name"]),
+                    email: read(&["config", "--get", "user.email"]),
+                },
+                branch: read(&["symbolic-ref", "--quiet", "--short", "HEAD"]),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_project_and_git_checkout() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("app");
+        fs::create_dir(&root).unwrap();
+        git(&root, &["init", "--quiet", "--initial-branch", "trunk"]).unwrap();
+        git(&root, &["config", "user.name", "Sasha"]).unwrap();
+        git(&root, &["config", "user.email", "sasha@example.com"]).unwrap();
+
+        let project =
+            Project::from_config(Config::new(Config::join_path(&root), ConfigUser::default()));
+        let env = project.render_env();
+        let project_env = env.project.expect("project");
+        assert_eq!(project_env.name, "app");
+        assert!(project_env.root.ends_with("app"));
+        assert_eq!(env.out, None);
+        assert_eq!(env.git.user.name.as_deref(), Some("Sasha"));
+        assert_eq!(env.git.user.email.as_deref(), Some("sasha@example.com"));
+        assert_eq!(env.git.branch.as_deref(), Some("trunk"));
+    }
+}
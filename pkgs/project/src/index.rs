@@ -1,44 +1,511 @@
-use wax::Glob;
-
 use crate::prelude::*;
 
+pub use nmcr_types_internal::{TemplateLayer, TemplateSource};
+use std::collections::HashSet;
+use wax::{Glob, Pattern};
+
 #[derive(Debug, Clone)]
 pub struct Project {
     pub config: Config,
+    /// User-global config, whose templates load beneath the project's own.
+    pub global: Option<Config>,
 }
 
 impl Project {
     pub fn from_config(config: Config) -> Self {
-        Self { config }
+        Self {
+            config,
+            global: None,
+        }
+    }
+
+    pub fn with_global(self, global: Option<Config>) -> Self {
+        Self { global, ..self }
     }
 
-    pub fn load<P: AsRef<Path>>(path: Option<P>) -> Result<Self> {
-        let config = Config::find(path)?;
-        Ok(Self::from_config(config))
+    /// Load the project config and the user-global one, applying `profile` or the one
+    /// selected with `NMCR_PROFILE`.
+    pub fn load<P: AsRef<Path>>(path: Option<P>, profile: Option<&str>) -> Result<Self> {
+        Self::load_with_global_dir(path, profile, Config::global_dir().as_deref())
+    }
+
+    /// Load the project config like [`Self::load`], reading the user-global config from
+    /// `global_dir` instead of `NMCR_HOME` or the user config directory.
+    pub fn load_with_global_dir<P: AsRef<Path>>(
+        path: Option<P>,
+        profile: Option<&str>,
+        global_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let config = Config::find(path, profile)?;
+        let global = match global_dir {
+            Some(dir) => Config::read_global(dir, profile)?,
+            None => None,
+        };
+        Ok(Self::from_config(config).with_global(global))
+    }
+
+    /// The user-global library on its own, for running outside any project.
+    pub fn load_global(profile: Option<&str>) -> Result<Self> {
+        let dir = Config::global_dir().with_context(|| {
+            format!("Failed to locate the user config directory; set {HOME_ENV}")
+        })?;
+        Self::load_global_dir(&dir, profile)
+    }
+
+    /// The user-global library in `dir` on its own, like [`Self::load_global`].
+    pub fn load_global_dir(dir: &Path, profile: Option<&str>) -> Result<Self> {
+        let config = Config::read_global(dir, profile)?.with_context(|| {
+            format!(
+                "No {CONFIG_FILENAME} found and no user-global library at {}",
+                dir.display()
+            )
+        })?;
+        config.user.ensure_profile()?;
+        Ok(Self::from_config(config.clone()).with_global(Some(config)))
+    }
+
+    /// Whether this is the user-global library rather than a project.
+    pub fn is_global(&self) -> bool {
+        self.global
+            .as_ref()
+            .is_some_and(|global| global.path == self.config.path)
+    }
+
+    /// Whether the config lists workspace members.
+    pub fn is_workspace(&self) -> bool {
+        self.config.user.workspace.is_some()
     }
 
     pub fn path(&self) -> PathBuf {
-        self.config
-            .path
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .to_path_buf()
+        config_root(&self.config)
     }
 
+    /// Default output directory of `nmcr gen` set in the config or its profile.
+    pub fn out_dir(&self) -> Option<PathBuf> {
+        let out = self.config.user.out.as_ref()?;
+        Some(self.path().join(out))
+    }
+
+    /// Template files matching the local glob, followed by those of every fetched source.
     pub fn template_paths(&self) -> Result<Vec<PathBuf>> {
-        let mut paths = Vec::new();
+        Ok(self
+            .template_sources()?
+            .into_iter()
+            .flat_map(|source| source.paths)
+            .collect())
+    }
 
-        let pattern = self.config.user.normalized_templates();
-        let glob = Glob::new(&pattern)
-            .with_context(|| format!("Failed to build glob with pattern: {}", &pattern))?;
+    /// Template files grouped by source: the local globs and named roots first, then every
+    /// fetched source with its namespace, then the user-global library.
+    pub fn template_sources(&self) -> Result<Vec<TemplateSource>> {
+        let layer = if self.is_global() {
+            TemplateLayer::Global
+        } else {
+            TemplateLayer::Local
+        };
+        // A workspace root may keep no templates of its own
+        let mut sources = glob_sources(&self.config, layer, self.is_workspace())?;
 
-        for entry in glob.walk(self.path()) {
-            let entry = entry?;
-            if entry.file_type().is_file() {
-                paths.push(entry.path().to_path_buf());
-            }
+        for source in &self.config.user.sources {
+            sources.push(TemplateSource {
+                layer: TemplateLayer::Source,
+                namespace: source.namespace.clone(),
+                paths: self.source_template_paths(source)?,
+                out: None,
+            });
         }
 
+        if let Some(global) = self.global.as_ref().filter(|_| !self.is_global()) {
+            sources.extend(glob_sources(global, TemplateLayer::Global, true)?);
+        }
+
+        Ok(sources)
+    }
+
+    /// Paths of the helper scripts listed in the user-global config and then in the project
+    /// config, each relative to its own directory.
+    pub fn helper_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = match self.global.as_ref().filter(|_| !self.is_global()) {
+            Some(global) => config_helper_paths(global)?,
+            None => Vec::new(),
+        };
+        paths.extend(config_helper_paths(&self.config)?);
         Ok(paths)
     }
+
+    /// Whether a template file lives inside the project or the user-global library rather
+    /// than in a fetched source, so its hooks run without asking.
+    pub fn is_trusted_source(&self, path: &Path) -> bool {
+        let Ok(path) = path.canonicalize() else {
+            return false;
+        };
+        std::iter::once(&self.config)
+            .chain(&self.global)
+            .any(|config| {
+                let root = config_root(config);
+                let root = if root.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    &root
+                };
+                root.canonicalize().is_ok_and(|root| {
+                    path.starts_with(&root) && !path.starts_with(root.join(SOURCES_DIR))
+                })
+            })
+    }
+}
+
+fn config_root(config: &Config) -> PathBuf {
+    match config.path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Sources matched by the config's globs: the `templates` group followed by each named
+/// root, whose files the group leaves out. Unless `lenient`, a glob matching no files
+/// fails.
+fn glob_sources(
+    config: &Config,
+    layer: TemplateLayer,
+    lenient: bool,
+) -> Result<Vec<TemplateSource>> {
+    let user = &config.user;
+    let mut roots = Vec::new();
+    let mut grouped: HashSet<PathBuf> = HashSet::new();
+    for (name, group) in &user.roots {
+        let mut exclude = user.exclude.normalized_excludes();
+        exclude.extend(group.exclude.normalized_excludes());
+        let paths = glob_files(config, &group.templates, &exclude, lenient)
+            .with_context(|| format!("Failed to collect the templates of root '{name}'"))?;
+        grouped.extend(paths.iter().cloned());
+        roots.push(TemplateSource {
+            layer,
+            namespace: None,
+            paths,
+            out: group.out.as_ref().map(|out| config_root(config).join(out)),
+        });
+    }
+
+    let paths = glob_files(
+        config,
+        &user.templates,
+        &user.exclude.normalized_excludes(),
+        lenient,
+    )?
+    .into_iter()
+    .filter(|path| !grouped.contains(path))
+    .collect();
+    let mut sources = vec![TemplateSource {
+        layer,
+        namespace: None,
+        paths,
+        out: None,
+    }];
+    sources.extend(roots);
+    Ok(sources)
+}
+
+/// Files matching any of the globs and none of the excludes, outside fetched sources.
+fn glob_files(
+    config: &Config,
+    globs: &ConfigGlobs,
+    exclude: &[String],
+    lenient: bool,
+) -> Result<Vec<PathBuf>> {
+    let root = config_root(config);
+    let sources_dir = root.join(SOURCES_DIR);
+    let exclude = exclude
+        .iter()
+        .map(|pattern| {
+            Glob::new(pattern)
+                .with_context(|| format!("Failed to build glob with pattern: {pattern}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for (pattern, normalized) in globs.iter().zip(globs.normalized()) {
+        let matched: Vec<PathBuf> = walk_files(&normalized, &root)?
+            .into_iter()
+            .filter(|path| !path.starts_with(&sources_dir))
+            .filter(|path| {
+                let relative = path.strip_prefix(&root).unwrap_or(path);
+                !exclude.iter().any(|glob| glob.is_match(relative))
+            })
+            .collect();
+        if matched.is_empty() && !lenient {
+            bail!(
+                "Template glob '{pattern}' in {} matches no files",
+                config.path.display()
+            );
+        }
+        for path in matched {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+fn config_helper_paths(config: &Config) -> Result<Vec<PathBuf>> {
+    let root = config_root(config);
+    config
+        .user
+        .helpers
+        .iter()
+        .map(|helper| {
+            let path = root.join(helper);
+            if !path.is_file() {
+                bail!(
+                    "Helper script listed in {} not found: {}",
+                    config.path.display(),
+                    path.display()
+                );
+            }
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    /// Load without the user-global config so tests don't depend on the machine's.
+    fn load(path: &Path, profile: Option<&str>) -> Result<Project> {
+        Project::load_with_global_dir(Some(path), profile, None)
+    }
+
+    #[test]
+    fn global_templates_load_beneath_the_project() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let global_dir = dir.path().join("global");
+        assert!(Config::read_global(&global_dir, None).unwrap().is_none());
+
+        write(&global_dir.join("tmpls/snippet.md"), "# Snippet\n");
+        write(&global_dir.join("helpers/shout.rhai"), "");
+        write(
+            &Config::join_path(&global_dir),
+            "helpers = [\"helpers/shout.rhai\"]\n",
+        );
+        write(&dir.path().join("project/tmpls/readme.md"), "# Readme\n");
+        let global = Config::read_global(&global_dir, None)
+            .unwrap()
+            .expect("global");
+        let project = Project::from_config(Config::new(
+            Config::join_path(dir.path().join("project")),
+            ConfigUser::default(),
+        ))
+        .with_global(Some(global.clone()));
+
+        let sources = project.template_sources().unwrap();
+        let layers: Vec<_> = sources
+            .iter()
+            .map(|source| (source.layer, source.paths.len()))
+            .collect();
+        assert_eq!(
+            layers,
+            [(TemplateLayer::Local, 1), (TemplateLayer::Global, 1)]
+        );
+        assert_eq!(
+            project.helper_paths().unwrap(),
+            [global_dir.join("helpers/shout.rhai")]
+        );
+        assert!(project.is_trusted_source(&global_dir.join("tmpls/snippet.md")));
+
+        let standalone = Project::from_config(global.clone()).with_global(Some(global));
+        assert!(standalone.is_global());
+        let sources = standalone.template_sources().unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].layer, TemplateLayer::Global);
+    }
+
+    #[test]
+    fn global_library_errors() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let global_dir = dir.path().join("global");
+        let err = Project::load_global_dir(&global_dir, None).expect_err("no library");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "No {CONFIG_FILENAME} found and no user-global library at {}",
+                global_dir.display()
+            )
+        );
+
+        write(
+            &Config::join_path(&global_dir),
+            "[profiles.oss]\nout = \"./public\"\n",
+        );
+        let err = Project::load_global_dir(&global_dir, Some("staging")).expect_err("unknown");
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'staging'; defined profiles: oss"
+        );
+
+        // A project profile the global config lacks leaves the global config as is
+        let config_path = Config::join_path(dir.path().join("project"));
+        write(
+            &config_path,
+            "templates = []\n\n[profiles.staging]\nout = \"./staging\"\n",
+        );
+        let project =
+            Project::load_with_global_dir(Some(&config_path), Some("staging"), Some(&global_dir))
+                .unwrap();
+        assert!(project.global.expect("global").user.out.is_none());
+
+        write(&Config::join_path(&global_dir), "templates = 1 = 2\n");
+        assert!(
+            Project::load_with_global_dir(Some(&config_path), None, Some(&global_dir)).is_err()
+        );
+    }
+
+    #[test]
+    fn globs_excludes_and_named_roots() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        for file in [
+            "tmpls/readme.md",
+            "tmpls/README.md",
+            "tmpls/drafts/wip.md",
+            "shared/license.md",
+            "web/page.md",
+        ] {
+            write(&dir.path().join(file), "# Template\n");
+        }
+        let config_path = Config::join_path(dir.path());
+        write(
+            &config_path,
+            r#"
+templates = ["./tmpls/**/*.md", "./shared/*.md", "./web/*.md"]
+exclude = ["**/drafts/**", "README.md"]
+
+[roots.web]
+templates = "./web/*.md"
+out = "./apps/web"
+"#,
+        );
+        let project = load(&config_path, None).unwrap();
+        let sources = project.template_sources().unwrap();
+        let relative = |source: &TemplateSource| -> Vec<PathBuf> {
+            source
+                .paths
+                .iter()
+                .map(|path| path.strip_prefix(dir.path()).unwrap().to_path_buf())
+                .collect()
+        };
+        assert_eq!(
+            relative(&sources[0]),
+            [
+                PathBuf::from("tmpls/readme.md"),
+                PathBuf::from("shared/license.md")
+            ]
+        );
+        assert_eq!(sources[0].out, None);
+        assert_eq!(relative(&sources[1]), [PathBuf::from("web/page.md")]);
+        assert_eq!(sources[1].out, Some(dir.path().join("./apps/web")));
+
+        let toml = toml::to_string(&ConfigUser::default()).unwrap();
+        assert!(toml.contains("templates = \"./tmpls/**/*.md\""), "{toml}");
+
+        write(
+            &config_path,
+            "templates = [\"./tmpls/*.md\", \"./missing/*.md\"]\n",
+        );
+        let err = load(&config_path, None)
+            .unwrap()
+            .template_sources()
+            .expect_err("empty glob");
+        assert!(
+            err.to_string()
+                .contains("Template glob './missing/*.md' in"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn glob_errors_name_the_pattern_and_root() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        write(&dir.path().join("tmpls/readme.md"), "# Template\n");
+        let config_path = Config::join_path(dir.path());
+        let sources_error = |config: &str| {
+            write(&config_path, config);
+            let project = load(&config_path, None).unwrap();
+            format!("{:#}", project.template_sources().expect_err("glob error"))
+        };
+
+        assert_eq!(
+            sources_error("templates = \"./tmpls/*.md\"\nexclude = \"readme.md\"\n"),
+            format!(
+                "Template glob './tmpls/*.md' in {} matches no files",
+                config_path.display()
+            )
+        );
+        assert!(
+            sources_error("templates = \"./tmpls/*.md\"\nexclude = \"[\"\n")
+                .starts_with("Failed to build glob with pattern: **/[: ")
+        );
+        assert!(
+            sources_error(
+                "templates = \"./tmpls/*.md\"\n\n[roots.web]\ntemplates = \"./web/*.md\"\n"
+            )
+            .starts_with(
+                "Failed to collect the templates of root 'web': Template glob './web/*.md'"
+            )
+        );
+    }
+
+    #[test]
+    fn profiles_override_defaults_sources_out_and_mcp() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        write(&dir.path().join("tmpls/readme.md"), "# Template\n");
+        let config_path = Config::join_path(dir.path());
+        write(
+            &config_path,
+            r#"
+profile = "internal"
+out = "./out"
+
+[defaults]
+license = "MIT"
+author = "Team"
+
+[profiles.internal]
+defaults = { license = "UNLICENSED" }
+sources = [{ git = "https://example.com/internal.git" }]
+mcp = { expose = "internal::*" }
+
+[profiles.oss]
+out = "./public"
+"#,
+        );
+
+        let project = load(&config_path, None).unwrap();
+        let user = &project.config.user;
+        assert_eq!(user.defaults["license"], "UNLICENSED");
+        assert_eq!(user.defaults["author"], "Team");
+        assert_eq!(user.sources.len(), 1);
+        assert_eq!(project.out_dir(), Some(dir.path().join("./out")));
+        assert!(user.mcp.exposes("internal::crate"));
+        assert!(!user.mcp.exposes("readme"));
+
+        let project = load(&config_path, Some("oss")).unwrap();
+        let user = &project.config.user;
+        assert_eq!(user.defaults["license"], "MIT");
+        assert!(user.sources.is_empty());
+        assert_eq!(project.out_dir(), Some(dir.path().join("./public")));
+        assert!(user.mcp.exposes("readme"));
+
+        let err = load(&config_path, Some("staging")).expect_err("unknown");
+        assert_eq!(
+            err.to_string(),
+            "Unknown profile 'staging'; defined profiles: internal, oss"
+        );
+    }
 }
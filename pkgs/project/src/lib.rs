@@ -1,3 +1,8 @@
 pub mod config;
+pub mod defaults;
+pub mod env;
 pub mod index;
+pub mod lock;
 pub mod prelude;
+pub mod sources;
+pub mod workspace;
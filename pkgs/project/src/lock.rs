@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use sha2::{Digest, Sha256};
+
+use crate::prelude::*;
+use crate::sources::git;
+
+pub const LOCK_FILENAME: &str = "ncmr.lock";
+
+const LOCK_VERSION: u32 = 1;
+
+const LOCK_HEADER: &str = "# Generated by `nmcr sources update`; do not edit by hand.\n\n";
+
+/// Resolved state of every template source, written to `ncmr.lock`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<LockedSource>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LockedSource {
+    pub git: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    pub path: String,
+    /// Commit the source was checked out at.
+    pub commit: String,
+    /// `sha256:<hex>` hash of each template file, keyed by its path in the repository.
+    pub files: BTreeMap<String, String>,
+}
+
+impl LockedSource {
+    /// Whether the entry was resolved from the source as currently configured.
+    fn matches(&self, source: &ConfigSource) -> bool {
+        self.git == source.git && self.rev == source.rev && self.path == source.path
+    }
+}
+
+/// Outcome of checking fetched sources against `ncmr.lock`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockCheck {
+    /// The lockfile is up to date and every source matches it.
+    Verified,
+    /// Sources are configured but there is no lockfile.
+    Missing,
+    /// The lockfile was written for other sources than the configured ones.
+    Stale,
+}
+
+impl LockCheck {
+    /// Warning to show when sources are used without an up-to-date lockfile.
+    pub fn warning(self) -> Option<String> {
+        match self {
+            LockCheck::Verified => None,
+            LockCheck::Missing => Some(format!(
+                "{LOCK_FILENAME} is missing; run `nmcr sources update` to pin template sources"
+            )),
+            LockCheck::Stale => Some(format!(
+                "{LOCK_FILENAME} is out of date; run `nmcr sources update` to refresh it"
+            )),
+        }
+    }
+}
+
+impl Project {
+    pub fn lock_path(&self) -> PathBuf {
+        self.path().join(LOCK_FILENAME)
+    }
+
+    pub fn read_lock(&self) -> Result<Option<Lockfile>> {
+        let path = self.lock_path();
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let lock: Lockfile = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        if lock.version != LOCK_VERSION {
+            bail!(
+                "Unsupported {} version {}; run `nmcr sources update`",
+                LOCK_FILENAME,
+                lock.version
+            );
+        }
+        Ok(Some(lock))
+    }
+
+    /// Record the checked out commit and template file hashes of every source.
+    pub(crate) fn write_lock(&self, commits: &[String]) -> Result<()> {
+        let sources = self
+            .config
+            .user
+            .sources
+            .iter()
+            .zip(commits)
+            .map(|(source, commit)| {
+                Ok(LockedSource {
+                    git: source.git.clone(),
+                    rev: source.rev.clone(),
+                    path: source.path.clone(),
+                    commit: commit.clone(),
+                    files: self.hash_source_files(source)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let lock = Lockfile {
+            version: LOCK_VERSION,
+            sources,
+        };
+        let toml = toml::to_string_pretty(&lock).context("Failed to serialize lockfile")?;
+        let path = self.lock_path();
+        fs::write(&path, format!("{LOCK_HEADER}{toml}"))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The lockfile, failing when it is missing or does not match the configured sources.
+    pub(crate) fn fresh_lock(&self) -> Result<Lockfile> {
+        match self.read_lock()? {
+            Some(lock) if self.lock_is_fresh(&lock) => Ok(lock),
+            Some(_) => bail!(
+                "{LOCK_FILENAME} does not match the sources in {}; run `nmcr sources update` to refresh it",
+                self.config.path.display()
+            ),
+            None => bail!("{LOCK_FILENAME} is missing; run `nmcr sources update` to create it"),
+        }
+    }
+
+    fn lock_is_fresh(&self, lock: &Lockfile) -> bool {
+        let sources = &self.config.user.sources;
+        lock.sources.len() == sources.len()
+            && lock
+                .sources
+                .iter()
+                .zip(sources)
+                .all(|(entry, source)| entry.matches(source))
+    }
+
+    /// Check fetched sources against the lockfile. A missing or stale lockfile is reported,
+    /// or an error in locked mode; a source that differs from its pinned commit or file
+    /// hashes is always an error.
+    pub fn verify_lock(&self, locked: bool) -> Result<LockCheck> {
+        if self.config.user.sources.is_empty() {
+            return Ok(LockCheck::Verified);
+        }
+        let lock = match self.read_lock()? {
+            Some(lock) if self.lock_is_fresh(&lock) => lock,
+            // Fails with the reason the lockfile cannot be used
+            _ if locked => self.fresh_lock()?,
+            Some(_) => return Ok(LockCheck::Stale),
+            None => return Ok(LockCheck::Missing),
+        };
+
+        for (source, entry) in self.config.user.sources.iter().zip(&lock.sources) {
+            let dir = self.source_dir(source);
+            if !dir.is_dir() {
+                bail!(
+                    "Template source {} is not fetched; run `nmcr sources update --locked`",
+                    source.git
+                );
+            }
+            let head = git(&dir, &["rev-parse", "HEAD"])?;
+            if head != entry.commit {
+                bail!(
+                    "Template source {} is checked out at {head} but {LOCK_FILENAME} pins {}; run `nmcr sources update --locked`",
+                    source.git,
+                    entry.commit
+                );
+            }
+            let files = self.hash_source_files(source)?;
+            let changed = entry
+                .files
+                .keys()
+                .chain(files.keys())
+                .find(|file| entry.files.get(*file) != files.get(*file));
+            if let Some(file) = changed {
+                bail!(
+                    "Template file {file} of source {} does not match {LOCK_FILENAME}",
+                    source.git
+                );
+            }
+        }
+        Ok(LockCheck::Verified)
+    }
+
+    /// Hashes of a fetched source's template files, keyed by path within the checkout.
+    fn hash_source_files(&self, source: &ConfigSource) -> Result<BTreeMap<String, String>> {
+        let dir = self.source_dir(source);
+        self.source_template_paths(source)?
+            .into_iter()
+            .map(|path| {
+                let content = fs::read(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let name = path
+                    .strip_prefix(&dir)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                Ok((name, format!("sha256:{:x}", Sha256::digest(&content))))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::tests::project_with_sources;
+
+    #[test]
+    fn verifies_sources_against_the_lockfile() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut project = project_with_sources(dir.path());
+
+        assert_eq!(
+            project.verify_lock(false).expect("checked"),
+            LockCheck::Missing
+        );
+        let err = project.update_sources(true).expect_err("no lockfile");
+        assert!(err.to_string().starts_with("ncmr.lock is missing"));
+
+        let commits = project.update_sources(false).expect("sources update");
+        let lock = project.read_lock().expect("read").expect("written");
+        assert_eq!(lock.sources[0].commit, commits[0]);
+        assert_eq!(
+            lock.sources[1].files.keys().collect::<Vec<_>>(),
+            vec!["tmpls/license.md", "tmpls/readme.md"]
+        );
+        assert!(lock.sources[1].files["tmpls/readme.md"].starts_with("sha256:"));
+        assert_eq!(
+            project.verify_lock(true).expect("verified"),
+            LockCheck::Verified
+        );
+
+        // Edits to the cache are caught and undone by a locked update
+        let cached = project
+            .source_dir(&project.config.user.sources[0])
+            .join("tmpls/readme.md");
+        fs::write(&cached, "# Edited\n").unwrap();
+        let err = project.verify_lock(false).expect_err("tampered");
+        assert!(
+            err.to_string()
+                .starts_with("Template file tmpls/readme.md of source")
+        );
+        assert_eq!(
+            project.update_sources(true).expect("locked update"),
+            commits
+        );
+        assert_eq!(
+            project.verify_lock(true).expect("verified"),
+            LockCheck::Verified
+        );
+
+        project.config.user.sources[0].rev = Some("main".into());
+        assert_eq!(
+            project.verify_lock(false).expect("checked"),
+            LockCheck::Stale
+        );
+        let err = project.verify_lock(true).expect_err("stale lockfile");
+        assert!(err.to_string().starts_with("ncmr.lock does not match"));
+    }
+
+    #[test]
+    fn reports_why_a_locked_check_fails() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let project = project_with_sources(dir.path());
+        let err = project.verify_lock(true).expect_err("no lockfile");
+        assert_eq!(
+            err.to_string(),
+            "ncmr.lock is missing; run `nmcr sources update` to create it"
+        );
+
+        project.update_sources(false).expect("sources update");
+        let latest = &project.config.user.sources[1];
+        let checkout = project.source_dir(latest);
+        fs::write(checkout.join("tmpls/license.md"), "# Edited\n").unwrap();
+        let err = project.verify_lock(true).expect_err("hash mismatch");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Template file tmpls/license.md of source {} does not match ncmr.lock",
+                latest.git
+            )
+        );
+
+        git(&checkout, &["checkout", "--quiet", "--force", "HEAD~1"]).unwrap();
+        let err = project.verify_lock(true).expect_err("moved checkout");
+        assert!(
+            err.to_string().starts_with(&format!(
+                "Template source {} is checked out at ",
+                latest.git
+            )),
+            "unexpected error: {err}"
+        );
+
+        fs::remove_dir_all(&checkout).unwrap();
+        let err = project.verify_lock(true).expect_err("not fetched");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Template source {} is not fetched; run `nmcr sources update --locked`",
+                latest.git
+            )
+        );
+
+        fs::write(project.lock_path(), "version = 2\nsources = []\n").unwrap();
+        let err = project.verify_lock(true).expect_err("future lockfile");
+        assert_eq!(
+            err.to_string(),
+            "Unsupported ncmr.lock version 2; run `nmcr sources update`"
+        );
+    }
+}
@@ -2,4 +2,8 @@ mod internal;
 pub(crate) use internal::*;
 
 pub use crate::config::*;
+pub use crate::env::*;
 pub use crate::index::*;
+pub use crate::lock::*;
+pub use crate::sources::*;
+pub use crate::workspace::*;
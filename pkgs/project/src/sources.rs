@@ -0,0 +1,330 @@
+use std::fs;
+use std::process::Command;
+
+use wax::Glob;
+
+use crate::prelude::*;
+
+/// Directory under the project root that caches fetched template sources.
+pub const SOURCES_DIR: &str = ".nmcr/sources";
+
+impl Project {
+    /// Directory caching fetched template sources.
+    pub fn sources_dir(&self) -> PathBuf {
+        self.path().join(SOURCES_DIR)
+    }
+
+    /// Checkout directory of a source, named after its URL and revision.
+    pub fn source_dir(&self, source: &ConfigSource) -> PathBuf {
+        let mut name: String = source
+            .git
+            .split("://")
+            .last()
+            .unwrap_or(&source.git)
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .chars()
+            .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+            .collect();
+        if let Some(rev) = &source.rev {
+            name.push('@');
+            name.extend(rev.chars().map(|ch| {
+                if ch.is_ascii_alphanumeric() || ch == '.' {
+                    ch
+                } else {
+                    '_'
+                }
+            }));
+        }
+        self.sources_dir().join(name)
+    }
+
+    /// Template files of a fetched source that match its glob.
+    pub fn source_template_paths(&self, source: &ConfigSource) -> Result<Vec<PathBuf>> {
+        let dir = self.source_dir(source);
+        if !dir.is_dir() {
+            bail!(
+                "Template source {} is not fetched; run `nmcr sources update`",
+                source.git
+            );
+        }
+        walk_files(&source.normalized_path(), &dir)
+    }
+
+    /// Clone missing sources and fetch existing ones, then check out their revisions and
+    /// refresh the lockfile. In locked mode the commits pinned by an up-to-date lockfile
+    /// are checked out and verified instead. Returns the checked out commit of each source.
+    pub fn update_sources(&self, locked: bool) -> Result<Vec<String>> {
+        let sources = &self.config.user.sources;
+        if locked {
+            let lock = self.fresh_lock()?;
+            let commits = sources
+                .iter()
+                .zip(&lock.sources)
+                .map(|(source, entry)| self.update_source(source, Some(&entry.commit)))
+                .collect::<Result<Vec<_>>>()?;
+            self.verify_lock(true)?;
+            return Ok(commits);
+        }
+
+        let commits = sources
+            .iter()
+            .map(|source| self.update_source(source, None))
+            .collect::<Result<Vec<_>>>()?;
+        self.write_lock(&commits)?;
+        Ok(commits)
+    }
+
+    /// Fetch a source and check out `commit`, or its configured revision.
+    fn update_source(&self, source: &ConfigSource, commit: Option<&str>) -> Result<String> {
+        // Values reach git as arguments, where a leading dash would read as an option
+        for (field, value) in [
+            ("git", Some(source.git.as_str())),
+            ("rev", source.rev.as_deref()),
+            ("commit", commit),
+        ] {
+            if let Some(value) = value.filter(|value| value.starts_with('-')) {
+                bail!("Template source {field} '{value}' can't start with '-'");
+            }
+        }
+        let dir = self.source_dir(source);
+        if dir.join(".git").is_dir() {
+            git(&dir, &["fetch", "--quiet", "--tags", "--force", "origin"])?;
+        } else {
+            fs::create_dir_all(self.sources_dir())?;
+            let target = dir
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            git(
+                &self.sources_dir(),
+                &[
+                    "clone",
+                    "--quiet",
+                    "--no-checkout",
+                    "--",
+                    &source.git,
+                    &target,
+                ],
+            )
+            .with_context(|| format!("Failed to clone template source {}", source.git))?;
+        }
+
+        // Prefer the remote branch so fetched updates are picked up
+        let rev = match (commit, &source.rev) {
+            (Some(commit), _) => commit.to_string(),
+            (None, Some(rev)) => {
+                let remote = format!("origin/{rev}^{{commit}}");
+                if git(&dir, &["rev-parse", "--verify", "--quiet", &remote]).is_ok() {
+                    format!("origin/{rev}")
+                } else {
+                    rev.clone()
+                }
+            }
+            (None, None) => "origin/HEAD".to_string(),
+        };
+        git(&dir, &["checkout", "--quiet", "--force", "--detach", &rev]).with_context(|| {
+            format!(
+                "Failed to check out {rev} of template source {}",
+                source.git
+            )
+        })?;
+        git(&dir, &["rev-parse", "HEAD"])
+    }
+}
+
+/// Files under `root` that match a glob pattern.
+pub(crate) fn walk_files(pattern: &str, root: &Path) -> Result<Vec<PathBuf>> {
+    let glob = Glob::new(pattern)
+        .with_context(|| format!("Failed to build glob with pattern: {}", pattern))?;
+    let mut paths = Vec::new();
+    for entry in glob.walk(root) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // The directory the glob starts from doesn't exist, so nothing matches
+            Err(error) if error.depth() == 0 && error.path().is_some_and(|path| !path.exists()) => {
+                break;
+            }
+            Err(error) => return Err(error.into()),
+        };
+        if entry.file_type().is_file() {
+            paths.push(entry.path().to_path_buf());
+        }
+    }
+    Ok(paths)
+}
+
+/// Run git in `dir`, returning its trimmed stdout.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.first().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn commit(repo: &Path, file: &str, content: &str) {
+        fs::write(repo.join(file), content).unwrap();
+        git(repo, &["add", "."]).unwrap();
+        git(
+            repo,
+            &[
+                "-c",
+                "user.name=nmcr",
+                "-c",
+                "user.email=nmcr@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                file,
+            ],
+        )
+        .unwrap();
+    }
+
+    /// A project in `dir` with two sources from a bare repo: `tmpls/*.md` at tag `v1`
+    /// and every markdown file on the default branch, one commit ahead.
+    pub(crate) fn project_with_sources(dir: &Path) -> Project {
+        let work = dir.join("work");
+        fs::create_dir_all(work.join("tmpls")).unwrap();
+        git(&work, &["init", "--quiet", "--initial-branch=main"]).unwrap();
+        commit(&work, "tmpls/readme.md", "# Readme\n");
+        git(&work, &["tag", "v1"]).unwrap();
+        commit(&work, "tmpls/license.md", "# License\n");
+        git(dir, &["clone", "--quiet", "--bare", "work", "shared.git"]).unwrap();
+
+        let url = format!("file://{}", dir.join("shared.git").display());
+        let project_dir = dir.join("project");
+        fs::create_dir_all(&project_dir).unwrap();
+        let user = ConfigUser {
+            sources: vec![
+                ConfigSource {
+                    git: url.clone(),
+                    rev: Some("v1".into()),
+                    path: "tmpls/*.md".into(),
+                    namespace: None,
+                },
+                ConfigSource {
+                    git: url,
+                    rev: None,
+                    path: Config::default_source_glob(),
+                    namespace: None,
+                },
+            ],
+            ..Default::default()
+        };
+        Project::from_config(Config::new(Config::join_path(&project_dir), user))
+    }
+
+    #[test]
+    fn fetches_git_sources_into_the_cache() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let project = project_with_sources(dir.path());
+
+        let pinned = &project.config.user.sources[0];
+        let err = project
+            .source_template_paths(pinned)
+            .expect_err("not fetched yet");
+        assert!(err.to_string().contains("nmcr sources update"));
+
+        let commits = project.update_sources(false).expect("sources update");
+        assert_eq!(commits.len(), 2);
+        assert_ne!(commits[0], commits[1]);
+        let names = |source: &ConfigSource| -> Vec<String> {
+            let mut names: Vec<String> = project
+                .source_template_paths(source)
+                .unwrap()
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(pinned), vec!["readme.md"]);
+        assert_eq!(
+            names(&project.config.user.sources[1]),
+            vec!["license.md", "readme.md"]
+        );
+
+        // Updating again fetches into the existing checkouts
+        assert_eq!(
+            project.update_sources(false).expect("sources update"),
+            commits
+        );
+    }
+
+    fn project_with_source(dir: &Path, source: ConfigSource) -> Project {
+        let user = ConfigUser {
+            sources: vec![source],
+            ..Default::default()
+        };
+        Project::from_config(Config::new(Config::join_path(dir), user))
+    }
+
+    #[test]
+    fn rejects_sources_that_would_pass_git_options() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let marker = dir.path().join("pwned");
+        let injected = project_with_source(
+            dir.path(),
+            ConfigSource {
+                git: format!("--upload-pack=touch {}", marker.display()),
+                rev: None,
+                path: Config::default_source_glob(),
+                namespace: None,
+            },
+        );
+        let err = injected.update_sources(false).expect_err("option as url");
+        assert!(
+            err.to_string()
+                .starts_with("Template source git '--upload-pack=touch"),
+            "unexpected error: {err}"
+        );
+        assert!(!marker.exists());
+
+        let project = project_with_sources(dir.path());
+        let mut source = project.config.user.sources[0].clone();
+        source.rev = Some("--orphan=main".into());
+        let err = project_with_source(dir.path(), source)
+            .update_sources(false)
+            .expect_err("option as rev");
+        assert_eq!(
+            err.to_string(),
+            "Template source rev '--orphan=main' can't start with '-'"
+        );
+    }
+
+    #[test]
+    fn reports_sources_that_fail_to_clone() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let missing = format!("file://{}", dir.path().join("missing.git").display());
+        let project = project_with_source(
+            dir.path(),
+            ConfigSource {
+                git: missing.clone(),
+                rev: None,
+                path: Config::default_source_glob(),
+                namespace: None,
+            },
+        );
+        let err = project.update_sources(false).expect_err("clone fails");
+        assert_eq!(
+            err.to_string(),
+            format!("Failed to clone template source {missing}")
+        );
+        assert!(format!("{err:#}").contains("git clone failed"));
+        assert!(!project.source_dir(&project.config.user.sources[0]).exists());
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashSet;
+use std::fs;
+
+use nmcr_id::EntityId;
+use wax::{Glob, Pattern};
+
+use crate::prelude::*;
+
+/// A monorepo root whose config lists member projects in its `[workspace]` table.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    pub root: Project,
+    pub members: Vec<WorkspaceMember>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceMember {
+    /// Namespace of the member's templates: its directory name in snake_case.
+    pub name: String,
+    pub project: Project,
+}
+
+impl Workspace {
+    /// Load the members listed by a workspace root. A member applies the root's profile
+    /// when it defines one, and shares the root's user-global config.
+    pub fn load(root: Project) -> Result<Self> {
+        let Some(workspace) = &root.config.user.workspace else {
+            bail!("{} has no [workspace] table", root.config.path.display());
+        };
+        let profile = root.config.user.profile.as_deref();
+        let root_dir = root.path();
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for (pattern, normalized) in workspace.members.iter().zip(workspace.members.normalized()) {
+            let mut matched: Vec<PathBuf> = match normalized.trim_end_matches('/') {
+                "" | "." => vec![root_dir.clone()],
+                normalized => walk_files(&format!("{normalized}/{CONFIG_FILENAME}"), &root_dir)?
+                    .into_iter()
+                    .filter_map(|path| path.parent().map(Path::to_path_buf))
+                    .collect(),
+            };
+            matched.sort();
+            if matched.is_empty() {
+                bail!(
+                    "Workspace member '{pattern}' in {} matches no directory with {CONFIG_FILENAME}",
+                    root.config.path.display()
+                );
+            }
+            for dir in matched {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+
+        let mut members: Vec<WorkspaceMember> = Vec::new();
+        for dir in dirs {
+            let dir_name = canonical(&dir)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let name = EntityId::normalize_segment(&dir_name);
+            if let Some(other) = members.iter().find(|member| member.name == name) {
+                bail!(
+                    "Workspace members {} and {} share the name '{name}'",
+                    other.project.path().display(),
+                    dir.display()
+                );
+            }
+            let config = Config::read_lenient(Config::join_path(&dir), true, profile)
+                .with_context(|| format!("Failed to load workspace member {}", dir.display()))?;
+            members.push(WorkspaceMember {
+                name,
+                project: Project::from_config(config).with_global(root.global.clone()),
+            });
+        }
+        Ok(Self { root, members })
+    }
+
+    /// The workspace rooted at the project, or the one of the nearest parent workspace root
+    /// when it lists the project as a member.
+    pub fn find(project: &Project) -> Result<Option<Self>> {
+        if project.is_workspace() {
+            return Self::load(project.clone()).map(Some);
+        }
+        let Some((config, _)) = parent_root(project) else {
+            return Ok(None);
+        };
+        let workspace =
+            Self::load(Project::from_config(config).with_global(project.global.clone()))?;
+        Ok(workspace.member(project).is_some().then_some(workspace))
+    }
+
+    /// Whether the project is a member of its own workspace or of the nearest parent
+    /// workspace root, going by the member patterns alone so no member gets loaded.
+    pub fn is_member(project: &Project) -> bool {
+        let (config, relative) = match &project.config.user.workspace {
+            Some(_) => (project.config.clone(), PathBuf::new()),
+            None => match parent_root(project) {
+                Some(found) => found,
+                None => return false,
+            },
+        };
+        let Some(workspace) = &config.user.workspace else {
+            return false;
+        };
+        workspace
+            .members
+            .normalized()
+            .iter()
+            .any(|pattern| match pattern.trim_end_matches('/') {
+                "" | "." => relative.as_os_str().is_empty(),
+                pattern => Glob::new(pattern).is_ok_and(|glob| glob.is_match(relative.as_path())),
+            })
+    }
+
+    /// The member whose root is the project's.
+    pub fn member(&self, project: &Project) -> Option<&WorkspaceMember> {
+        let root = canonical(&project.path());
+        self.members
+            .iter()
+            .find(|member| canonical(&member.project.path()) == root)
+    }
+
+    /// Template sources of every member, as listed by [`WorkspaceMember::template_sources`].
+    pub fn template_sources(&self) -> Result<Vec<TemplateSource>> {
+        let mut sources = Vec::new();
+        for member in &self.members {
+            sources.extend(member.template_sources()?);
+        }
+        Ok(sources)
+    }
+
+    /// Helper scripts of every member, each registered once.
+    pub fn helper_paths(&self) -> Result<Vec<PathBuf>> {
+        let mut seen = HashSet::new();
+        let mut paths = Vec::new();
+        for member in &self.members {
+            for path in member.project.helper_paths()? {
+                if seen.insert(canonical(&path)) {
+                    paths.push(path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Lockfile warnings of the members, prefixed with the member name.
+    pub fn lock_warnings(&self, locked: bool) -> Result<Vec<String>> {
+        let mut warnings = Vec::new();
+        for member in &self.members {
+            let check = member
+                .project
+                .verify_lock(locked)
+                .with_context(|| format!("Workspace member '{}'", member.name))?;
+            if let Some(warning) = check.warning() {
+                warnings.push(format!("{}: {warning}", member.name));
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+impl WorkspaceMember {
+    /// Template sources of the member with ids namespaced by its name, as in `web::readme`
+    /// or `web::acme::readme` for a namespaced source. Templates write into the member's
+    /// output directory, or the member root when it sets none. The user-global library is
+    /// left out.
+    pub fn template_sources(&self) -> Result<Vec<TemplateSource>> {
+        let out = self
+            .project
+            .out_dir()
+            .unwrap_or_else(|| self.project.path());
+        let sources = self.project.template_sources().with_context(|| {
+            format!(
+                "Failed to collect the templates of workspace member '{}'",
+                self.name
+            )
+        })?;
+        Ok(sources
+            .into_iter()
+            .filter(|source| source.layer != TemplateLayer::Global)
+            .map(|source| {
+                let namespace = match &source.namespace {
+                    Some(namespace) => EntityId::qualify(&self.name, namespace),
+                    None => self.name.clone(),
+                };
+                TemplateSource {
+                    namespace: Some(namespace),
+                    out: source.out.or_else(|| Some(out.clone())),
+                    ..source
+                }
+            })
+            .collect())
+    }
+}
+
+/// Config of the nearest parent workspace root and the project's directory relative to it.
+/// Parent configs that can't be read or have no `[workspace]` table are skipped.
+fn parent_root(project: &Project) -> Option<(Config, PathBuf)> {
+    if project.is_global() {
+        return None;
+    }
+    let root = canonical(&project.path());
+    root.ancestors().skip(1).find_map(|dir| {
+        let path = Config::join_path(dir);
+        if !path.is_file() {
+            return None;
+        }
+        let config =
+            Config::read_lenient(path, true, project.config.user.profile.as_deref()).ok()?;
+        config.user.workspace.as_ref()?;
+        let relative = root.strip_prefix(dir).ok()?.to_path_buf();
+        Some((config, relative))
+    })
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn load(path: &Path) -> Project {
+        Project::load_with_global_dir(Some(path), None, None).unwrap()
+    }
+
+    #[test]
+    fn aggregates_members_namespaced_by_name() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        write(
+            &Config::join_path(root),
+            "[workspace]\nmembers = [\"packages/*\"]\n",
+        );
+        write(
+            &Config::join_path(root.join("packages/web-app")),
+            "templates = \"./tmpls/*.md\"\nout = \"./src\"\n",
+        );
+        write(&root.join("packages/web-app/tmpls/page.md"), "# Page\n");
+        write(
+            &Config::join_path(root.join("packages/cli")),
+            "templates = \"./tmpls/*.md\"\n",
+        );
+        write(&root.join("packages/cli/tmpls/command.md"), "# Command\n");
+        write(&root.join("packages/docs/readme.md"), "# Not a member\n");
+
+        let project = load(root);
+        let workspace = Workspace::find(&project).unwrap().expect("workspace");
+        let names: Vec<&str> = workspace.members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["cli", "web_app"]);
+
+        let sources = workspace.template_sources().unwrap();
+        let summary: Vec<(Option<&str>, Option<PathBuf>)> = sources
+            .iter()
+            .map(|source| (source.namespace.as_deref(), source.out.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Some("cli"), Some(root.join("packages/cli"))),
+                (
+                    Some("web_app"),
+                    Some(root.join("packages/web-app").join("./src"))
+                ),
+            ]
+        );
+
+        let member = load(&root.join("packages/cli"));
+        let found = Workspace::find(&member).unwrap().expect("parent workspace");
+        assert_eq!(found.member(&member).unwrap().name, "cli");
+
+        let outside = tempfile::tempdir().expect("tempdir");
+        write(&Config::join_path(outside.path()), "templates = []\n");
+        let lone = load(outside.path());
+        assert!(Workspace::find(&lone).unwrap().is_none());
+    }
+
+    #[test]
+    fn errors_on_missing_and_clashing_members() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        let config_path = Config::join_path(root);
+        write(&config_path, "[workspace]\nmembers = [\"packages/*\"]\n");
+        let err = Workspace::load(load(root)).expect_err("no members");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Workspace member 'packages/*' in {} matches no directory with {CONFIG_FILENAME}",
+                config_path.display()
+            )
+        );
+
+        write(
+            &config_path,
+            "[workspace]\nmembers = [\"apps/*\", \"libs/*\"]\n",
+        );
+        write(&Config::join_path(root.join("apps/web-ui")), "");
+        write(&Config::join_path(root.join("libs/web_ui")), "");
+        let err = Workspace::load(load(root)).expect_err("clashing names");
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Workspace members {} and {} share the name 'web_ui'",
+                root.join("apps/web-ui").display(),
+                root.join("libs/web_ui").display()
+            )
+        );
+    }
+
+    #[test]
+    fn membership_skips_broken_parents_and_loads_no_member() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        write(
+            &Config::join_path(root),
+            "[workspace]\nmembers = [\"packages/*\", \"missing/*\"]\n",
+        );
+        write(
+            &Config::join_path(root.join("packages/cli")),
+            "templates = []\n",
+        );
+        let member = load(&root.join("packages/cli"));
+        assert!(Workspace::is_member(&member));
+        assert!(Workspace::find(&member).is_err());
+
+        write(&Config::join_path(root.join("tools")), "templates = []\n");
+        assert!(!Workspace::is_member(&load(&root.join("tools"))));
+
+        write(
+            &Config::join_path(root.join("packages")),
+            "templates = 1 = 2\n",
+        );
+        assert!(Workspace::is_member(&member));
+
+        let outside = tempfile::tempdir().expect("tempdir");
+        let standalone = outside.path().join("app");
+        write(&Config::join_path(outside.path()), "templates = 1 = 2\n");
+        write(&Config::join_path(&standalone), "templates = []\n");
+        let project = load(&standalone);
+        assert!(!Workspace::is_member(&project));
+        assert!(Workspace::find(&project).unwrap().is_none());
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::TemplateRenderer;
+
+/// Wrap a file condition in an `#if` block that renders `true` when the condition holds.
+///
+/// A single name or subexpression is used as is; anything else is a helper call and is
+/// wrapped in parentheses, so `and binary lib` becomes `(and binary lib)`.
+pub fn condition_template(condition: &str) -> String {
+    let condition = condition.trim();
+    let condition = condition
+        .strip_prefix("{{")
+        .and_then(|inner| inner.strip_suffix("}}"))
+        .map(str::trim)
+        .unwrap_or(condition);
+    if condition.starts_with('(') || !condition.contains(char::is_whitespace) {
+        format!("{{{{#if {condition}}}}}true{{{{/if}}}}")
+    } else {
+        format!("{{{{#if ({condition})}}}}true{{{{/if}}}}")
+    }
+}
+
+/// Whether a rendered file path means "skip this file": empty, or only `./`.
+pub fn is_skipped_path(path: &str) -> bool {
+    let path = path.trim();
+    path.is_empty() || path == "." || path == "./"
+}
+
+impl TemplateRenderer {
+    /// Evaluate a file condition against the context using Handlebars truthiness.
+    pub fn condition_holds(
+        &self,
+        template_id: &str,
+        condition: &str,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<bool> {
+        let rendered = self.render_map(
+            &format!("{template_id}::condition"),
+            &condition_template(condition),
+            context,
+        )?;
+        Ok(rendered == "true")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn holds(condition: &str, context: JsonValue) -> bool {
+        TemplateRenderer::new()
+            .condition_holds("t", condition, context.as_object().unwrap())
+            .expect("evaluated")
+    }
+
+    #[test]
+    fn evaluates_names_and_helper_calls() {
+        assert!(holds("binary", json!({"binary": true})));
+        assert!(!holds("binary", json!({"binary": false})));
+        assert!(!holds("binary", json!({})));
+        assert!(holds("{{with_css}}", json!({"with_css": true})));
+        assert!(holds("and binary lib", json!({"binary": true, "lib": 1})));
+        assert!(!holds("(eq kind \"bin\")", json!({"kind": "lib"})));
+        assert!(holds("not binary", json!({"binary": false})));
+    }
+
+    #[test]
+    fn detects_skipped_paths() {
+        assert!(is_skipped_path(""));
+        assert!(is_skipped_path("  ./ "));
+        assert!(!is_skipped_path("./src/main.rs"));
+    }
+}
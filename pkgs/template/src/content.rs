@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use nmcr_types::{OutputFile, OutputFileEncodingBase64, TemplateFile};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::TemplateRenderer;
+
+/// Content of a generated file: text, or bytes of an asset that is not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileContent {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl FileContent {
+    /// Build the output of `file` at the rendered path, base64-encoding binary content.
+    pub fn into_output(self, file: &TemplateFile, path: Option<String>) -> OutputFile {
+        let (content, encoding) = match self {
+            FileContent::Text(text) => (text, None),
+            FileContent::Binary(bytes) => (STANDARD.encode(bytes), Some(OutputFileEncodingBase64)),
+        };
+        OutputFile {
+            path,
+            lang: file.lang.clone(),
+            content,
+            encoding,
+            patch: file.patch.clone(),
+            merge: file.merge.clone(),
+            hooks: None,
+        }
+    }
+}
+
+/// Bytes to write for an output file, decoding base64 content.
+pub fn output_bytes(file: &OutputFile) -> Result<Vec<u8>> {
+    match file.encoding {
+        Some(_) => STANDARD
+            .decode(&file.content)
+            .context("Failed to decode base64 file content"),
+        None => Ok(file.content.clone().into_bytes()),
+    }
+}
+
+impl TemplateRenderer {
+    /// Produce a file's content: raw files are copied verbatim, assets are read from disk
+    /// and everything else is rendered against the context.
+    pub fn render_content(
+        &self,
+        file: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<FileContent> {
+        if let Some(asset) = &file.asset {
+            let bytes = std::fs::read(asset).with_context(|| {
+                format!("Failed to read asset '{}' of template '{}'", asset, file.id)
+            })?;
+            return Ok(match String::from_utf8(bytes) {
+                Ok(text) => FileContent::Text(text),
+                Err(err) => FileContent::Binary(err.into_bytes()),
+            });
+        }
+        if file.raw == Some(true) {
+            return Ok(FileContent::Text(file.content.clone()));
+        }
+        self.render_map(&file.id, &file.content, context)
+            .map(FileContent::Text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmcr_types::{Location, Span, TemplateFileKind, TemplateFileKindFile};
+    use serde_json::json;
+
+    fn template_file(content: &str) -> TemplateFile {
+        TemplateFile {
+            kind: TemplateFileKind::File(TemplateFileKindFile),
+            id: "t".into(),
+            name: "T".into(),
+            description: String::new(),
+            args: Vec::new(),
+            lang: None,
+            content: content.into(),
+            path: None,
+            raw: None,
+            asset: None,
+            condition: None,
+            each: None,
+            patch: None,
+            merge: None,
+            hooks: Vec::new(),
+            location: Location {
+                path: String::new(),
+                span: Span { start: 0, end: 0 },
+            },
+        }
+    }
+
+    fn context() -> JsonMap<String, JsonValue> {
+        json!({"name": "app"}).as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn renders_or_copies_raw_content() {
+        let renderer = TemplateRenderer::new();
+        let rendered = renderer
+            .render_content(&template_file("{{name}}"), &context())
+            .expect("rendered");
+        assert_eq!(rendered, FileContent::Text("app".into()));
+
+        let raw = TemplateFile {
+            raw: Some(true),
+            ..template_file("${{ github.sha }} {{missing}}")
+        };
+        let copied = renderer.render_content(&raw, &context()).expect("copied");
+        assert_eq!(
+            copied,
+            FileContent::Text("${{ github.sha }} {{missing}}".into())
+        );
+    }
+
+    #[test]
+    fn encodes_binary_assets() {
+        let dir = std::env::temp_dir().join(format!("nmcr-asset-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logo.png");
+        std::fs::write(&path, [0x89, b'P', b'N', b'G', 0xff]).unwrap();
+
+        let asset = TemplateFile {
+            asset: Some(path.to_string_lossy().into_owned()),
+            ..template_file("")
+        };
+        let content = TemplateRenderer::new()
+            .render_content(&asset, &context())
+            .expect("read");
+        assert_eq!(
+            content,
+            FileContent::Binary(vec![0x89, b'P', b'N', b'G', 0xff])
+        );
+
+        let output = content.into_output(&asset, Some("logo.png".into()));
+        assert_eq!(output.content, "iVBOR/8=");
+        assert!(output.encoding.is_some());
+        assert_eq!(
+            output_bytes(&output).expect("decoded"),
+            vec![0x89, b'P', b'N', b'G', 0xff]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
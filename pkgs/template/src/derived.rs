@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, bail};
+use nmcr_types::Arg;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{TemplateRenderer, discover_placeholders};
+
+/// An argument whose value is computed from a Handlebars expression over other arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DerivedArg<'a> {
+    pub name: &'a str,
+    pub expression: &'a str,
+}
+
+impl<'a> DerivedArg<'a> {
+    /// Collect the derived args declared in a template's arg list.
+    pub fn from_args(args: &'a [Arg]) -> Vec<Self> {
+        args.iter()
+            .filter_map(|arg| {
+                arg.derived.as_deref().map(|expression| DerivedArg {
+                    name: &arg.name,
+                    expression,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Insert configured default values for the args the context doesn't set.
+pub fn apply_defaults<'a>(
+    context: &mut JsonMap<String, JsonValue>,
+    defaults: &JsonMap<String, JsonValue>,
+    args: impl IntoIterator<Item = &'a Arg>,
+) {
+    for arg in args {
+        if arg.derived.is_none()
+            && !context.contains_key(&arg.name)
+            && let Some(value) = defaults.get(&arg.name)
+        {
+            context.insert(arg.name.clone(), value.clone());
+        }
+    }
+}
+
+impl TemplateRenderer {
+    /// Evaluate derived args in dependency order and insert the results into the context.
+    ///
+    /// Each expression is rendered against the context as it stands, so a derived arg may
+    /// reference both regular args and other derived args. An expression that is just
+    /// `{{other}}` aliases `other`, copying its value as is and skipping it when absent.
+    pub fn apply_derived(
+        &self,
+        template_id: &str,
+        derived: &[DerivedArg<'_>],
+        context: &mut JsonMap<String, JsonValue>,
+    ) -> Result<()> {
+        for idx in derived_order(derived)? {
+            let arg = derived[idx];
+            if let Some(source) = alias_of(arg.expression) {
+                if let Some(value) = context.get(source).cloned() {
+                    context.insert(arg.name.to_string(), value);
+                }
+                continue;
+            }
+            let value = self.render_map(
+                &format!("{template_id}::{}", arg.name),
+                arg.expression,
+                context,
+            )?;
+            context.insert(arg.name.to_string(), JsonValue::String(value));
+        }
+        Ok(())
+    }
+}
+
+fn alias_of(expression: &str) -> Option<&str> {
+    let name = expression
+        .trim()
+        .strip_prefix("{{")?
+        .strip_suffix("}}")?
+        .trim();
+    let is_name = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    is_name.then_some(name)
+}
+
+/// Return the evaluation order of derived args, failing when their references form a cycle.
+pub fn derived_order(derived: &[DerivedArg<'_>]) -> Result<Vec<usize>> {
+    let positions: BTreeMap<&str, usize> = derived
+        .iter()
+        .enumerate()
+        .map(|(idx, arg)| (arg.name, idx))
+        .collect();
+
+    let deps: Vec<Vec<usize>> = derived
+        .iter()
+        .map(|arg| {
+            let mut refs: Vec<usize> = discover_placeholders(arg.expression)
+                .iter()
+                .filter_map(|p| positions.get(p.name.as_str()).copied())
+                .collect();
+            refs.sort_unstable();
+            refs.dedup();
+            refs
+        })
+        .collect();
+
+    let mut state = vec![VisitState::Pending; derived.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    let mut order = Vec::with_capacity(derived.len());
+    for idx in 0..derived.len() {
+        visit(idx, derived, &deps, &mut state, &mut stack, &mut order)?;
+    }
+    Ok(order)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    Pending,
+    Active,
+    Done,
+}
+
+fn visit(
+    idx: usize,
+    derived: &[DerivedArg<'_>],
+    deps: &[Vec<usize>],
+    state: &mut [VisitState],
+    stack: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<()> {
+    match state[idx] {
+        VisitState::Done => return Ok(()),
+        VisitState::Active => {
+            let start = stack.iter().position(|&i| i == idx).unwrap_or(0);
+            let mut cycle: Vec<&str> = stack[start..].iter().map(|&i| derived[i].name).collect();
+            cycle.push(derived[idx].name);
+            bail!("Derived arguments form a cycle: {}", cycle.join(" -> "));
+        }
+        VisitState::Pending => {}
+    }
+
+    state[idx] = VisitState::Active;
+    stack.push(idx);
+    for &dep in &deps[idx] {
+        visit(dep, derived, deps, state, stack, order)?;
+    }
+    stack.pop();
+    state[idx] = VisitState::Done;
+    order.push(idx);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn arg<'a>(name: &'a str, expression: &'a str) -> DerivedArg<'a> {
+        DerivedArg { name, expression }
+    }
+
+    #[test]
+    fn orders_by_dependencies() {
+        let derived = [
+            arg("full", "{{prefix}}-{{base}}"),
+            arg("base", "{{name}}_core"),
+            arg("prefix", "lib"),
+        ];
+        let order = derived_order(&derived).expect("ordered");
+        let names: Vec<_> = order.iter().map(|&i| derived[i].name).collect();
+        assert_eq!(names, vec!["base", "prefix", "full"]);
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let derived = [
+            arg("a", "{{b}}"),
+            arg("b", "{{c.field}}"),
+            arg("c", "{{a}}"),
+        ];
+        let err = derived_order(&derived).expect_err("cycle should fail");
+        assert_eq!(
+            err.to_string(),
+            "Derived arguments form a cycle: a -> b -> c -> a"
+        );
+    }
+
+    #[test]
+    fn applies_derived_values() {
+        let renderer = TemplateRenderer::new();
+        let mut ctx = json!({"name": "app"}).as_object().cloned().unwrap();
+        let derived = [arg("full", "{{base}}!"), arg("base", "{{name}}_core")];
+        renderer
+            .apply_derived("tmpl", &derived, &mut ctx)
+            .expect("applied");
+        assert_eq!(ctx.get("base"), Some(&json!("app_core")));
+        assert_eq!(ctx.get("full"), Some(&json!("app_core!")));
+    }
+
+    #[test]
+    fn aliases_keep_value_types() {
+        let renderer = TemplateRenderer::new();
+        let mut ctx = json!({"flag": false, "tags": ["a"]})
+            .as_object()
+            .cloned()
+            .unwrap();
+        let derived = [
+            arg("enabled", "{{flag}}"),
+            arg("labels", "{{ tags }}"),
+            arg("missing", "{{absent}}"),
+        ];
+        renderer
+            .apply_derived("tmpl", &derived, &mut ctx)
+            .expect("applied");
+        assert_eq!(ctx.get("enabled"), Some(&json!(false)));
+        assert_eq!(ctx.get("labels"), Some(&json!(["a"])));
+        assert_eq!(ctx.get("missing"), None);
+    }
+
+    #[test]
+    fn defaults_fill_missing_args() {
+        let args: Vec<Arg> = ["author", "license", "slug"]
+            .into_iter()
+            .map(|name| Arg {
+                name: name.into(),
+                description: String::new(),
+                kind: nmcr_types::ArgKind::Any(nmcr_types::ArgKindAny),
+                required: true,
+                derived: (name == "slug").then(|| "{{author}}".into()),
+            })
+            .collect();
+        let defaults = json!({"author": "Sasha", "license": "MIT", "slug": "x", "unused": 1});
+        let mut ctx = json!({"license": "ISC"}).as_object().cloned().unwrap();
+        apply_defaults(&mut ctx, defaults.as_object().unwrap(), &args);
+        assert_eq!(
+            JsonValue::Object(ctx),
+            json!({"author": "Sasha", "license": "ISC"})
+        );
+    }
+}
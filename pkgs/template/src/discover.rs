@@ -0,0 +1,797 @@
+//! Placeholder and partial discovery over the parsed Handlebars template.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use handlebars::Path as HbsPath;
+use handlebars::template::{
+    BlockParam, DecoratorTemplate, HelperTemplate, Parameter, Template, TemplateElement,
+    TemplateMapping,
+};
+
+use crate::{RESERVED_KEY, is_nullary_helper};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaceholderOccurrence {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub name: String,
+    /// The shape the template needs from the value.
+    pub shape: PlaceholderShape,
+    /// Every use is an `#if`/`#unless` condition.
+    pub condition_only: bool,
+    /// Every use is a condition or sits in the branch it guards, so the value may be absent.
+    pub guarded: bool,
+    pub occurrences: Vec<PlaceholderOccurrence>,
+}
+
+/// The shape of an input, inferred from how the template uses it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PlaceholderShape {
+    /// Rendered or tested as a single value.
+    #[default]
+    Scalar,
+    /// Iterated with `#each`; holds the shape of each element.
+    Iterable(Box<PlaceholderShape>),
+    /// Accessed by field, with the shape each field needs.
+    Object(BTreeMap<String, PlaceholderShape>),
+}
+
+impl PlaceholderShape {
+    /// Combine two uses of the same input into the shape satisfying both.
+    pub fn merge(self, other: PlaceholderShape) -> PlaceholderShape {
+        use PlaceholderShape::*;
+        match (self, other) {
+            (Scalar, shape) | (shape, Scalar) => shape,
+            (Iterable(a), Iterable(b)) => Iterable(Box::new(a.merge(*b))),
+            (Object(mut a), Object(b)) => {
+                for (field, shape) in b {
+                    let merged = match a.remove(&field) {
+                        Some(existing) => existing.merge(shape),
+                        None => shape,
+                    };
+                    a.insert(field, merged);
+                }
+                Object(a)
+            }
+            // `#each` also iterates objects, so iteration is the stronger requirement
+            (Iterable(a), Object(_)) | (Object(_), Iterable(a)) => Iterable(a),
+        }
+    }
+}
+
+/// A `{{> name}}` (or `{{#> name}}`) partial inclusion found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialReference {
+    pub name: String,
+    /// Hash params passed to the partial, which shadow args inside it.
+    pub params: Vec<String>,
+}
+
+/// Find the top-level inputs a template reads, with the shape each one needs.
+///
+/// Templates that fail to parse yield nothing; the syntax error surfaces when rendering.
+pub fn discover_placeholders(template: &str) -> Vec<Placeholder> {
+    discover_placeholders_with_helpers(template, &BTreeSet::new())
+}
+
+/// Find the inputs like [`discover_placeholders`], treating a bare `{{name}}` of one of the
+/// given script helpers as a helper call.
+pub fn discover_placeholders_with_helpers(
+    template: &str,
+    helpers: &BTreeSet<String>,
+) -> Vec<Placeholder> {
+    let Some(walker) = Walker::run(template, helpers) else {
+        return Vec::new();
+    };
+    walker
+        .found
+        .into_iter()
+        .map(|(name, found)| Placeholder {
+            name,
+            shape: found.shape,
+            condition_only: found.condition_only,
+            guarded: found.guarded,
+            occurrences: found.occurrences,
+        })
+        .collect()
+}
+
+/// Find the partials a template includes, in order of first appearance.
+pub fn discover_partials(template: &str) -> Vec<PartialReference> {
+    Walker::run(template, &BTreeSet::new())
+        .map(|walker| walker.partials)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Field(String),
+    Element,
+}
+
+/// A path from the root context, or `None` when it can't be traced back to an input.
+type Binding = Option<Vec<Step>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Usage {
+    Value,
+    Iterate,
+    Condition,
+}
+
+struct Found {
+    shape: PlaceholderShape,
+    condition_only: bool,
+    guarded: bool,
+    occurrences: Vec<PlaceholderOccurrence>,
+}
+
+struct Frame {
+    context: Binding,
+    /// Whether the block changes the context `../` steps out of.
+    scope: bool,
+    params: BTreeMap<String, Binding>,
+}
+
+struct Walker<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    frames: Vec<Frame>,
+    found: BTreeMap<String, Found>,
+    /// Inputs whose truthiness guards the branch being walked.
+    guards: Vec<String>,
+    partials: Vec<PartialReference>,
+    /// Script helpers, which may be called without params.
+    helpers: &'a BTreeSet<String>,
+}
+
+impl<'a> Walker<'a> {
+    fn run(source: &'a str, helpers: &'a BTreeSet<String>) -> Option<Self> {
+        let template = Template::compile(source).ok()?;
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        let mut walker = Self {
+            source,
+            line_starts,
+            frames: vec![Frame {
+                context: Some(Vec::new()),
+                scope: true,
+                params: BTreeMap::new(),
+            }],
+            found: BTreeMap::new(),
+            guards: Vec::new(),
+            partials: Vec::new(),
+            helpers,
+        };
+        walker.walk(&template);
+        Some(walker)
+    }
+
+    fn walk(&mut self, template: &Template) {
+        for (element, mapping) in template.elements.iter().zip(&template.mapping) {
+            let span = self.tag_span(mapping);
+            match element {
+                TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+                TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                    self.expression(helper, &span)
+                }
+                TemplateElement::HelperBlock(helper) => self.block(helper, &span),
+                TemplateElement::PartialExpression(partial)
+                | TemplateElement::PartialBlock(partial) => self.partial(partial, &span),
+                TemplateElement::DecoratorExpression(decorator)
+                | TemplateElement::DecoratorBlock(decorator) => {
+                    // Only `*inline` ships with Handlebars; its first param names the partial
+                    for param in decorator.params.iter().skip(1) {
+                        self.param(param, Usage::Value, &span);
+                    }
+                    if let Some(body) = &decorator.template {
+                        self.walk(body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, helper: &HelperTemplate, span: &PlaceholderOccurrence) {
+        if helper.params.is_empty() && helper.hash.is_empty() {
+            if let Parameter::Path(path) = &helper.name
+                && !is_nullary_helper(raw_path(path))
+                && !self.helpers.contains(raw_path(path))
+            {
+                self.param(&helper.name, Usage::Value, span);
+            }
+            return;
+        }
+        self.helper_args(helper, span);
+    }
+
+    fn helper_args(&mut self, helper: &HelperTemplate, span: &PlaceholderOccurrence) {
+        for param in &helper.params {
+            self.param(param, Usage::Value, span);
+        }
+        for value in helper.hash.values() {
+            self.param(value, Usage::Value, span);
+        }
+    }
+
+    fn block(&mut self, helper: &HelperTemplate, span: &PlaceholderOccurrence) {
+        let name = match &helper.name {
+            Parameter::Name(name) => name.as_str(),
+            Parameter::Path(path) => raw_path(path),
+            _ => "",
+        };
+        match name {
+            "raw" => {}
+            "each" | "with" => {
+                let usage = if name == "each" {
+                    Usage::Iterate
+                } else {
+                    Usage::Value
+                };
+                let target = helper.params.first().and_then(|param| self.resolve(param));
+                if let Some(param) = helper.params.first() {
+                    self.param(param, usage, span);
+                }
+                for param in helper.params.iter().skip(1) {
+                    self.param(param, Usage::Value, span);
+                }
+                let context = target.map(|mut steps| {
+                    if name == "each" {
+                        steps.push(Step::Element);
+                    }
+                    steps
+                });
+                // `each` binds `|item index|`, `with` binds `|value|`
+                let mut params = BTreeMap::new();
+                if let Some(block_param) = &helper.block_param {
+                    let (value, extra) = block_param_names(block_param);
+                    if let Some(value) = value {
+                        params.insert(value, context.clone());
+                    }
+                    if let Some(extra) = extra {
+                        params.insert(extra, None);
+                    }
+                }
+                self.nested(context, true, params, helper.template.as_ref());
+                if let Some(inverse) = &helper.inverse {
+                    self.walk(inverse);
+                }
+            }
+            _ => {
+                let conditional = name == "if" || name == "unless";
+                let mut guard = None;
+                match helper.params.split_first() {
+                    Some((condition, rest)) if conditional => {
+                        guard = self
+                            .resolve(condition)
+                            .and_then(|steps| match steps.first() {
+                                Some(Step::Field(name)) => Some(name.clone()),
+                                _ => None,
+                            });
+                        self.param(condition, Usage::Condition, span);
+                        for param in rest {
+                            self.param(param, Usage::Value, span);
+                        }
+                        for value in helper.hash.values() {
+                            self.param(value, Usage::Value, span);
+                        }
+                    }
+                    _ => self.helper_args(helper, span),
+                }
+
+                let context = self.frames.last().and_then(|frame| frame.context.clone());
+                let mut params = BTreeMap::new();
+                if let Some(block_param) = &helper.block_param {
+                    let (value, extra) = block_param_names(block_param);
+                    params.extend(value.into_iter().chain(extra).map(|name| (name, None)));
+                }
+                let guards = self.guards.len();
+                // `#if x` renders its block only when `x` is truthy, `#unless x` its inverse
+                if name == "if" {
+                    self.guards.extend(guard.clone());
+                }
+                self.nested(context, false, params, helper.template.as_ref());
+                self.guards.truncate(guards);
+                if let Some(inverse) = &helper.inverse {
+                    if name == "unless" {
+                        self.guards.extend(guard);
+                    }
+                    self.walk(inverse);
+                    self.guards.truncate(guards);
+                }
+            }
+        }
+    }
+
+    fn nested(
+        &mut self,
+        context: Binding,
+        scope: bool,
+        params: BTreeMap<String, Binding>,
+        template: Option<&Template>,
+    ) {
+        let Some(template) = template else {
+            return;
+        };
+        self.frames.push(Frame {
+            context,
+            scope,
+            params,
+        });
+        self.walk(template);
+        self.frames.pop();
+    }
+
+    fn partial(&mut self, partial: &DecoratorTemplate, span: &PlaceholderOccurrence) {
+        let name = match &partial.name {
+            Parameter::Name(name) => Some(name.clone()),
+            Parameter::Path(path) => Some(raw_path(path).to_string()),
+            Parameter::Literal(value) => value.as_str().map(str::to_string),
+            Parameter::Subexpression(_) => None,
+        };
+        if let Some(name) = name {
+            let mut params: Vec<String> = partial.hash.keys().cloned().collect();
+            params.sort();
+            let reference = PartialReference { name, params };
+            if !self.partials.contains(&reference) {
+                self.partials.push(reference);
+            }
+        }
+        for param in &partial.params {
+            self.param(param, Usage::Value, span);
+        }
+        for value in partial.hash.values() {
+            self.param(value, Usage::Value, span);
+        }
+        if let Some(body) = &partial.template {
+            self.walk(body);
+        }
+    }
+
+    fn param(&mut self, param: &Parameter, usage: Usage, span: &PlaceholderOccurrence) {
+        match param {
+            Parameter::Literal(_) => {}
+            Parameter::Subexpression(subexpression) => {
+                if let TemplateElement::Expression(helper) = subexpression.as_element() {
+                    self.expression(helper, span);
+                }
+            }
+            Parameter::Name(_) | Parameter::Path(_) => {
+                if let Some(steps) = self.resolve(param) {
+                    self.record(steps, usage, span);
+                }
+            }
+        }
+    }
+
+    /// Trace a path through block contexts and block params back to the root context.
+    fn resolve(&self, param: &Parameter) -> Binding {
+        let raw = match param {
+            Parameter::Name(name) => name.as_str(),
+            Parameter::Path(HbsPath::Relative((_, raw))) => raw.as_str(),
+            _ => return None,
+        };
+        let reference = PathReference::parse(raw)?;
+
+        let base = if reference.root {
+            Vec::new()
+        } else {
+            if !reference.explicit
+                && reference.up == 0
+                && let Some(first) = reference.segments.first()
+                && let Some(binding) = self
+                    .frames
+                    .iter()
+                    .rev()
+                    .find_map(|frame| frame.params.get(first))
+            {
+                let mut steps = binding.clone()?;
+                steps.extend(
+                    reference.segments[1..]
+                        .iter()
+                        .map(|segment| segment_step(segment)),
+                );
+                return Some(steps);
+            }
+            let scopes: Vec<&Frame> = self.frames.iter().filter(|frame| frame.scope).collect();
+            let idx = scopes.len().saturating_sub(1 + reference.up);
+            // Non-scope frames inherit the context of the scope they sit in
+            let frame = if reference.up == 0 {
+                self.frames.last()
+            } else {
+                scopes.get(idx).copied()
+            };
+            frame?.context.clone()?
+        };
+
+        let mut steps = base;
+        steps.extend(
+            reference
+                .segments
+                .iter()
+                .map(|segment| segment_step(segment)),
+        );
+        Some(steps)
+    }
+
+    fn record(&mut self, steps: Vec<Step>, usage: Usage, span: &PlaceholderOccurrence) {
+        let Some((Step::Field(name), rest)) = steps.split_first() else {
+            return;
+        };
+        if name == RESERVED_KEY {
+            return;
+        }
+        let mut shape = match usage {
+            Usage::Value | Usage::Condition => PlaceholderShape::Scalar,
+            Usage::Iterate => PlaceholderShape::Iterable(Box::default()),
+        };
+        for step in rest.iter().rev() {
+            shape = match step {
+                Step::Field(field) => {
+                    PlaceholderShape::Object(BTreeMap::from([(field.clone(), shape)]))
+                }
+                Step::Element => PlaceholderShape::Iterable(Box::new(shape)),
+            };
+        }
+
+        let guarded = usage == Usage::Condition || self.guards.contains(name);
+        // A condition on a field says nothing about the input itself being a flag
+        let condition = usage == Usage::Condition && rest.is_empty();
+        let found = self.found.entry(name.clone()).or_insert_with(|| Found {
+            shape: PlaceholderShape::Scalar,
+            condition_only: true,
+            guarded: true,
+            occurrences: Vec::new(),
+        });
+        found.shape = std::mem::take(&mut found.shape).merge(shape);
+        found.condition_only &= condition;
+        found.guarded &= guarded;
+        if !found.occurrences.contains(span) {
+            found.occurrences.push(span.clone());
+        }
+    }
+
+    /// Byte range of the tag starting at the mapped position, closing braces included.
+    fn tag_span(&self, mapping: &TemplateMapping) -> PlaceholderOccurrence {
+        let TemplateMapping(line, column) = *mapping;
+        let line_start = self
+            .line_starts
+            .get(line.saturating_sub(1))
+            .copied()
+            .unwrap_or(self.source.len());
+        let start = self.source[line_start..]
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map(|(idx, _)| line_start + idx)
+            .unwrap_or(self.source.len());
+        let end = match self.source[start..].find("}}") {
+            Some(idx) => {
+                let close = start + idx + 2;
+                let extra = self.source[close..]
+                    .bytes()
+                    .take(2)
+                    .take_while(|byte| *byte == b'}')
+                    .count();
+                close + extra
+            }
+            None => self.source.len(),
+        };
+        PlaceholderOccurrence { start, end }
+    }
+}
+
+fn raw_path(path: &HbsPath) -> &str {
+    match path {
+        HbsPath::Relative((_, raw)) | HbsPath::Local((_, _, raw)) => raw,
+    }
+}
+
+fn block_param_names(param: &BlockParam) -> (Option<String>, Option<String>) {
+    let name = |param: &Parameter| match param {
+        Parameter::Name(name) => Some(name.clone()),
+        _ => None,
+    };
+    match param {
+        BlockParam::Single(value) => (name(value), None),
+        BlockParam::Pair((value, extra)) => (name(value), name(extra)),
+    }
+}
+
+fn segment_step(segment: &str) -> Step {
+    if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+        Step::Element
+    } else {
+        Step::Field(segment.to_string())
+    }
+}
+
+/// A relative path split into its `@root`, `../` and `this` prefixes and field segments.
+struct PathReference {
+    root: bool,
+    up: usize,
+    /// Prefixed with `this` or `./`, which bypasses block params.
+    explicit: bool,
+    segments: Vec<String>,
+}
+
+impl PathReference {
+    fn parse(raw: &str) -> Option<Self> {
+        let mut rest = raw.trim();
+        let mut root = false;
+        if let Some(after) = strip_segment(rest, "@root") {
+            root = true;
+            rest = after;
+        } else if rest.starts_with('@') {
+            return None;
+        }
+
+        let mut up = 0;
+        while let Some(after) = rest.strip_prefix("../") {
+            up += 1;
+            rest = after;
+        }
+
+        let mut explicit = false;
+        if let Some(after) = strip_segment(rest, "this").or_else(|| rest.strip_prefix("./")) {
+            explicit = true;
+            rest = after;
+        }
+        if rest == "." {
+            rest = "";
+        }
+
+        let mut segments = Vec::new();
+        let mut current = String::new();
+        let mut bracketed = false;
+        for ch in rest.chars() {
+            match ch {
+                '[' if !bracketed => bracketed = true,
+                ']' if bracketed => bracketed = false,
+                '.' | '/' if !bracketed => segments.push(std::mem::take(&mut current)),
+                _ => current.push(ch),
+            }
+        }
+        segments.push(current);
+        segments.retain(|segment| !segment.is_empty());
+
+        Some(Self {
+            root,
+            up,
+            explicit,
+            segments,
+        })
+    }
+}
+
+/// Strip a leading path segment, requiring a separator or the end of the path after it.
+fn strip_segment<'s>(raw: &'s str, segment: &str) -> Option<&'s str> {
+    let after = raw.strip_prefix(segment)?;
+    if after.is_empty() {
+        Some(after)
+    } else {
+        after.strip_prefix('.').or_else(|| after.strip_prefix('/'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(template: &str) -> Vec<String> {
+        discover_placeholders(template)
+            .into_iter()
+            .map(|p| p.name)
+            .collect()
+    }
+
+    fn shapes(template: &str) -> BTreeMap<String, PlaceholderShape> {
+        discover_placeholders(template)
+            .into_iter()
+            .map(|p| (p.name, p.shape))
+            .collect()
+    }
+
+    fn object(fields: &[(&str, PlaceholderShape)]) -> PlaceholderShape {
+        PlaceholderShape::Object(
+            fields
+                .iter()
+                .map(|(name, shape)| (name.to_string(), shape.clone()))
+                .collect(),
+        )
+    }
+
+    fn iterable(shape: PlaceholderShape) -> PlaceholderShape {
+        PlaceholderShape::Iterable(Box::new(shape))
+    }
+
+    #[test]
+    fn discovers_placeholders() {
+        let found = discover_placeholders("Hello {{ name }} {{#if flag}}{{greeting}}{{/if}}");
+        let names: Vec<_> = found.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["flag", "greeting", "name"]);
+        assert_eq!(
+            found[2].occurrences,
+            vec![PlaceholderOccurrence { start: 6, end: 16 }]
+        );
+    }
+
+    #[test]
+    fn discover_skips_helpers_and_literals() {
+        let tpl = r#"{{now}} {{snake_case name}} {{indent body 4}} {{json value pretty=true}} {{default (pascal_case title) "x"}}"#;
+        assert_eq!(names(tpl), vec!["body", "name", "title", "value"]);
+    }
+
+    #[test]
+    fn discover_keeps_args_named_like_helpers() {
+        let tpl = "{{default}} {{trim}} {{json}} {{len}} {{default title \"x\"}} {{#trim}}{{body}}{{/trim}} {{uuid}}";
+        assert_eq!(
+            names(tpl),
+            vec!["body", "default", "json", "len", "title", "trim"]
+        );
+    }
+
+    #[test]
+    fn discover_skips_script_helpers_called_without_params() {
+        let helpers = BTreeSet::from(["timestamp".to_string()]);
+        let found: Vec<String> =
+            discover_placeholders_with_helpers("{{timestamp}} {{name}}", &helpers)
+                .into_iter()
+                .map(|p| p.name)
+                .collect();
+        assert_eq!(found, vec!["name"]);
+        assert_eq!(names("{{timestamp}}"), vec!["timestamp"]);
+    }
+
+    #[test]
+    fn discover_skips_the_reserved_namespace() {
+        let tpl = "{{_ctx.date}} {{#each items}}{{@root._ctx.git.branch}}{{name}}{{/each}}";
+        assert_eq!(names(tpl), vec!["items"]);
+    }
+
+    #[test]
+    fn discover_skips_alias_tokens() {
+        let tpl = "{{#each items as |item idx|}}{{item.name}} {{idx}}{{/each}}";
+        assert_eq!(
+            shapes(tpl),
+            BTreeMap::from([(
+                "items".to_string(),
+                iterable(object(&[("name", PlaceholderShape::Scalar)]))
+            )])
+        );
+    }
+
+    #[test]
+    fn tracks_block_contexts() {
+        let tpl = concat!(
+            "{{#each modules}}{{name}}{{#each exports}}{{this}} {{../name}} {{@root.crate}}{{/each}}{{/each}}",
+            "{{#with author}}{{name}} <{{email}}>{{/with}}{{#with missing}}{{else}}{{fallback}}{{/with}}",
+        );
+        assert_eq!(
+            shapes(tpl),
+            BTreeMap::from([
+                (
+                    "author".to_string(),
+                    object(&[
+                        ("email", PlaceholderShape::Scalar),
+                        ("name", PlaceholderShape::Scalar)
+                    ])
+                ),
+                ("crate".to_string(), PlaceholderShape::Scalar),
+                ("fallback".to_string(), PlaceholderShape::Scalar),
+                (
+                    "modules".to_string(),
+                    iterable(object(&[
+                        ("exports", iterable(PlaceholderShape::Scalar)),
+                        ("name", PlaceholderShape::Scalar)
+                    ]))
+                ),
+                ("missing".to_string(), PlaceholderShape::Scalar),
+            ])
+        );
+    }
+
+    #[test]
+    fn conditionals_keep_the_context() {
+        let tpl =
+            "{{#each items}}{{#if enabled}}{{label}}{{else}}{{../placeholder}}{{/if}}{{/each}}";
+        assert_eq!(
+            shapes(tpl),
+            BTreeMap::from([
+                (
+                    "items".to_string(),
+                    iterable(object(&[
+                        ("enabled", PlaceholderShape::Scalar),
+                        ("label", PlaceholderShape::Scalar)
+                    ]))
+                ),
+                ("placeholder".to_string(), PlaceholderShape::Scalar),
+            ])
+        );
+    }
+
+    #[test]
+    fn follows_subexpressions_and_paths() {
+        let tpl = "{{default (snake_case pkg.name) fallback}} {{pkg.version}} {{lookup deps key}}";
+        assert_eq!(
+            shapes(tpl),
+            BTreeMap::from([
+                ("deps".to_string(), PlaceholderShape::Scalar),
+                ("fallback".to_string(), PlaceholderShape::Scalar),
+                ("key".to_string(), PlaceholderShape::Scalar),
+                (
+                    "pkg".to_string(),
+                    object(&[
+                        ("name", PlaceholderShape::Scalar),
+                        ("version", PlaceholderShape::Scalar)
+                    ])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn skips_raw_blocks_and_comments() {
+        let tpl = "{{{{raw}}}}{{not_an_arg}}{{{{/raw}}}}{{! {{nor_this}} }}{{arg}}";
+        let found = discover_placeholders(tpl);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "arg");
+        assert_eq!(
+            &tpl[found[0].occurrences[0].start..found[0].occurrences[0].end],
+            "{{arg}}"
+        );
+    }
+
+    #[test]
+    fn tracks_conditions_and_guarded_uses() {
+        let tpl = concat!(
+            "{{#if private}}private{{/if}}",
+            "{{#if license}}License: {{license}}{{/if}}",
+            "{{#unless homepage}}none{{else}}{{homepage}}{{/unless}}",
+            "{{#if author.email}}{{author.name}}{{/if}}",
+            "{{#if name}}{{name}}{{/if}} {{name}}",
+        );
+        let found: BTreeMap<_, _> = discover_placeholders(tpl)
+            .into_iter()
+            .map(|p| (p.name, (p.condition_only, p.guarded)))
+            .collect();
+        assert_eq!(
+            found,
+            BTreeMap::from([
+                ("author".to_string(), (false, true)),
+                ("homepage".to_string(), (false, true)),
+                ("license".to_string(), (false, true)),
+                ("name".to_string(), (false, false)),
+                ("private".to_string(), (true, true)),
+            ])
+        );
+    }
+
+    #[test]
+    fn discovers_partials_and_their_params() {
+        let tpl = "{{> header title=name}}{{#> layout}}body{{/layout}}{{> header title=name}}{{> header}}";
+        let found = discover_partials(tpl);
+        assert_eq!(
+            found,
+            vec![
+                PartialReference {
+                    name: "header".into(),
+                    params: vec!["title".into()],
+                },
+                PartialReference {
+                    name: "layout".into(),
+                    params: vec![],
+                },
+                PartialReference {
+                    name: "header".into(),
+                    params: vec![],
+                },
+            ]
+        );
+        assert_eq!(names(tpl), vec!["name"]);
+    }
+}
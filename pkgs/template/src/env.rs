@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use nmcr_types::Location;
+pub use nmcr_types_internal::{GitEnv, GitUserEnv, ProjectEnv, RenderEnv};
+use serde_json::{Map as JsonMap, Value as JsonValue, json};
+
+use crate::TemplateRenderer;
+
+/// Key of the read-only namespace nmcr adds to every render context.
+pub const RESERVED_KEY: &str = "_ctx";
+
+impl TemplateRenderer {
+    /// Set the output directory exposed as `_ctx.out`.
+    pub fn set_out_dir(&mut self, out: Option<&Path>) {
+        self.env.out = out.map(|out| out.display().to_string());
+    }
+
+    /// Copy of the args with the `_ctx` namespace describing the run and the template with
+    /// the given id and location. Passing `_ctx` as an arg is an error.
+    pub fn with_env(
+        &self,
+        id: &str,
+        location: &Location,
+        args: &JsonMap<String, JsonValue>,
+    ) -> Result<JsonMap<String, JsonValue>> {
+        if args.contains_key(RESERVED_KEY) {
+            bail!("'{RESERVED_KEY}' is reserved for nmcr and can't be passed as an argument");
+        }
+        let mut env = match serde_json::to_value(&self.env)? {
+            JsonValue::Object(env) => env,
+            _ => JsonMap::new(),
+        };
+        env.insert(
+            "date".into(),
+            self.clock.now().format("%Y-%m-%d").to_string().into(),
+        );
+        env.insert("template".into(), json!({ "id": id, "location": location }));
+        let mut context = args.clone();
+        context.insert(RESERVED_KEY.into(), JsonValue::Object(env));
+        Ok(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{FixedClock, RendererOptions};
+    use chrono::{TimeZone, Utc};
+    use nmcr_types::Span;
+
+    #[test]
+    fn exposes_the_run_and_template_under_ctx() {
+        let mut renderer = TemplateRenderer::with_options(RendererOptions {
+            clock: Some(Arc::new(FixedClock(
+                Utc.with_ymd_and_hms(2025, 3, 4, 12, 0, 0).unwrap(),
+            ))),
+            env: RenderEnv {
+                project: Some(ProjectEnv {
+                    root: "/work/app".into(),
+                    name: "app".into(),
+                }),
+                out: None,
+                git: GitEnv {
+                    user: GitUserEnv {
+                        name: Some("Sasha".into()),
+                        email: None,
+                    },
+                    branch: Some("main".into()),
+                },
+            },
+            ..Default::default()
+        });
+        renderer.set_out_dir(Some(Path::new("out")));
+        let location = Location {
+            path: "templates/readme.md".into(),
+            span: Span { start: 0, end: 10 },
+        };
+
+        let mut args = JsonMap::new();
+        args.insert("name".into(), "pkg".into());
+        let context = renderer.with_env("readme", &location, &args).unwrap();
+        let rendered = renderer
+            .render_map(
+                "readme",
+                "{{name}} {{_ctx.project.name}} {{_ctx.out}} {{_ctx.date}} \
+                 {{_ctx.git.user.name}}{{#if _ctx.git.user.email}}!{{/if}} {{_ctx.git.branch}} \
+                 {{_ctx.template.id}} {{_ctx.template.location.path}}",
+                &context,
+            )
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "pkg app out 2025-03-04 Sasha main readme templates/readme.md"
+        );
+
+        let err = renderer
+            .with_env("readme", &location, &context)
+            .expect_err("reserved arg");
+        assert_eq!(
+            err.to_string(),
+            "'_ctx' is reserved for nmcr and can't be passed as an argument"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use nmcr_types::{OutputFile, TemplateFile};
+
+/// Built-in whitespace normalizers, applied before any external formatter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Normalizers {
+    /// Strip spaces and tabs at the end of lines.
+    pub trim_trailing_whitespace: bool,
+    /// Collapse runs of blank lines into a single one.
+    pub collapse_blank_lines: bool,
+    /// End non-empty content with exactly one newline.
+    pub final_newline: bool,
+}
+
+impl Normalizers {
+    pub fn all() -> Self {
+        Self {
+            trim_trailing_whitespace: true,
+            collapse_blank_lines: true,
+            final_newline: true,
+        }
+    }
+
+    /// Normalize content, keeping its line endings.
+    pub fn apply(&self, content: &str) -> String {
+        if *self == Self::default() {
+            return content.to_string();
+        }
+        let newline = if content.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut lines: Vec<&str> = Vec::new();
+        for line in content.lines() {
+            let line = if self.trim_trailing_whitespace {
+                line.trim_end()
+            } else {
+                line.strip_suffix('\r').unwrap_or(line)
+            };
+            let blank = line.trim().is_empty();
+            if self.collapse_blank_lines
+                && blank
+                && lines.last().is_some_and(|l| l.trim().is_empty())
+            {
+                continue;
+            }
+            lines.push(line);
+        }
+        let mut out = lines.join(newline);
+        if self.final_newline {
+            let trimmed = out.trim_end_matches(['\r', '\n']).len();
+            out.truncate(trimmed);
+            if !out.is_empty() {
+                out.push_str(newline);
+            }
+        } else if content.ends_with('\n') {
+            out.push_str(newline);
+        }
+        out
+    }
+}
+
+/// Cleans up generated files: normalizes whitespace, then pipes the content through the
+/// external formatter configured for its language.
+#[derive(Debug, Clone, Default)]
+pub struct OutputFormatter {
+    normalizers: Normalizers,
+    /// Shell commands keyed by lowercase language name.
+    commands: BTreeMap<String, String>,
+}
+
+impl OutputFormatter {
+    pub fn new(normalizers: Normalizers, commands: BTreeMap<String, String>) -> Self {
+        let commands = commands
+            .into_iter()
+            .map(|(lang, command)| (lang.to_ascii_lowercase(), command))
+            .collect();
+        Self {
+            normalizers,
+            commands,
+        }
+    }
+
+    /// Format a rendered file in place. Verbatim files, assets and patches are left as
+    /// rendered: they are copied exactly or are fragments of another file.
+    pub fn format_file(&self, file: &TemplateFile, output: &mut OutputFile) -> Result<()> {
+        if file.raw == Some(true)
+            || file.asset.is_some()
+            || output.encoding.is_some()
+            || output.patch.is_some()
+        {
+            return Ok(());
+        }
+        output.content = self
+            .format(
+                output.lang.as_deref(),
+                output.path.as_deref(),
+                &output.content,
+            )
+            .with_context(|| format!("Failed to format output of template '{}'", file.id))?;
+        Ok(())
+    }
+
+    /// Normalize content and run the formatter configured for `lang`, if any.
+    pub fn format(&self, lang: Option<&str>, path: Option<&str>, content: &str) -> Result<String> {
+        let content = self.normalizers.apply(content);
+        let command = lang.and_then(|lang| self.commands.get(&lang.to_ascii_lowercase()));
+        match command {
+            Some(command) => run_formatter(command, path, &content),
+            None => Ok(content),
+        }
+    }
+}
+
+/// A command that runs `command` through the platform shell.
+pub fn shell_command(command: &str) -> Command {
+    let mut process = if cfg!(windows) {
+        let mut process = Command::new("cmd");
+        process.arg("/C");
+        process
+    } else {
+        let mut process = Command::new("sh");
+        process.arg("-c");
+        process
+    };
+    process.arg(command);
+    process
+}
+
+/// Pipe content through a formatter command, exposing the output path as `NMCR_PATH`.
+fn run_formatter(command: &str, path: Option<&str>, content: &str) -> Result<String> {
+    let mut process = shell_command(command);
+    if let Some(path) = path {
+        process.env("NMCR_PATH", path);
+    }
+    let mut child = process
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run formatter '{command}'"))?;
+    let mut stdin = child.stdin.take().context("Formatter stdin is not piped")?;
+    let input = content.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run formatter '{command}'"))?;
+    let written = writer
+        .join()
+        .map_err(|_| anyhow::anyhow!("Failed to write to formatter '{command}'"))?;
+    // A formatter that exits early closes its stdin, so its status explains the failure
+    if !output.status.success() {
+        bail!(
+            "Formatter '{command}' failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    written.with_context(|| format!("Failed to write to formatter '{command}'"))?;
+    String::from_utf8(output.stdout)
+        .with_context(|| format!("Formatter '{command}' produced invalid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_whitespace() {
+        let content = "fn main() {  \n\n\n    run();\t\n}\n\n";
+        assert_eq!(
+            Normalizers::all().apply(content),
+            "fn main() {\n\n    run();\n}\n"
+        );
+        assert_eq!(
+            Normalizers::all().apply("a \r\n\r\n\r\nb"),
+            "a\r\n\r\nb\r\n"
+        );
+        assert_eq!(Normalizers::default().apply(content), content);
+        assert_eq!(Normalizers::all().apply(""), "");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pipes_content_through_the_language_formatter() {
+        let formatter = OutputFormatter::new(
+            Normalizers::all(),
+            BTreeMap::from([("Text".to_string(), "tr a-z A-Z".to_string())]),
+        );
+        assert_eq!(
+            formatter
+                .format(Some("text"), None, "hello  \n")
+                .expect("formatted"),
+            "HELLO\n"
+        );
+        assert_eq!(
+            formatter
+                .format(Some("md"), None, "hello")
+                .expect("formatted"),
+            "hello\n"
+        );
+
+        let failing = OutputFormatter::new(
+            Normalizers::default(),
+            BTreeMap::from([("text".to_string(), "exit 2".to_string())]),
+        );
+        let err = failing
+            .format(Some("text"), None, "hello")
+            .expect_err("formatter fails");
+        assert!(err.to_string().starts_with("Formatter 'exit 2' failed"));
+    }
+}
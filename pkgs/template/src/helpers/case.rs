@@ -0,0 +1,205 @@
+//! Case conversion and inflection helpers.
+
+use handlebars::{Handlebars, handlebars_helper};
+use heck::{
+    ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToTitleCase, ToTrainCase,
+    ToUpperCamelCase,
+};
+
+handlebars_helper!(snake_case: |value: str| value.to_snake_case());
+handlebars_helper!(kebab_case: |value: str| value.to_kebab_case());
+handlebars_helper!(camel_case: |value: str| value.to_lower_camel_case());
+handlebars_helper!(pascal_case: |value: str| value.to_upper_camel_case());
+handlebars_helper!(constant_case: |value: str| value.to_shouty_snake_case());
+handlebars_helper!(title_case: |value: str| value.to_title_case());
+handlebars_helper!(train_case: |value: str| value.to_train_case());
+handlebars_helper!(lower_case: |value: str| value.to_lowercase());
+handlebars_helper!(upper_case: |value: str| value.to_uppercase());
+handlebars_helper!(plural: |value: str| pluralize(value));
+handlebars_helper!(singular: |value: str| singularize(value));
+
+pub(super) fn register(registry: &mut Handlebars<'static>) {
+    registry.register_helper("snake_case", Box::new(snake_case));
+    registry.register_helper("kebab_case", Box::new(kebab_case));
+    registry.register_helper("camel_case", Box::new(camel_case));
+    registry.register_helper("pascal_case", Box::new(pascal_case));
+    registry.register_helper("constant_case", Box::new(constant_case));
+    registry.register_helper("title_case", Box::new(title_case));
+    registry.register_helper("train_case", Box::new(train_case));
+    registry.register_helper("lower_case", Box::new(lower_case));
+    registry.register_helper("upper_case", Box::new(upper_case));
+    registry.register_helper("plural", Box::new(plural));
+    registry.register_helper("singular", Box::new(singular));
+}
+
+/// Irregular singular/plural pairs, matched against the last word.
+const IRREGULAR: &[(&str, &str)] = &[
+    ("person", "people"),
+    ("child", "children"),
+    ("man", "men"),
+    ("woman", "women"),
+    ("mouse", "mice"),
+    ("goose", "geese"),
+    ("tooth", "teeth"),
+    ("foot", "feet"),
+    ("index", "indices"),
+];
+
+/// Words with the same singular and plural form.
+const UNCOUNTABLE: &[&str] = &[
+    "data",
+    "equipment",
+    "fish",
+    "information",
+    "metadata",
+    "news",
+    "series",
+    "sheep",
+    "species",
+];
+
+/// Pluralize the last word of an English identifier or phrase.
+pub(crate) fn pluralize(value: &str) -> String {
+    let (head, word) = split_last_word(value);
+    if word.is_empty() {
+        return value.to_string();
+    }
+    let lower = word.to_lowercase();
+    if UNCOUNTABLE.contains(&lower.as_str()) {
+        return value.to_string();
+    }
+    if let Some((_, many)) = IRREGULAR.iter().find(|(one, _)| *one == lower) {
+        return format!("{head}{}", match_case(word, many));
+    }
+
+    let inflected = if ends_with_any(&lower, &["s", "x", "z", "ch", "sh"]) {
+        format!("{word}es")
+    } else if lower.ends_with('y') && !ends_with_vowel_y(&lower) {
+        format!("{}ies", &word[..word.len() - 1])
+    } else {
+        format!("{word}s")
+    };
+    format!("{head}{inflected}")
+}
+
+/// Singularize the last word of an English identifier or phrase.
+pub(crate) fn singularize(value: &str) -> String {
+    let (head, word) = split_last_word(value);
+    if word.is_empty() {
+        return value.to_string();
+    }
+    let lower = word.to_lowercase();
+    if UNCOUNTABLE.contains(&lower.as_str()) {
+        return value.to_string();
+    }
+    if let Some((one, _)) = IRREGULAR.iter().find(|(_, many)| *many == lower) {
+        return format!("{head}{}", match_case(word, one));
+    }
+
+    let inflected = if lower.ends_with("ies") && lower.len() > 3 {
+        format!("{}y", &word[..word.len() - 3])
+    } else if ends_with_any(&lower, &["sses", "xes", "zes", "ches", "shes"]) {
+        word[..word.len() - 2].to_string()
+    } else if lower.ends_with('s') && !lower.ends_with("ss") && lower.len() > 1 {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    };
+    format!("{head}{inflected}")
+}
+
+fn split_last_word(value: &str) -> (&str, &str) {
+    let start = value
+        .char_indices()
+        .rev()
+        .find(|(_, ch)| !ch.is_alphanumeric())
+        .map(|(idx, ch)| idx + ch.len_utf8())
+        .unwrap_or(0);
+    // Also split camel humps so `userProfile` inflects `Profile`
+    let word = &value[start..];
+    let hump = word
+        .char_indices()
+        .skip(1)
+        .filter(|(_, ch)| ch.is_uppercase())
+        .map(|(idx, _)| idx)
+        .last()
+        .unwrap_or(0);
+    value.split_at(start + hump)
+}
+
+fn ends_with_any(word: &str, suffixes: &[&str]) -> bool {
+    suffixes.iter().any(|suffix| word.ends_with(suffix))
+}
+
+fn ends_with_vowel_y(word: &str) -> bool {
+    let mut chars = word.chars().rev().skip(1);
+    matches!(chars.next(), Some('a' | 'e' | 'i' | 'o' | 'u'))
+}
+
+fn match_case(original: &str, replacement: &str) -> String {
+    if original.chars().all(|ch| !ch.is_lowercase()) && original.len() > 1 {
+        return replacement.to_uppercase();
+    }
+    let mut chars = replacement.chars();
+    match (original.chars().next(), chars.next()) {
+        (Some(first), Some(rep)) if first.is_uppercase() => {
+            rep.to_uppercase().chain(chars).collect()
+        }
+        _ => replacement.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TemplateRenderer;
+    use serde_json::json;
+
+    #[test]
+    fn converts_cases() {
+        let out = TemplateRenderer::new()
+            .render(
+                "t",
+                "{{snake_case name}} {{kebab_case name}} {{camel_case name}} {{pascal_case name}} {{constant_case name}}",
+                &json!({"name": "user profile"}),
+            )
+            .expect("rendered");
+        assert_eq!(
+            out,
+            "user_profile user-profile userProfile UserProfile USER_PROFILE"
+        );
+    }
+
+    #[test]
+    fn pluralizes_words() {
+        let cases = [
+            ("module", "modules"),
+            ("box", "boxes"),
+            ("category", "categories"),
+            ("key", "keys"),
+            ("person", "people"),
+            ("user_profile", "user_profiles"),
+            ("userProfile", "userProfiles"),
+            ("AdminPerson", "AdminPeople"),
+            ("data", "data"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(pluralize(input), expected, "plural of {input}");
+        }
+    }
+
+    #[test]
+    fn singularizes_words() {
+        let cases = [
+            ("modules", "module"),
+            ("boxes", "box"),
+            ("categories", "category"),
+            ("people", "person"),
+            ("class", "class"),
+            ("user_profiles", "user_profile"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(singularize(input), expected, "singular of {input}");
+        }
+    }
+}
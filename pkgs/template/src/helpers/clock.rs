@@ -0,0 +1,108 @@
+//! The `now` helper and the clock it reads from.
+
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, SecondsFormat, Utc};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use serde_json::Value as JsonValue;
+
+/// Source of the current time for the `now` helper.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// Reads the system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Always returns the same instant; handy for reproducible output in tests.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// `{{now}}` renders an RFC 3339 timestamp; `{{now "%Y-%m-%d"}}` uses a strftime format.
+pub(super) struct NowHelper {
+    clock: Arc<dyn Clock>,
+}
+
+impl NowHelper {
+    pub(super) fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { clock }
+    }
+}
+
+impl HelperDef for NowHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let now = self.clock.now();
+        let rendered = match h.param(0).map(|param| param.value()) {
+            None => now.to_rfc3339_opts(SecondsFormat::Secs, true),
+            Some(JsonValue::String(format)) => {
+                let items: Vec<Item<'_>> = StrftimeItems::new(format).collect();
+                if items.iter().any(|item| matches!(item, Item::Error)) {
+                    return Err(RenderErrorReason::Other(format!(
+                        "Invalid date format '{format}' passed to now"
+                    ))
+                    .into());
+                }
+                now.format_with_items(items.into_iter()).to_string()
+            }
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("string").into()),
+        };
+        Ok(ScopedJson::Derived(JsonValue::String(rendered)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RendererOptions, TemplateRenderer};
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn renderer() -> TemplateRenderer {
+        let clock = FixedClock(Utc.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap());
+        TemplateRenderer::with_options(RendererOptions {
+            clock: Some(Arc::new(clock)),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn renders_fixed_clock() {
+        let renderer = renderer();
+        let out = renderer
+            .render("t", r#"{{now}} {{now "%Y"}}"#, &json!({}))
+            .expect("rendered");
+        assert_eq!(out, "2025-03-04T05:06:07Z 2025");
+    }
+
+    #[test]
+    fn rejects_invalid_formats() {
+        let err = renderer()
+            .render("t", r#"{{now "%Q"}}"#, &json!({}))
+            .expect_err("invalid format");
+        assert!(err.to_string().contains("Invalid date format"));
+    }
+}
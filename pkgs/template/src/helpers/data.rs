@@ -0,0 +1,116 @@
+//! Value serialization helpers: `json`, `toml` and `yaml`.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use nmcr_types_internal::PlainJson;
+use serde_json::Value as JsonValue;
+
+use super::required_param;
+
+pub(super) fn register(registry: &mut Handlebars<'static>) {
+    registry.register_helper("json", Box::new(SerializeHelper(Format::Json)));
+    registry.register_helper("toml", Box::new(SerializeHelper(Format::Toml)));
+    registry.register_helper("yaml", Box::new(SerializeHelper(Format::Yaml)));
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+        }
+    }
+}
+
+/// Serialize a value: `{{json value}}` (compact, or `pretty=true`), `{{toml value}}`, `{{yaml value}}`.
+///
+/// TOML renders objects as documents and anything else as an inline value.
+struct SerializeHelper(Format);
+
+impl HelperDef for SerializeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let format = self.0;
+        let value = required_param(h, format.name(), 0)?.value();
+        let pretty = h
+            .hash_get("pretty")
+            .and_then(|param| param.value().as_bool())
+            .unwrap_or(false);
+        let serialized = serialize(format, value, pretty).map_err(|err| {
+            RenderErrorReason::Other(format!(
+                "Failed to serialize value as {}: {err}",
+                format.name()
+            ))
+        })?;
+        Ok(ScopedJson::Derived(JsonValue::String(serialized)))
+    }
+}
+
+fn serialize(format: Format, value: &JsonValue, pretty: bool) -> Result<String, String> {
+    let plain = PlainJson(value);
+    match format {
+        Format::Json if pretty => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        Format::Json => serde_json::to_string(value).map_err(|e| e.to_string()),
+        Format::Toml if value.is_object() => toml::to_string_pretty(&plain)
+            .map(|out| out.trim_end().to_string())
+            .map_err(|e| e.to_string()),
+        Format::Toml => toml::Value::try_from(plain)
+            .map(|out| out.to_string())
+            .map_err(|e| e.to_string()),
+        Format::Yaml => serde_yml::to_string(&plain)
+            .map(|out| out.trim_end().to_string())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TemplateRenderer;
+    use serde_json::json;
+
+    fn render(template: &str, ctx: serde_json::Value) -> String {
+        TemplateRenderer::new()
+            .render("test", template, &ctx)
+            .expect("rendered")
+    }
+
+    #[test]
+    fn serializes_values() {
+        let ctx = json!({"deps": {"serde": "1"}, "tags": ["a", "b"], "limits": {"jobs": 4, "ratio": 0.5}});
+        assert_eq!(render("{{json tags}}", ctx.clone()), r#"["a","b"]"#);
+        assert_eq!(render("{{toml deps}}", ctx.clone()), r#"serde = "1""#);
+        assert_eq!(render("{{toml tags}}", ctx.clone()), r#"["a", "b"]"#);
+        assert_eq!(
+            render("{{toml limits}}", ctx.clone()),
+            "jobs = 4\nratio = 0.5"
+        );
+        assert_eq!(render("{{yaml limits.jobs}}", ctx.clone()), "4");
+        assert_eq!(render("{{yaml deps}}", ctx), "serde: '1'");
+    }
+
+    #[test]
+    fn reports_unserializable_values() {
+        let err = TemplateRenderer::new()
+            .render("test", "{{toml value}}", &json!({"value": null}))
+            .expect_err("null has no toml form");
+        assert!(
+            err.to_string()
+                .contains("Failed to serialize value as toml")
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Built-in Handlebars helpers registered by every [`TemplateRenderer`](crate::TemplateRenderer).
+
+use handlebars::template::{Parameter, Template, TemplateElement};
+use handlebars::{
+    Handlebars, JsonRender, Path as HbsPath, PathAndJson, RenderError, RenderErrorReason,
+};
+use serde_json::Value as JsonValue;
+
+mod case;
+mod clock;
+pub use clock::*;
+mod data;
+mod script;
+pub(crate) use script::{ScriptHelper, ScriptOrigin};
+pub use script::{ScriptLimits, script_helper_name};
+mod text;
+mod uuid;
+
+use crate::RendererOptions;
+
+/// Helpers that ship with Handlebars itself.
+const HANDLEBARS_HELPERS: &[&str] = &[
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
+
+/// Helpers added by nmcr on top of the Handlebars built-ins.
+const NMCR_HELPERS: &[&str] = &[
+    "snake_case",
+    "kebab_case",
+    "camel_case",
+    "pascal_case",
+    "constant_case",
+    "title_case",
+    "train_case",
+    "lower_case",
+    "upper_case",
+    "plural",
+    "singular",
+    "default",
+    "join",
+    "indent",
+    "trim",
+    "now",
+    "uuid",
+    "json",
+    "toml",
+    "yaml",
+];
+
+/// Built-in helpers that render without params, so a bare `{{now}}` calls the helper.
+const NULLARY_HELPERS: &[&str] = &["now", "uuid"];
+
+/// Check whether a name refers to a helper available in every renderer.
+pub fn is_builtin_helper(name: &str) -> bool {
+    HANDLEBARS_HELPERS.contains(&name) || NMCR_HELPERS.contains(&name)
+}
+
+/// Check whether a bare `{{name}}` calls a built-in helper. Other helpers need params, so
+/// their bare names read the arg of the same name.
+pub fn is_nullary_helper(name: &str) -> bool {
+    NULLARY_HELPERS.contains(&name)
+}
+
+/// Names of every helper available in every renderer.
+pub fn builtin_helpers() -> impl Iterator<Item = &'static str> {
+    HANDLEBARS_HELPERS.iter().chain(NMCR_HELPERS).copied()
+}
+
+pub(crate) fn register(registry: &mut Handlebars<'static>, options: &RendererOptions) {
+    case::register(registry);
+    text::register(registry);
+    data::register(registry);
+    registry.register_helper(
+        "now",
+        Box::new(clock::NowHelper::new(
+            options
+                .clock
+                .clone()
+                .unwrap_or_else(|| std::sync::Arc::new(SystemClock)),
+        )),
+    );
+    registry.register_helper("uuid", Box::new(uuid::UuidHelper::new(options.uuid_seed)));
+}
+
+/// Point bare `{{name}}` expressions at the arg when `name` is a built-in helper that needs
+/// params, as Handlebars would otherwise call the helper without any.
+pub(crate) fn read_bare_args(template: &mut Template) {
+    for element in &mut template.elements {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                let name = match &helper.name {
+                    Parameter::Name(name) => name.as_str(),
+                    Parameter::Path(HbsPath::Relative((_, raw))) => raw.as_str(),
+                    _ => continue,
+                };
+                if helper.params.is_empty()
+                    && helper.hash.is_empty()
+                    && is_builtin_helper(name)
+                    && !is_nullary_helper(name)
+                    && let Ok(path) = HbsPath::parse(&format!("./{name}"))
+                {
+                    helper.name = Parameter::Path(path);
+                }
+            }
+            TemplateElement::HelperBlock(helper) => {
+                for body in [&mut helper.template, &mut helper.inverse]
+                    .into_iter()
+                    .flatten()
+                {
+                    read_bare_args(body);
+                }
+            }
+            TemplateElement::PartialBlock(partial) | TemplateElement::DecoratorBlock(partial) => {
+                if let Some(body) = &mut partial.template {
+                    read_bare_args(body);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Render a helper param as text, the way `{{value}}` would.
+fn param_text(param: &PathAndJson<'_>) -> String {
+    param.value().render()
+}
+
+/// Fetch a required param, honoring strict mode for missing values.
+fn required_param<'a, 'rc>(
+    h: &'a handlebars::Helper<'rc>,
+    helper: &'static str,
+    idx: usize,
+) -> Result<&'a PathAndJson<'rc>, RenderError> {
+    match h.param(idx) {
+        Some(param) if !param.is_value_missing() => Ok(param),
+        Some(param) => Err(RenderError::strict_error(param.relative_path())),
+        None => Err(RenderErrorReason::ParamNotFoundForIndex(helper, idx).into()),
+    }
+}
+
+fn is_blank(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Null => true,
+        JsonValue::String(s) => s.is_empty(),
+        _ => false,
+    }
+}
@@ -0,0 +1,270 @@
+//! Custom helpers written as Rhai scripts.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use nmcr_types_internal::PlainJson;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Position, Scope};
+use serde_json::Value as JsonValue;
+
+/// Execution limits applied to every script helper call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptLimits {
+    /// Maximum number of operations a single call may perform.
+    pub max_operations: u64,
+    /// Maximum function call nesting depth.
+    pub max_call_levels: usize,
+    /// Maximum length of any string, in bytes.
+    pub max_string_size: usize,
+    /// Maximum number of elements in any array or object map.
+    pub max_collection_size: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 100_000,
+            max_call_levels: 32,
+            max_string_size: 1024 * 1024,
+            max_collection_size: 10_000,
+        }
+    }
+}
+
+/// Where a script came from, used to point errors at the right file.
+#[derive(Debug, Clone)]
+pub(crate) enum ScriptOrigin {
+    File(PathBuf),
+    Inline(String),
+}
+
+impl ScriptOrigin {
+    fn locate(&self, position: Position) -> String {
+        let origin = match self {
+            ScriptOrigin::File(path) => path.display().to_string(),
+            ScriptOrigin::Inline(name) => format!("<{name}>"),
+        };
+        match (position.line(), position.position()) {
+            (Some(line), Some(column)) => format!("{origin}:{line}:{column}"),
+            (Some(line), None) => format!("{origin}:{line}"),
+            _ => origin,
+        }
+    }
+}
+
+/// `{{name arg key=value}}` evaluates the script with `params` (array) and `hash` (map) in scope.
+pub(crate) struct ScriptHelper {
+    name: String,
+    engine: Engine,
+    ast: AST,
+    origin: ScriptOrigin,
+}
+
+impl ScriptHelper {
+    pub(crate) fn compile(
+        name: &str,
+        source: &str,
+        origin: ScriptOrigin,
+        limits: ScriptLimits,
+    ) -> Result<Self, String> {
+        let engine = sandboxed_engine(limits);
+        let ast = engine.compile(source).map_err(|err| {
+            format!(
+                "Failed to compile helper script '{name}' at {}: {}",
+                origin.locate(err.1),
+                err.0
+            )
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            engine,
+            ast,
+            origin,
+        })
+    }
+
+    pub(crate) fn from_file(path: &Path, limits: ScriptLimits) -> Result<(String, Self), String> {
+        let name = script_helper_name(path)
+            .ok_or_else(|| format!("Invalid helper script file name: {}", path.display()))?;
+        let source = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read helper script {}: {err}", path.display()))?;
+        let helper = Self::compile(&name, &source, ScriptOrigin::File(path.into()), limits)?;
+        Ok((name, helper))
+    }
+}
+
+/// Name of the helper a script file registers: its file stem.
+pub fn script_helper_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(str::to_string)
+}
+
+impl HelperDef for ScriptHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        if let Some(missing) = h.params().iter().find(|param| param.is_value_missing()) {
+            return Err(RenderError::strict_error(missing.relative_path()));
+        }
+        let params: Vec<PlainJson> = h
+            .params()
+            .iter()
+            .map(|param| PlainJson(param.value()))
+            .collect();
+        let hash: BTreeMap<&str, PlainJson> = h
+            .hash()
+            .iter()
+            .map(|(key, param)| (*key, PlainJson(param.value())))
+            .collect();
+
+        let mut scope = Scope::new();
+        scope.push_dynamic("params", to_dynamic(params).map_err(conversion_error)?);
+        scope.push_dynamic("hash", to_dynamic(hash).map_err(conversion_error)?);
+
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|mut err| {
+                let position = err.take_position();
+                RenderErrorReason::Other(format!(
+                    "Helper '{}' failed at {}: {err}",
+                    self.name,
+                    self.origin.locate(position)
+                ))
+            })?;
+        let value: JsonValue = from_dynamic(&result).map_err(conversion_error)?;
+        Ok(ScopedJson::Derived(value))
+    }
+}
+
+fn conversion_error(err: Box<EvalAltResult>) -> RenderErrorReason {
+    RenderErrorReason::Other(format!("Failed to convert helper script value: {err}"))
+}
+
+/// An engine without module imports, `eval` or stdout output, bounded by the limits.
+fn sandboxed_engine(limits: ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .on_print(|_| {})
+        .on_debug(|_, _, _| {})
+        .set_max_operations(limits.max_operations)
+        .set_max_call_levels(limits.max_call_levels)
+        .set_max_string_size(limits.max_string_size)
+        .set_max_array_size(limits.max_collection_size)
+        .set_max_map_size(limits.max_collection_size);
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RendererOptions, TemplateRenderer};
+    use serde_json::json;
+
+    fn renderer(name: &str, script: &str) -> TemplateRenderer {
+        let mut renderer = TemplateRenderer::new();
+        renderer
+            .register_script_helper(name, script)
+            .expect("script compiles");
+        renderer
+    }
+
+    #[test]
+    fn calls_script_with_params_and_hash() {
+        let renderer = renderer(
+            "module_path",
+            r#"
+                let path = params[0];
+                path.replace(".rs", "");
+                let parts = path.split("/").filter(|part| part != "src");
+                let out = parts.shift();
+                for part in parts {
+                    out += (hash.sep ?? "::") + part;
+                }
+                out
+            "#,
+        );
+        let out = renderer
+            .render(
+                "t",
+                "crate::{{module_path file}} {{module_path file sep=\".\"}}",
+                &json!({"file": "src/api/users.rs"}),
+            )
+            .expect("rendered");
+        assert_eq!(out, "crate::api::users api.users");
+    }
+
+    #[test]
+    fn passes_numbers_to_scripts() {
+        let out = renderer("add", "params[0] + hash.step")
+            .render("t", "{{add jobs step=1.5}}", &json!({"jobs": 4}))
+            .expect("rendered");
+        assert_eq!(out, "5.5");
+    }
+
+    #[test]
+    fn reports_compile_errors_with_location() {
+        let err = TemplateRenderer::new()
+            .register_script_helper("broken", "let x = ;")
+            .expect_err("invalid script");
+        assert!(
+            err.to_string()
+                .contains("Failed to compile helper script 'broken' at <broken>:1:"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn reports_runtime_errors_with_location() {
+        let renderer = renderer("fail", "let a = 1;\nthrow \"boom\";");
+        let err = renderer
+            .render("t", "{{fail 1}}", &json!({}))
+            .expect_err("script throws");
+        assert!(
+            err.to_string()
+                .contains("Helper 'fail' failed at <fail>:2:1"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn enforces_operation_limits() {
+        let mut renderer = TemplateRenderer::with_options(RendererOptions {
+            script_limits: crate::ScriptLimits {
+                max_operations: 1_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        renderer
+            .register_script_helper("spin", "loop { }")
+            .expect("script compiles");
+        let err = renderer
+            .render("t", "{{spin 1}}", &json!({}))
+            .expect_err("loop exceeds limits");
+        assert!(
+            err.to_string().contains("Too many operations"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn rejects_shadowing_builtins() {
+        let err = TemplateRenderer::new()
+            .register_script_helper("snake_case", "params[0]")
+            .expect_err("shadowing builtin");
+        assert!(err.to_string().contains("shadows a built-in helper"));
+    }
+}
@@ -0,0 +1,191 @@
+//! Text shaping helpers: `default`, `join`, `indent` and `trim`.
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, JsonRender, Output, RenderContext,
+    RenderError, RenderErrorReason, Renderable, ScopedJson, StringOutput,
+};
+use serde_json::Value as JsonValue;
+
+use super::{is_blank, param_text, required_param};
+
+pub(super) fn register(registry: &mut Handlebars<'static>) {
+    registry.register_helper("default", Box::new(DefaultHelper));
+    registry.register_helper("join", Box::new(JoinHelper));
+    registry.register_helper("indent", Box::new(IndentHelper));
+    registry.register_helper("trim", Box::new(TrimHelper));
+}
+
+/// `{{default value "fallback"}}` yields the fallback when the value is missing, null or empty.
+struct DefaultHelper;
+
+impl HelperDef for DefaultHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let fallback = h
+            .param(1)
+            .ok_or(RenderErrorReason::ParamNotFoundForIndex("default", 1))?;
+        let value = h
+            .param(0)
+            .filter(|param| !param.is_value_missing() && !is_blank(param.value()));
+        Ok(ScopedJson::Derived(
+            value.unwrap_or(fallback).value().clone(),
+        ))
+    }
+}
+
+/// `{{join items ", "}}` joins array elements; the separator defaults to `", "`.
+struct JoinHelper;
+
+impl HelperDef for JoinHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let items = required_param(h, "join", 0)?;
+        let separator = h.param(1).map(param_text).unwrap_or_else(|| ", ".into());
+        let joined = match items.value() {
+            JsonValue::Array(values) => values
+                .iter()
+                .map(|value| value.render())
+                .collect::<Vec<_>>()
+                .join(&separator),
+            other => other.render(),
+        };
+        Ok(ScopedJson::Derived(JsonValue::String(joined)))
+    }
+}
+
+/// `{{indent text 4}}` or `{{#indent 4}}...{{/indent}}` prefixes every non-empty line.
+struct IndentHelper;
+
+impl HelperDef for IndentHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let (text, width_idx) = match block_content(h, r, ctx, rc)? {
+            Some(text) => (text, 0),
+            None => (param_text(required_param(h, "indent", 0)?), 1),
+        };
+        let prefix = match h.param(width_idx).map(|param| param.value()) {
+            Some(JsonValue::Number(width)) => " ".repeat(width.as_u64().unwrap_or(0) as usize),
+            Some(JsonValue::String(prefix)) => prefix.clone(),
+            Some(_) => return Err(RenderErrorReason::InvalidParamType("number or string").into()),
+            None => "  ".to_string(),
+        };
+        out.write(&indent(&text, &prefix))?;
+        Ok(())
+    }
+}
+
+/// `{{trim value}}` or `{{#trim}}...{{/trim}}` strips surrounding whitespace.
+struct TrimHelper;
+
+impl HelperDef for TrimHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let text = match block_content(h, r, ctx, rc)? {
+            Some(text) => text,
+            None => param_text(required_param(h, "trim", 0)?),
+        };
+        out.write(text.trim())?;
+        Ok(())
+    }
+}
+
+fn block_content<'reg: 'rc, 'rc>(
+    h: &Helper<'rc>,
+    r: &'reg Handlebars<'reg>,
+    ctx: &'rc Context,
+    rc: &mut RenderContext<'reg, 'rc>,
+) -> Result<Option<String>, RenderError> {
+    let Some(template) = h.template() else {
+        return Ok(None);
+    };
+    let mut buffer = StringOutput::new();
+    template.render(r, ctx, rc, &mut buffer)?;
+    Ok(Some(buffer.into_string()?))
+}
+
+fn indent(text: &str, prefix: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("{prefix}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TemplateRenderer;
+    use serde_json::json;
+
+    fn render(template: &str, ctx: serde_json::Value) -> String {
+        TemplateRenderer::new()
+            .render("test", template, &ctx)
+            .expect("rendered")
+    }
+
+    #[test]
+    fn default_falls_back_for_missing_and_empty_values() {
+        let ctx = json!({"empty": "", "set": "value"});
+        assert_eq!(
+            render(
+                r#"{{default missing "a"}} {{default empty "b"}} {{default set "c"}}"#,
+                ctx
+            ),
+            "a b value"
+        );
+    }
+
+    #[test]
+    fn joins_arrays() {
+        let ctx = json!({"items": ["a", "b", 3]});
+        assert_eq!(render("{{join items}}", ctx.clone()), "a, b, 3");
+        assert_eq!(render(r#"{{join items "|"}}"#, ctx), "a|b|3");
+    }
+
+    #[test]
+    fn indents_inline_and_block_content() {
+        let ctx = json!({"body": "a\n\nb"});
+        assert_eq!(render("{{indent body 2}}", ctx.clone()), "  a\n\n  b");
+        assert_eq!(
+            render("{{#indent 4}}x\ny{{/indent}}", ctx.clone()),
+            "    x\n    y"
+        );
+        assert_eq!(render(r#"{{indent body "> "}}"#, ctx), "> a\n\n> b");
+    }
+
+    #[test]
+    fn trims_inline_and_block_content() {
+        let ctx = json!({"name": "  padded  "});
+        assert_eq!(render("[{{trim name}}]", ctx.clone()), "[padded]");
+        assert_eq!(
+            render("[{{#trim}}\n  {{name}}\n{{/trim}}]", ctx),
+            "[padded]"
+        );
+    }
+}
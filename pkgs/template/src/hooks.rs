@@ -0,0 +1,102 @@
+use anyhow::{Context, Result};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{TemplateRenderer, format_render_error};
+
+/// Append commands that are not in `hooks` yet, keeping the order they were declared in.
+pub fn push_hooks(hooks: &mut Vec<String>, commands: impl IntoIterator<Item = String>) {
+    for command in commands {
+        if !hooks.contains(&command) {
+            hooks.push(command);
+        }
+    }
+}
+
+/// Quote a value as a single POSIX shell word, leaving words that need no quoting as is.
+pub fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+impl TemplateRenderer {
+    /// Render the post-generation commands of a template against the context, dropping
+    /// commands that render empty. Every `{{expression}}` is shell-quoted, so an arg value
+    /// stays a single word; `{{{expression}}}` inserts it as is.
+    pub fn render_hooks(
+        &self,
+        template_id: &str,
+        hooks: &[String],
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<Vec<String>> {
+        let mut registry = self.registry.clone();
+        registry.register_escape_fn(shell_quote);
+        let context = JsonValue::Object(context.clone());
+        let mut rendered = Vec::new();
+        for (idx, hook) in hooks.iter().enumerate() {
+            let id = format!("{template_id}::hook{idx}");
+            let command = self
+                .render_compiled(&registry, &id, hook, &context)
+                .map_err(|err| format_render_error(&id, hook, err))
+                .with_context(|| format!("Failed to render hook '{hook}' of '{template_id}'"))?;
+            let command = command.trim();
+            if !command.is_empty() {
+                push_hooks(&mut rendered, [command.to_string()]);
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_hooks_with_args_once_each() {
+        let context = json!({"name": "app", "fmt": false})
+            .as_object()
+            .cloned()
+            .expect("object");
+        let hooks = vec![
+            "cargo new {{name}}".to_string(),
+            "{{#if fmt}}cargo fmt{{/if}}".to_string(),
+            "cargo new {{name}}".to_string(),
+        ];
+        let rendered = TemplateRenderer::new()
+            .render_hooks("crate", &hooks, &context)
+            .expect("rendered");
+        assert_eq!(rendered, vec!["cargo new app".to_string()]);
+
+        let mut all = vec!["git init".to_string()];
+        push_hooks(&mut all, rendered.into_iter().chain(["git init".into()]));
+        assert_eq!(all, vec!["git init", "cargo new app"]);
+    }
+
+    #[test]
+    fn quotes_interpolated_values() {
+        let context = json!({"message": "it's $(rm -rf ~)", "name": "my-app", "empty": ""})
+            .as_object()
+            .cloned()
+            .expect("object");
+        let hooks = vec![
+            "git commit -m {{message}}".to_string(),
+            "cargo new {{name}} {{empty}}".to_string(),
+            "echo {{{name}}}".to_string(),
+        ];
+        let rendered = TemplateRenderer::new()
+            .render_hooks("crate", &hooks, &context)
+            .expect("rendered");
+        assert_eq!(
+            rendered,
+            [
+                r"git commit -m 'it'\''s $(rm -rf ~)'",
+                "cargo new my-app ''",
+                "echo my-app",
+            ]
+        );
+    }
+}
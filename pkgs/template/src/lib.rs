@@ -1,24 +1,138 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
-
-use anyhow::{Context, Result, anyhow};
-use handlebars::{Handlebars, RenderError, no_escape};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, anyhow, bail};
+use handlebars::template::Template;
+use handlebars::{
+    Context as HbsContext, Handlebars, RenderContext, RenderError, Renderable, StringOutput,
+    no_escape,
+};
 use serde::Serialize;
 use serde_json::{Map as JsonMap, Value as JsonValue};
 
+mod condition;
+pub use condition::*;
+mod content;
+pub use content::*;
+mod derived;
+pub use derived::*;
+mod discover;
+pub use discover::*;
+mod env;
+pub use env::*;
+mod format;
+pub use format::*;
+mod helpers;
+pub use helpers::*;
+mod hooks;
+pub use hooks::*;
+mod merge;
+pub use merge::*;
+mod output;
+pub use output::*;
+mod partials;
+pub use partials::*;
+mod patch;
+pub use patch::*;
+mod repeat;
+pub use repeat::*;
+
 /// A reusable Handlebars renderer configured for strict argument handling.
 #[derive(Debug)]
 pub struct TemplateRenderer {
     registry: Handlebars<'static>,
+    script_limits: ScriptLimits,
+    script_helpers: BTreeSet<String>,
+    /// Sources of the registered partials, by name.
+    partials: BTreeMap<String, String>,
+    env: RenderEnv,
+    clock: Arc<dyn Clock>,
+}
+
+/// Configuration for the non-deterministic helpers, mainly to pin output in tests.
+#[derive(Debug, Clone, Default)]
+pub struct RendererOptions {
+    /// Clock read by the `now` helper; the system clock when unset.
+    pub clock: Option<Arc<dyn Clock>>,
+    /// Seed for the `uuid` helper; UUIDs are random when unset.
+    pub uuid_seed: Option<u64>,
+    /// Limits for script helpers registered with this renderer.
+    pub script_limits: ScriptLimits,
+    /// Run details exposed to templates under `_ctx`.
+    pub env: RenderEnv,
 }
 
 impl TemplateRenderer {
     /// Create a new renderer with strict mode enabled.
     pub fn new() -> Self {
+        Self::with_options(RendererOptions::default())
+    }
+
+    /// Create a new renderer with strict mode enabled and custom helper options.
+    pub fn with_options(mut options: RendererOptions) -> Self {
+        let clock = options
+            .clock
+            .get_or_insert_with(|| Arc::new(SystemClock))
+            .clone();
         let mut registry = Handlebars::new();
         registry.set_strict_mode(true);
         registry.register_escape_fn(no_escape);
-        Self { registry }
+        helpers::register(&mut registry, &options);
+        Self {
+            registry,
+            script_limits: options.script_limits,
+            script_helpers: BTreeSet::new(),
+            partials: BTreeMap::new(),
+            env: options.env,
+            clock,
+        }
+    }
+
+    /// Register a partial that templates rendered by this renderer include with `{{> name}}`.
+    /// A partial named `namespace::name` takes precedence over `name` for the templates and
+    /// partials of that namespace.
+    pub fn register_partial(&mut self, name: &str, content: &str) -> Result<()> {
+        Template::compile_with_name(content, name.to_string())
+            .map_err(|err| anyhow!("Failed to compile partial '{name}': {err}"))?;
+        self.partials.insert(name.to_string(), content.to_string());
+        // A new name may change what the includes of the other partials resolve to
+        for (name, content) in &self.partials {
+            let mut partial = Template::compile_with_name(content, name.clone())
+                .map_err(|err| anyhow!("Failed to compile partial '{name}': {err}"))?;
+            helpers::read_bare_args(&mut partial);
+            scope_partials(&mut partial, name, &self.partials);
+            self.registry.register_template(name, partial);
+        }
+        Ok(())
+    }
+
+    /// Register a Rhai script as a helper; it sees the helper params as `params` and `hash`.
+    pub fn register_script_helper(&mut self, name: &str, script: &str) -> Result<()> {
+        let origin = ScriptOrigin::Inline(name.to_string());
+        let helper = ScriptHelper::compile(name, script, origin, self.script_limits)
+            .map_err(|message| anyhow!(message))?;
+        self.add_script_helper(name, helper)
+    }
+
+    /// Register a Rhai script file as a helper named after the file stem.
+    pub fn register_script_helper_file(&mut self, path: &Path) -> Result<()> {
+        let (name, helper) = ScriptHelper::from_file(path, self.script_limits)
+            .map_err(|message| anyhow!(message))?;
+        self.add_script_helper(&name, helper)
+            .with_context(|| format!("Failed to register helper script {}", path.display()))
+    }
+
+    fn add_script_helper(&mut self, name: &str, helper: ScriptHelper) -> Result<()> {
+        if is_builtin_helper(name) {
+            bail!("Helper script '{name}' shadows a built-in helper");
+        }
+        if !self.script_helpers.insert(name.to_string()) {
+            bail!("Duplicate helper script: {name}");
+        }
+        self.registry.register_helper(name, Box::new(helper));
+        Ok(())
     }
 
     /// Render a template string with the provided JSON object context.
@@ -56,10 +170,25 @@ impl TemplateRenderer {
             ));
         }
 
-        self.registry
-            .render_template(template, context)
+        self.render_compiled(&self.registry, template_id, template, context)
             .map_err(|err| format_render_error(template_id, template, err))
     }
+
+    fn render_compiled(
+        &self,
+        registry: &Handlebars<'static>,
+        template_id: &str,
+        template: &str,
+        context: &JsonValue,
+    ) -> Result<String, RenderError> {
+        let mut compiled = Template::compile(template)?;
+        helpers::read_bare_args(&mut compiled);
+        scope_partials(&mut compiled, template_id, &self.partials);
+        let context = HbsContext::wraps(context)?;
+        let mut out = StringOutput::new();
+        compiled.render(registry, &context, &mut RenderContext::new(None), &mut out)?;
+        out.into_string().map_err(RenderError::from)
+    }
 }
 
 impl Default for TemplateRenderer {
@@ -86,15 +215,15 @@ impl<'a> fmt::Display for ContextType<'a> {
 fn format_render_error(template_id: &str, template: &str, err: RenderError) -> anyhow::Error {
     let mut message = format!("Failed to render template '{template_id}': {}", err);
 
-    if let Some(line) = err.line_no {
-        if let Some(snippet) = line_snippet(template, line) {
-            if let Some(column) = err.column_no {
-                message.push_str(&format!("\n --> line {line}, column {column}:"));
-            } else {
-                message.push_str(&format!("\n --> line {line}:"));
-            }
-            message.push_str(&format!("\n     {snippet}"));
+    if let Some(line) = err.line_no
+        && let Some(snippet) = line_snippet(template, line)
+    {
+        if let Some(column) = err.column_no {
+            message.push_str(&format!("\n --> line {line}, column {column}:"));
+        } else {
+            message.push_str(&format!("\n --> line {line}:"));
         }
+        message.push_str(&format!("\n     {snippet}"));
     }
 
     anyhow!(message)
@@ -105,195 +234,6 @@ fn line_snippet(src: &str, line: usize) -> Option<String> {
     src.lines().nth(line_idx).map(|s| s.trim_end().to_string())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct PlaceholderOccurrence {
-    pub start: usize,
-    pub end: usize,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Placeholder {
-    pub name: String,
-    pub occurrences: Vec<PlaceholderOccurrence>,
-}
-
-pub fn discover_placeholders(template: &str) -> Vec<Placeholder> {
-    let mut buckets: BTreeMap<String, Vec<PlaceholderOccurrence>> = BTreeMap::new();
-    for m in scan_template(template) {
-        buckets
-            .entry(m.name)
-            .or_default()
-            .push(PlaceholderOccurrence {
-                start: m.start,
-                end: m.end,
-            });
-    }
-    buckets
-        .into_iter()
-        .map(|(name, occurrences)| Placeholder { name, occurrences })
-        .collect()
-}
-
-#[derive(Debug)]
-struct TokenMatch {
-    name: String,
-    start: usize,
-    end: usize,
-}
-
-fn scan_template(template: &str) -> Vec<TokenMatch> {
-    let mut matches = Vec::new();
-    let mut cursor = 0;
-    let bytes = template.as_bytes();
-    while cursor < bytes.len() {
-        let relative = match template[cursor..].find("{{") {
-            Some(idx) => idx,
-            None => break,
-        };
-        let start = cursor + relative;
-        let mut idx = start + 2;
-        if idx < bytes.len() && bytes[idx] == b'{' {
-            idx += 1;
-        }
-
-        let search_slice = &template[idx..];
-        let close_rel = match search_slice.find("}}") {
-            Some(idx) => idx,
-            None => break,
-        };
-        let end = idx + close_rel;
-        let mut closing_len = 2;
-        if template[end..].starts_with("}}}") {
-            closing_len = 3;
-        }
-
-        cursor = (end + closing_len).min(template.len());
-
-        let trimmed = template[idx..end].trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-
-        let trimmed = trimmed.trim_start_matches('~').trim_end_matches('~').trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-        if trimmed.starts_with('!') {
-            continue;
-        }
-        if trimmed.starts_with('/') {
-            continue;
-        }
-        if trimmed == "else" || trimmed.starts_with("else ") {
-            continue;
-        }
-
-        let parts: Vec<&str> = trimmed.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
-        }
-
-        let first = parts[0];
-        let rest = &parts[1..];
-        let mut collected: Vec<String> = Vec::new();
-        if let Some(first_char) = first.chars().next() {
-            match first_char {
-                '#' | '^' => {
-                    for token in rest {
-                        if let Some(name) = normalize_placeholder_name(token) {
-                            collected.push(name);
-                        }
-                    }
-                }
-                '>' => {
-                    for (idx, token) in rest.iter().enumerate() {
-                        if idx == 0 {
-                            continue;
-                        }
-                        if let Some(name) = normalize_placeholder_name(token) {
-                            collected.push(name);
-                        }
-                    }
-                }
-                '&' => {
-                    if let Some(name) = normalize_placeholder_name(&first[1..]) {
-                        collected.push(name);
-                    }
-                    for token in rest {
-                        if let Some(name) = normalize_placeholder_name(token) {
-                            collected.push(name);
-                        }
-                    }
-                }
-                _ => {
-                    if rest.is_empty() {
-                        if let Some(name) = normalize_placeholder_name(first) {
-                            collected.push(name);
-                        }
-                    } else {
-                        for token in rest {
-                            if let Some(name) = normalize_placeholder_name(token) {
-                                collected.push(name);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        for name in collected {
-            matches.push(TokenMatch {
-                name,
-                start,
-                end: cursor,
-            });
-        }
-    }
-    matches
-}
-
-fn normalize_placeholder_name(token: &str) -> Option<String> {
-    if token.contains('|') {
-        return None;
-    }
-    let mut candidate = token.trim();
-    candidate = candidate
-        .trim_matches(|c: char| matches!(c, '|' | ',' | ')' | '('))
-        .trim();
-
-    while candidate.starts_with("../") {
-        candidate = candidate.trim_start_matches("../");
-    }
-
-    if let Some((before, _)) = candidate.split_once('=') {
-        candidate = before.trim();
-    }
-
-    candidate = candidate.trim_matches('|').trim();
-
-    if candidate.is_empty() || candidate == "." || candidate.eq_ignore_ascii_case("this") {
-        return None;
-    }
-
-    if candidate.starts_with('@') {
-        return None;
-    }
-
-    const RESERVED: &[&str] = &["as", "in", "let"];
-    if RESERVED.iter().any(|kw| candidate.eq_ignore_ascii_case(kw)) {
-        return None;
-    }
-
-    let valid = candidate
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
-    if valid {
-        Some(candidate.to_string())
-    } else {
-        None
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -344,17 +284,55 @@ mod tests {
     }
 
     #[test]
-    fn discovers_placeholders() {
-        let found = discover_placeholders("Hello {{ name }} {{#if flag}}{{greeting}}{{/if}}");
-        let names: Vec<_> = found.iter().map(|p| p.name.as_str()).collect();
-        assert_eq!(names, vec!["flag", "greeting", "name"]);
+    fn renders_args_named_like_helpers() {
+        let mut renderer = TemplateRenderer::new();
+        renderer
+            .register_partial("footer", "{{trim}}")
+            .expect("registered");
+        let rendered = renderer
+            .render(
+                "args",
+                "{{default}} {{#if json}}{{len}}{{/if}} {{trim \" x \"}} {{> footer}}",
+                &json!({"default": "a", "json": true, "len": 2, "trim": "t"}),
+            )
+            .expect("rendered");
+        assert_eq!(rendered, "a 2 x t");
+    }
+
+    #[test]
+    fn renders_registered_partials() {
+        let mut renderer = TemplateRenderer::new();
+        renderer
+            .register_partial("license", "Licensed under {{license}}.")
+            .expect("registered");
+        let rendered = renderer
+            .render(
+                "readme",
+                "# {{name}}\n\n{{> license}}",
+                &json!({"name": "pkg", "license": "MIT"}),
+            )
+            .expect("rendered");
+        assert_eq!(rendered, "# pkg\n\nLicensed under MIT.");
     }
 
     #[test]
-    fn discover_skips_alias_tokens() {
-        let tpl = "{{#each items as |item|}}{{item.name}}{{/each}}";
-        let found = discover_placeholders(tpl);
-        let names: Vec<_> = found.iter().map(|p| p.name.as_str()).collect();
-        assert_eq!(names, vec!["item.name", "items"]);
+    fn namespaced_partials_take_precedence_in_their_namespace() {
+        let mut renderer = TemplateRenderer::new();
+        for (name, content) in [
+            ("web::header", "web {{> footer}}"),
+            ("header", "root {{> footer}}"),
+            ("footer", "root footer"),
+            ("web::footer", "web footer"),
+        ] {
+            renderer
+                .register_partial(name, content)
+                .expect("registered");
+        }
+        let context = json!({});
+        let render = |id: &str| renderer.render(id, "{{> header}}", &context).unwrap();
+        assert_eq!(render("web::readme"), "web web footer");
+        assert_eq!(render("web::acme::readme::path"), "web web footer");
+        assert_eq!(render("cli::readme"), "root root footer");
+        assert_eq!(render("readme"), "root root footer");
     }
 }
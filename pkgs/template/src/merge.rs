@@ -0,0 +1,364 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use nmcr_types::{TemplateMerge, TemplateMergeArrays};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use serde_yml::Value as YamlValue;
+use toml_edit::{DocumentMut, Item, Value as TomlValue};
+
+/// Document formats a generated file can be deep-merged into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl MergeFormat {
+    /// Detect the format from the language hint, falling back to the path extension.
+    pub fn detect(lang: Option<&str>, path: Option<&str>) -> Option<Self> {
+        let extension = path
+            .and_then(|path| Path::new(path).extension())
+            .and_then(|ext| ext.to_str());
+        [lang, extension].into_iter().flatten().find_map(|name| {
+            match name.to_ascii_lowercase().as_str() {
+                "json" => Some(MergeFormat::Json),
+                "toml" => Some(MergeFormat::Toml),
+                "yaml" | "yml" => Some(MergeFormat::Yaml),
+                _ => None,
+            }
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MergeFormat::Json => "JSON",
+            MergeFormat::Toml => "TOML",
+            MergeFormat::Yaml => "YAML",
+        }
+    }
+}
+
+/// Name of a merge's array semantics as written in templates.
+pub fn describe_merge(merge: &TemplateMerge) -> &'static str {
+    match merge.arrays {
+        TemplateMergeArrays::AppendUnique(_) => "append-unique",
+        TemplateMergeArrays::Replace(_) => "replace",
+    }
+}
+
+/// Deep-merge rendered content into an existing document.
+///
+/// Tables and objects merge key by key, scalars take the rendered value and arrays follow
+/// `merge.arrays`. TOML keeps the existing formatting and comments. JSON keeps key order,
+/// numbers as written and the existing indentation, but is re-serialized. YAML keeps key
+/// order but is re-serialized, losing its comments. Returns `None` when the merge changes
+/// nothing.
+pub fn merge_document(
+    format: MergeFormat,
+    existing: &str,
+    rendered: &str,
+    merge: &TemplateMerge,
+) -> Result<Option<String>> {
+    let merged = match format {
+        MergeFormat::Toml => {
+            let mut document: DocumentMut =
+                existing.parse().context("Failed to parse existing TOML")?;
+            let update: DocumentMut = rendered.parse().context("Failed to parse rendered TOML")?;
+            merge_toml(document.as_item_mut(), update.as_item(), &merge.arrays);
+            document.to_string()
+        }
+        MergeFormat::Json => {
+            let mut document: JsonValue =
+                serde_json::from_str(existing).context("Failed to parse existing JSON")?;
+            let update: JsonValue =
+                serde_json::from_str(rendered).context("Failed to parse rendered JSON")?;
+            let original = document.clone();
+            merge_json(&mut document, update, &merge.arrays);
+            if document == original {
+                return Ok(None);
+            }
+            to_json_like(&document, existing)?
+        }
+        MergeFormat::Yaml => {
+            let mut document: YamlValue = serde_yml::from_str(existing)
+                .with_context(|| format!("Failed to parse existing {}", format.name()))?;
+            let update: YamlValue = serde_yml::from_str(rendered)
+                .with_context(|| format!("Failed to parse rendered {}", format.name()))?;
+            let original = document.clone();
+            merge_yaml(&mut document, update, &merge.arrays);
+            if document == original {
+                return Ok(None);
+            }
+            serde_yml::to_string(&document)?
+        }
+    };
+    Ok((merged != existing).then_some(merged))
+}
+
+fn merge_toml(existing: &mut Item, rendered: &Item, arrays: &TemplateMergeArrays) {
+    if let (Some(table), Some(update)) = (existing.as_table_like_mut(), rendered.as_table_like()) {
+        for (key, item) in update.iter() {
+            match table.get_mut(key) {
+                Some(current) => merge_toml(current, item, arrays),
+                None => {
+                    table.insert(key, item.clone());
+                }
+            }
+        }
+        return;
+    }
+
+    match (existing, rendered) {
+        (Item::Value(TomlValue::Array(current)), Item::Value(TomlValue::Array(update))) => {
+            if let TemplateMergeArrays::Replace(_) = arrays {
+                current.clear();
+            }
+            for value in update.iter() {
+                let plain = plain_toml(value);
+                if !current.iter().any(|item| plain_toml(item) == plain) {
+                    current.push(value.clone().decorated("", ""));
+                }
+            }
+            current.fmt();
+        }
+        (Item::ArrayOfTables(current), Item::ArrayOfTables(update)) => {
+            if let TemplateMergeArrays::Replace(_) = arrays {
+                current.clear();
+            }
+            for table in update.iter() {
+                let plain = table.to_string();
+                if !current.iter().any(|item| item.to_string() == plain) {
+                    current.push(table.clone());
+                }
+            }
+        }
+        (Item::Value(current), Item::Value(update)) => {
+            let decor = current.decor().clone();
+            *current = update.clone();
+            *current.decor_mut() = decor;
+        }
+        (current, update) => *current = update.clone(),
+    }
+}
+
+/// A TOML value without its surrounding whitespace and comments, for comparisons.
+fn plain_toml(value: &TomlValue) -> String {
+    value.clone().decorated("", "").to_string()
+}
+
+fn merge_json(existing: &mut JsonValue, rendered: JsonValue, arrays: &TemplateMergeArrays) {
+    match (existing, rendered) {
+        (JsonValue::Object(current), JsonValue::Object(update)) => {
+            for (key, value) in update {
+                match current.get_mut(&key) {
+                    Some(item) => merge_json(item, value, arrays),
+                    None => {
+                        current.insert(key, value);
+                    }
+                }
+            }
+        }
+        (JsonValue::Array(current), JsonValue::Array(update)) => {
+            if let TemplateMergeArrays::Replace(_) = arrays {
+                current.clear();
+            }
+            for value in update {
+                if !current.contains(&value) {
+                    current.push(value);
+                }
+            }
+        }
+        (current, update) => *current = update,
+    }
+}
+
+/// Pretty-print a JSON document with the indentation and final newline of `existing`,
+/// two spaces when it has no indented line.
+fn to_json_like(document: &JsonValue, existing: &str) -> Result<String> {
+    let indent = existing
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    let mut out = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+    document.serialize(&mut serializer)?;
+    let mut out = String::from_utf8(out)?;
+    if existing.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn merge_yaml(existing: &mut YamlValue, rendered: YamlValue, arrays: &TemplateMergeArrays) {
+    match (existing, rendered) {
+        (YamlValue::Mapping(current), YamlValue::Mapping(update)) => {
+            for (key, value) in update {
+                match current.get_mut(&key) {
+                    Some(item) => merge_yaml(item, value, arrays),
+                    None => {
+                        current.insert(key, value);
+                    }
+                }
+            }
+        }
+        (YamlValue::Sequence(current), YamlValue::Sequence(update)) => {
+            if let TemplateMergeArrays::Replace(_) = arrays {
+                current.clear();
+            }
+            for value in update {
+                if !current.contains(&value) {
+                    current.push(value);
+                }
+            }
+        }
+        (current, update) => *current = update,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use nmcr_types::{TemplateMergeArraysAppendUnique, TemplateMergeArraysReplace};
+
+    fn append_unique() -> TemplateMerge {
+        TemplateMerge {
+            arrays: TemplateMergeArrays::AppendUnique(TemplateMergeArraysAppendUnique),
+        }
+    }
+
+    #[test]
+    fn detects_formats_from_lang_or_extension() {
+        assert_eq!(
+            MergeFormat::detect(Some("json"), None),
+            Some(MergeFormat::Json)
+        );
+        assert_eq!(
+            MergeFormat::detect(None, Some("./{{name}}/Cargo.toml")),
+            Some(MergeFormat::Toml)
+        );
+        assert_eq!(
+            MergeFormat::detect(Some("text"), Some("ci.yml")),
+            Some(MergeFormat::Yaml)
+        );
+        assert_eq!(MergeFormat::detect(Some("rust"), Some("lib.rs")), None);
+    }
+
+    #[test]
+    fn merges_toml_keeping_comments() {
+        let existing = indoc! {r#"
+            # Workspace manifest
+            [package]
+            name = "app" # keep me
+            keywords = ["cli"]
+
+            [dependencies]
+            serde = "1"
+        "#};
+        let rendered = indoc! {r#"
+            [package]
+            name = "app"
+            keywords = ["cli", "scaffold"]
+
+            [dependencies]
+            anyhow = "1"
+        "#};
+        let merged = merge_document(MergeFormat::Toml, existing, rendered, &append_unique())
+            .expect("merged")
+            .expect("changed");
+        assert_eq!(
+            merged,
+            indoc! {r#"
+                # Workspace manifest
+                [package]
+                name = "app" # keep me
+                keywords = ["cli", "scaffold"]
+
+                [dependencies]
+                serde = "1"
+                anyhow = "1"
+            "#}
+        );
+        assert_eq!(
+            merge_document(MergeFormat::Toml, &merged, rendered, &append_unique()).expect("merged"),
+            None
+        );
+    }
+
+    #[test]
+    fn merges_json_and_yaml_in_key_order() {
+        let existing = "{\n  \"name\": \"app\",\n  \"scripts\": {\"build\": \"tsc\"},\n  \"files\": [\"dist\"]\n}\n";
+        let rendered = r#"{"scripts": {"test": "vitest"}, "files": ["src"]}"#;
+        let merged = merge_document(MergeFormat::Json, existing, rendered, &append_unique())
+            .expect("merged")
+            .expect("changed");
+        assert_eq!(
+            merged,
+            indoc! {r#"
+                {
+                  "name": "app",
+                  "scripts": {
+                    "build": "tsc",
+                    "test": "vitest"
+                  },
+                  "files": [
+                    "dist",
+                    "src"
+                  ]
+                }
+            "#}
+        );
+
+        let replace = TemplateMerge {
+            arrays: TemplateMergeArrays::Replace(TemplateMergeArraysReplace),
+        };
+        let merged = merge_document(
+            MergeFormat::Yaml,
+            "on: [push]\njobs:\n  test: {}\n",
+            "on: [pull_request]\n",
+            &replace,
+        )
+        .expect("merged")
+        .expect("changed");
+        // serde_yml quotes keys YAML 1.1 reads as booleans, and comments are lost
+        assert_eq!(merged, "'on':\n- pull_request\njobs:\n  test: {}\n");
+    }
+
+    #[test]
+    fn merges_json_keeping_numbers_and_indentation() {
+        let existing =
+            "{\n\t\"weight\": 1e2,\n\t\"id\": 340282366920938463463374607431768211456\n}";
+        let merged = merge_document(
+            MergeFormat::Json,
+            existing,
+            r#"{"ratio": 0.10}"#,
+            &append_unique(),
+        )
+        .expect("merged")
+        .expect("changed");
+        assert_eq!(
+            merged,
+            "{\n\t\"weight\": 1e2,\n\t\"id\": 340282366920938463463374607431768211456,\n\t\"ratio\": 0.10\n}"
+        );
+
+        let existing = "{\n    \"scripts\": {\n        \"build\": \"tsc\"\n    }\n}\n";
+        let merged = merge_document(
+            MergeFormat::Json,
+            existing,
+            r#"{"scripts": {"test": "vitest"}}"#,
+            &append_unique(),
+        )
+        .expect("merged")
+        .expect("changed");
+        assert_eq!(
+            merged,
+            "{\n    \"scripts\": {\n        \"build\": \"tsc\",\n        \"test\": \"vitest\"\n    }\n}\n"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use anyhow::{Context, Result, bail};
+use nmcr_types::{OutputFile, TemplateFile};
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::{DerivedArg, TemplateRenderer, each_contexts, ensure_distinct_paths, is_skipped_path};
+
+/// Fail when the context lacks a required arg of the template; derived args don't count.
+pub fn ensure_required_args(
+    template: &TemplateFile,
+    context: &JsonMap<String, JsonValue>,
+) -> Result<()> {
+    let missing: Vec<String> = template
+        .args
+        .iter()
+        .filter(|arg| arg.required && arg.derived.is_none() && !context.contains_key(&arg.name))
+        .map(|arg| arg.name.clone())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Missing required argument(s) {} for template '{}'.",
+            missing.join(", "),
+            template.id
+        );
+    }
+    Ok(())
+}
+
+impl TemplateRenderer {
+    /// Render a tree file with the run details under `_ctx`: once per element when it
+    /// repeats with `each`, leaving out the renders whose condition is false or whose path
+    /// renders empty.
+    pub fn render_tree_file(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<Vec<OutputFile>> {
+        let context = &self.with_env(&template.id, &template.location, context)?;
+        let Some(each) = &template.each else {
+            return Ok(self
+                .render_conditional_file(template, context)?
+                .into_iter()
+                .collect());
+        };
+
+        let mut rendered = Vec::new();
+        for context in each_contexts(&template.id, each, context)? {
+            rendered.extend(self.render_conditional_file(template, &context)?);
+        }
+        ensure_distinct_paths(
+            &template.id,
+            rendered.iter().filter_map(|file| file.path.as_deref()),
+        )?;
+        Ok(rendered)
+    }
+
+    /// Render a file unless its condition is false or its path renders empty. The args a
+    /// skipped file requires may be missing.
+    pub fn render_conditional_file(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<Option<OutputFile>> {
+        if let Some(condition) = &template.condition {
+            let context = self.derive_args(template, context)?;
+            let holds = self
+                .condition_holds(&template.id, condition, &context)
+                .with_context(|| {
+                    format!(
+                        "Failed to evaluate condition for template '{}'",
+                        template.id
+                    )
+                })?;
+            if !holds {
+                return Ok(None);
+            }
+        }
+
+        ensure_required_args(template, context)?;
+        let context = self.derive_args(template, context)?;
+        let path = self.render_path(template, &context)?;
+        if path.as_deref().is_some_and(is_skipped_path) {
+            return Ok(None);
+        }
+        self.render_output(template, &context, path).map(Some)
+    }
+
+    /// Render a file whatever its condition, as when it is generated on its own.
+    pub fn render_file(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<OutputFile> {
+        ensure_required_args(template, context)?;
+        let context = self.derive_args(template, context)?;
+        let path = self.render_path(template, &context)?;
+        self.render_output(template, &context, path)
+    }
+
+    /// Extend the context with the template's derived args.
+    fn derive_args(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<JsonMap<String, JsonValue>> {
+        let derived = DerivedArg::from_args(&template.args);
+        let mut context = context.clone();
+        self.apply_derived(&template.id, &derived, &mut context)
+            .with_context(|| {
+                format!("Failed to derive arguments for template '{}'", template.id)
+            })?;
+        Ok(context)
+    }
+
+    fn render_path(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+    ) -> Result<Option<String>> {
+        let Some(path) = &template.path else {
+            return Ok(None);
+        };
+        self.render_map(&format!("{}::path", template.id), path, context)
+            .with_context(|| format!("Failed to render path for template '{}'", template.id))
+            .map(Some)
+    }
+
+    /// Render the content and hooks of a file whose path is already rendered.
+    fn render_output(
+        &self,
+        template: &TemplateFile,
+        context: &JsonMap<String, JsonValue>,
+        path: Option<String>,
+    ) -> Result<OutputFile> {
+        let content = self
+            .render_content(template, context)
+            .with_context(|| format!("Failed to render content for template '{}'", template.id))?;
+        let hooks = self
+            .render_hooks(&template.id, &template.hooks, context)
+            .with_context(|| format!("Failed to render hooks for template '{}'", template.id))?;
+        let mut output = content.into_output(template, path);
+        output.hooks = (!hooks.is_empty()).then_some(hooks);
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmcr_types::{
+        Arg, ArgKind, ArgKindString, Location, Span, TemplateFileKind, TemplateFileKindFile,
+    };
+    use serde_json::json;
+
+    fn template_file(path: Option<&str>, content: &str, required: &[&str]) -> TemplateFile {
+        TemplateFile {
+            kind: TemplateFileKind::File(TemplateFileKindFile),
+            id: "greeting".into(),
+            name: "Greeting".into(),
+            description: String::new(),
+            args: required
+                .iter()
+                .map(|name| Arg {
+                    name: name.to_string(),
+                    description: String::new(),
+                    kind: ArgKind::String(ArgKindString),
+                    required: true,
+                    derived: None,
+                })
+                .collect(),
+            lang: None,
+            content: content.into(),
+            path: path.map(str::to_string),
+            raw: None,
+            asset: None,
+            condition: None,
+            each: None,
+            patch: None,
+            merge: None,
+            hooks: Vec::new(),
+            location: Location {
+                path: String::new(),
+                span: Span { start: 0, end: 0 },
+            },
+        }
+    }
+
+    fn context(value: JsonValue) -> JsonMap<String, JsonValue> {
+        value.as_object().cloned().expect("object")
+    }
+
+    #[test]
+    fn renders_files_and_names_missing_variables() {
+        let renderer = TemplateRenderer::new();
+        let file = template_file(None, "Hello, {{name}}!", &[]);
+        let rendered = renderer
+            .render_file(&file, &context(json!({"name": "world"})))
+            .expect("render");
+        assert_eq!(rendered.content, "Hello, world!");
+
+        let err = renderer
+            .render_file(&file, &JsonMap::new())
+            .expect_err("missing args should fail");
+        assert!(format!("{err:#}").contains("greeting"), "{err:#}");
+    }
+
+    #[test]
+    fn skips_false_conditions_and_empty_paths_before_rendering_content() {
+        let renderer = TemplateRenderer::new();
+        let mut file = template_file(
+            Some("{{#if with_css}}./app.css{{/if}}"),
+            "{{theme.missing}}",
+            &[],
+        );
+        let skipped = renderer
+            .render_conditional_file(&file, &context(json!({"with_css": false})))
+            .expect("empty path skips the file");
+        assert_eq!(skipped, None);
+
+        file.path = Some("./app.css".into());
+        file.condition = Some("with_css".into());
+        file.args = template_file(None, "", &["theme"]).args;
+        let skipped = renderer
+            .render_conditional_file(&file, &context(json!({"with_css": false})))
+            .expect("false condition skips the file");
+        assert_eq!(skipped, None);
+        let err = renderer
+            .render_conditional_file(&file, &context(json!({"with_css": true})))
+            .expect_err("required args apply once the condition holds");
+        assert_eq!(
+            err.to_string(),
+            "Missing required argument(s) theme for template 'greeting'."
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+
+use handlebars::template::{Parameter, Template, TemplateElement};
+use nmcr_id::EntityId;
+
+/// Name of the partial that `{{> name}}` includes from the template or partial `scope`:
+/// the one declared in the innermost namespace around `scope`, or `name` itself.
+pub fn resolve_partial(scope: &str, name: &str, exists: impl Fn(&str) -> bool) -> String {
+    EntityId::namespaces(scope)
+        .map(|namespace| EntityId::qualify(namespace, name))
+        .find(|qualified| exists(qualified))
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// Point the partials a template includes at those of its namespaces.
+pub(crate) fn scope_partials(
+    template: &mut Template,
+    scope: &str,
+    partials: &BTreeMap<String, String>,
+) {
+    for element in &mut template.elements {
+        match element {
+            TemplateElement::PartialExpression(partial) => {
+                scope_name(&mut partial.name, scope, partials);
+            }
+            TemplateElement::PartialBlock(partial) => {
+                scope_name(&mut partial.name, scope, partials);
+                if let Some(body) = &mut partial.template {
+                    scope_partials(body, scope, partials);
+                }
+            }
+            TemplateElement::DecoratorBlock(decorator) => {
+                if let Some(body) = &mut decorator.template {
+                    scope_partials(body, scope, partials);
+                }
+            }
+            TemplateElement::HelperBlock(helper) => {
+                for body in [&mut helper.template, &mut helper.inverse]
+                    .into_iter()
+                    .flatten()
+                {
+                    scope_partials(body, scope, partials);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn scope_name(name: &mut Parameter, scope: &str, partials: &BTreeMap<String, String>) {
+    if let Parameter::Name(name) = name {
+        *name = resolve_partial(scope, name, |name| partials.contains_key(name));
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{Context, Result, anyhow, bail};
+use nmcr_types::{TemplatePatch, TemplatePatchOp};
+use regex::Regex;
+
+/// Compile a patch anchor, naming it in the error when it is not a valid regex.
+pub fn anchor_regex(anchor: &str) -> Result<Regex> {
+    Regex::new(anchor).with_context(|| format!("Invalid patch anchor '{anchor}'"))
+}
+
+/// Name of a patch operation as written in templates.
+pub fn patch_op_name(op: &TemplatePatchOp) -> &'static str {
+    match op {
+        TemplatePatchOp::Append(_) => "append",
+        TemplatePatchOp::Prepend(_) => "prepend",
+        TemplatePatchOp::Before(_) => "before",
+        TemplatePatchOp::After(_) => "after",
+        TemplatePatchOp::Between(_) => "between",
+    }
+}
+
+/// Describe a patch the way it is written in templates, e.g. `after ^mod `.
+pub fn describe_patch(patch: &TemplatePatch) -> String {
+    let op = patch_op_name(&patch.op);
+    match (&patch.anchor, &patch.start, &patch.end) {
+        (Some(anchor), _, _) => format!("{op} {anchor}"),
+        (None, Some(start), Some(end)) => format!("{op} {start} and {end}"),
+        _ => op.to_string(),
+    }
+}
+
+/// Apply rendered patch content to an existing file.
+///
+/// Returns `None` when the file already holds the content, so applying a patch twice
+/// changes nothing: inserts are skipped when the content is present anywhere in the file,
+/// and `between` only rewrites what lies between its markers.
+pub fn apply_patch(existing: &str, patch: &TemplatePatch, content: &str) -> Result<Option<String>> {
+    let snippet = with_newline(content);
+    if let TemplatePatchOp::Between(_) = patch.op {
+        return replace_between(existing, patch, &snippet);
+    }
+    let needle = content.trim_end_matches('\n');
+    if needle.trim().is_empty() || existing.contains(needle) {
+        return Ok(None);
+    }
+
+    let patched = match &patch.op {
+        TemplatePatchOp::Append(_) if existing.is_empty() => snippet,
+        TemplatePatchOp::Append(_) => with_newline(existing) + snippet.as_str(),
+        TemplatePatchOp::Prepend(_) => snippet + existing,
+        TemplatePatchOp::Before(_) | TemplatePatchOp::After(_) => {
+            let anchor = patch
+                .anchor
+                .as_deref()
+                .ok_or_else(|| anyhow!("Patch '{}' needs an anchor", patch_op_name(&patch.op)))?;
+            let regex = anchor_regex(anchor)?;
+            let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+            let Some(idx) = lines
+                .iter()
+                .position(|line| regex.is_match(line.trim_end_matches(['\n', '\r'])))
+            else {
+                bail!("Patch anchor '{anchor}' matches no line");
+            };
+            let at = match patch.op {
+                TemplatePatchOp::After(_) => idx + 1,
+                _ => idx,
+            };
+            let mut out: String = lines[..at].concat();
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&snippet);
+            out.push_str(&lines[at..].concat());
+            out
+        }
+        TemplatePatchOp::Between(_) => unreachable!("handled above"),
+    };
+    Ok(Some(patched))
+}
+
+fn replace_between(existing: &str, patch: &TemplatePatch, snippet: &str) -> Result<Option<String>> {
+    let (Some(start), Some(end)) = (patch.start.as_deref(), patch.end.as_deref()) else {
+        bail!("Patch 'between' needs start and end markers");
+    };
+    let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+    let opening = lines.iter().position(|line| line.contains(start));
+    let closing = opening.and_then(|opening| {
+        lines[opening + 1..]
+            .iter()
+            .position(|line| line.contains(end))
+            .map(|offset| opening + 1 + offset)
+    });
+    let (Some(opening), Some(closing)) = (opening, closing) else {
+        bail!("Patch markers '{start}' and '{end}' not found");
+    };
+
+    let mut out: String = lines[..=opening].concat();
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(snippet.trim_start_matches('\n'));
+    out.push_str(&lines[closing..].concat());
+    Ok((out != existing).then_some(out))
+}
+
+fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{text}\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nmcr_types::{
+        TemplatePatchOpAfter, TemplatePatchOpAppend, TemplatePatchOpBefore, TemplatePatchOpBetween,
+    };
+
+    fn patch(op: TemplatePatchOp) -> TemplatePatch {
+        TemplatePatch {
+            op,
+            anchor: None,
+            start: None,
+            end: None,
+        }
+    }
+
+    /// Apply twice, checking the second application is a no-op.
+    fn apply(existing: &str, patch: &TemplatePatch, content: &str) -> String {
+        let patched = apply_patch(existing, patch, content)
+            .expect("applied")
+            .expect("changed");
+        assert_eq!(
+            apply_patch(&patched, patch, content).expect("reapplied"),
+            None
+        );
+        patched
+    }
+
+    #[test]
+    fn appends_and_inserts_around_anchors() {
+        let append = patch(TemplatePatchOp::Append(TemplatePatchOpAppend));
+        assert_eq!(apply("mod a;", &append, "mod b;"), "mod a;\nmod b;\n");
+
+        let after = TemplatePatch {
+            anchor: Some("^mod ".into()),
+            ..patch(TemplatePatchOp::After(TemplatePatchOpAfter))
+        };
+        assert_eq!(
+            apply("//! Crate\nmod a;\n\nfn main() {}\n", &after, "mod b;\n"),
+            "//! Crate\nmod a;\nmod b;\n\nfn main() {}\n"
+        );
+
+        let before = TemplatePatch {
+            anchor: Some(r"^\s*\]".into()),
+            ..patch(TemplatePatchOp::Before(TemplatePatchOpBefore))
+        };
+        assert_eq!(
+            apply("routes = [\n  home,\n]\n", &before, "  users,"),
+            "routes = [\n  home,\n  users,\n]\n"
+        );
+        let err = apply_patch("fn main() {}\n", &before, "x").expect_err("no anchor");
+        assert_eq!(err.to_string(), r"Patch anchor '^\s*\]' matches no line");
+    }
+
+    #[test]
+    fn replaces_between_markers() {
+        let between = TemplatePatch {
+            start: Some("# nmcr:start".into()),
+            end: Some("# nmcr:end".into()),
+            ..patch(TemplatePatchOp::Between(TemplatePatchOpBetween))
+        };
+        assert_eq!(
+            apply("a\n# nmcr:start\nold\n# nmcr:end\nb\n", &between, "new\n"),
+            "a\n# nmcr:start\nnew\n# nmcr:end\nb\n"
+        );
+        let err = apply_patch("a\n# nmcr:end\n", &between, "new").expect_err("no markers");
+        assert_eq!(
+            err.to_string(),
+            "Patch markers '# nmcr:start' and '# nmcr:end' not found"
+        );
+    }
+}
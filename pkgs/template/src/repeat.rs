@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Result, bail};
+use nmcr_types::TemplateEach;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// Build one context per element of the repeated array, in array order, with the element
+/// bound under `each.binding`. An absent or null array repeats the file zero times.
+pub fn each_contexts(
+    template_id: &str,
+    each: &TemplateEach,
+    context: &JsonMap<String, JsonValue>,
+) -> Result<Vec<JsonMap<String, JsonValue>>> {
+    match context.get(&each.arg) {
+        None | Some(JsonValue::Null) => Ok(Vec::new()),
+        Some(JsonValue::Array(items)) => Ok(items
+            .iter()
+            .map(|item| {
+                let mut context = context.clone();
+                context.insert(each.binding.clone(), item.clone());
+                context
+            })
+            .collect()),
+        Some(other) => bail!(
+            "Argument '{}' must be an array to repeat template '{}', got {}",
+            each.arg,
+            template_id,
+            other
+        ),
+    }
+}
+
+/// Fail when several elements of a repeated file render the same path.
+pub fn ensure_distinct_paths<'a>(
+    template_id: &str,
+    paths: impl IntoIterator<Item = &'a str>,
+) -> Result<()> {
+    let mut seen = BTreeSet::new();
+    for path in paths {
+        if !seen.insert(path) {
+            bail!(
+                "Repeated template '{}' renders the path '{}' more than once",
+                template_id,
+                path
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn each() -> TemplateEach {
+        TemplateEach {
+            arg: "modules".into(),
+            binding: "module".into(),
+        }
+    }
+
+    #[test]
+    fn binds_each_element_in_order() {
+        let context = json!({"crate": "app", "modules": ["b", "a"]});
+        let contexts = each_contexts("t", &each(), context.as_object().unwrap()).expect("ok");
+        let bound: Vec<_> = contexts.iter().map(|c| c["module"].clone()).collect();
+        assert_eq!(bound, [json!("b"), json!("a")]);
+        assert_eq!(contexts[0]["crate"], json!("app"));
+
+        let empty = each_contexts("t", &each(), &JsonMap::new()).expect("ok");
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn rejects_non_arrays_and_duplicate_paths() {
+        let context = json!({"modules": "a"});
+        let err = each_contexts("t", &each(), context.as_object().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("must be an array"));
+
+        let err = ensure_distinct_paths("t", ["a.rs", "b.rs", "a.rs"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Repeated template 't' renders the path 'a.rs' more than once"
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use nmcr_types::Location;
+use serde::{Serialize, Serializer};
+use serde_json::Value as JsonValue;
 use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
 pub struct FormattedLocation<'a>(pub &'a Location);
@@ -16,3 +19,93 @@ impl<'a> fmt::Display for FormattedLocation<'a> {
         write!(f, "{}:{}-{}", path, location.span.start, location.span.end)
     }
 }
+
+/// A JSON value to serialize into formats other than JSON, such as TOML, YAML or Rhai.
+///
+/// The workspace enables serde_json's `arbitrary_precision` to keep numbers as written, which
+/// makes a bare number serialize as an internal map; this writes it as an integer or float.
+#[derive(Debug, Clone, Copy)]
+pub struct PlainJson<'a>(pub &'a JsonValue);
+
+impl Serialize for PlainJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            JsonValue::Number(number) => {
+                if let Some(value) = number.as_u64() {
+                    serializer.serialize_u64(value)
+                } else if let Some(value) = number.as_i64() {
+                    serializer.serialize_i64(value)
+                } else {
+                    serializer.serialize_f64(number.as_f64().unwrap_or(f64::NAN))
+                }
+            }
+            JsonValue::Array(items) => serializer.collect_seq(items.iter().map(PlainJson)),
+            JsonValue::Object(map) => {
+                serializer.collect_map(map.iter().map(|(key, value)| (key, PlainJson(value))))
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+/// Where templates come from, ordered by precedence: a template or tree member from a
+/// higher layer replaces the one with the same id from a lower layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TemplateLayer {
+    /// The user-global template library.
+    Global,
+    /// Sources declared in the project config.
+    Source,
+    /// Templates matched by the project's own glob.
+    Local,
+}
+
+impl fmt::Display for TemplateLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TemplateLayer::Global => "global",
+            TemplateLayer::Source => "source",
+            TemplateLayer::Local => "local",
+        })
+    }
+}
+
+/// Template files of one layer, sharing a namespace.
+#[derive(Debug, Clone)]
+pub struct TemplateSource {
+    pub layer: TemplateLayer,
+    /// Namespace prefixing the ids of the templates.
+    pub namespace: Option<String>,
+    pub paths: Vec<PathBuf>,
+    /// Default output directory of the templates.
+    pub out: Option<PathBuf>,
+}
+
+/// Details of the run that templates read from the reserved `_ctx` namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RenderEnv {
+    pub project: Option<ProjectEnv>,
+    /// Directory the output is written to, unset when printing or serving.
+    pub out: Option<String>,
+    pub git: GitEnv,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProjectEnv {
+    pub root: String,
+    /// Name of the project root directory.
+    pub name: String,
+}
+
+/// Values read from the local git config and checkout; unset ones are `null`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GitEnv {
+    pub user: GitUserEnv,
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GitUserEnv {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
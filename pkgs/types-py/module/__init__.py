@@ -2,7 +2,7 @@ from .arg import ArgKind, Arg
 from .location import Location
 from .output import OutputFile, OutputTree, Output
 from .span import Span
-from .template import TemplateFile, TemplateTree, Template, TemplateCollection
+from .template import TemplateFile, TemplateEach, TemplatePatchOp, TemplatePatch, TemplateMergeArrays, TemplateMerge, TemplateTree, TemplateInclude, Template, TemplatePartial, TemplateCollection
 
 
-__all__ = ["ArgKind", "Arg", "Location", "OutputFile", "OutputTree", "Output", "Span", "TemplateFile", "TemplateTree", "Template", "TemplateCollection"]
\ No newline at end of file
+__all__ = ["ArgKind", "Arg", "Location", "OutputFile", "OutputTree", "Output", "Span", "TemplateFile", "TemplateEach", "TemplatePatchOp", "TemplatePatch", "TemplateMergeArrays", "TemplateMerge", "TemplateTree", "TemplateInclude", "Template", "TemplatePartial", "TemplateCollection"]
\ No newline at end of file
@@ -1,8 +1,8 @@
 from genotype import Model
-from typing import Literal
+from typing import Literal, Optional
 
 
-type ArgKind = Literal["any"] | Literal["boolean"] | Literal["string"] | Literal["number"]
+type ArgKind = Literal["any"] | Literal["boolean"] | Literal["string"] | Literal["number"] | Literal["array"] | Literal["object"]
 
 
 class Arg(Model):
@@ -10,3 +10,5 @@ class Arg(Model):
     description: str
     kind: ArgKind
     required: bool
+    derived: Optional[str] = None
+    """Handlebars expression computing the value from other arguments."""
@@ -1,7 +1,8 @@
 """Generation output shared by CLI and MCP."""
 
 
-from typing import Optional
+from typing import Literal, Optional
+from .template import TemplateMerge, TemplatePatch
 from genotype import Model
 
 
@@ -12,10 +13,20 @@ class OutputFile(Model):
     """Optional language hint carried through for consumers."""
     content: str
     """Rendered file content."""
+    encoding: Optional[Literal["base64"]] = None
+    """Set when content is base64-encoded binary data."""
+    patch: Optional[TemplatePatch] = None
+    """Set when the content patches the existing file at `path` instead of replacing it."""
+    merge: Optional[TemplateMerge] = None
+    """Set when the content is deep-merged into the existing document at `path`."""
+    hooks: Optional[list[str]] = None
+    """Rendered post-generation commands to run in the output directory."""
 
 
 class OutputTree(Model):
     files: list[OutputFile]
+    hooks: Optional[list[str]] = None
+    """Rendered post-generation commands to run in the output directory."""
 
 
 type Output = OutputTree | OutputFile
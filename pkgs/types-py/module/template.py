@@ -10,8 +10,8 @@ from genotype import Model
 class TemplateFile(Model):
     """A single-file template node."""
 
-    kind: Literal["file"]
-    """Discriminator for unions."""
+    kind: Literal["file"] | Literal["patch"]
+    """Discriminator for unions; patches modify an existing file instead of writing one."""
     id: str
     name: str
     description: str
@@ -22,9 +22,57 @@ class TemplateFile(Model):
     """Raw template content."""
     path: Optional[str] = None
     """Optional relative path to use when writing to disk."""
+    raw: Optional[bool] = None
+    """Content is written verbatim, without Handlebars rendering."""
+    asset: Optional[str] = None
+    """Path of a file copied byte-for-byte instead of the content."""
+    condition: Optional[str] = None
+    """Handlebars expression; in trees the file is only rendered when it is truthy."""
+    each: Optional[TemplateEach] = None
+    """Repeats the file once per element of an array arg when rendered in a tree."""
+    patch: Optional[TemplatePatch] = None
+    """How a patch template applies its content to the existing file."""
+    merge: Optional[TemplateMerge] = None
+    """Deep-merges the content into an existing JSON, TOML or YAML file."""
+    hooks: list[str]
+    """Handlebars shell commands run in the output directory after generation."""
     location: Location
 
 
+class TemplateEach(Model):
+    """Repetition of a file over the elements of an array arg."""
+
+    arg: str
+    """Name of the array arg."""
+    binding: str
+    """Name the current element is bound to in the path and content."""
+
+
+type TemplatePatchOp = Literal["append"] | Literal["prepend"] | Literal["before"] | Literal["after"] | Literal["between"]
+
+
+class TemplatePatch(Model):
+    """An idempotent modification of an existing file."""
+
+    op: TemplatePatchOp
+    anchor: Optional[str] = None
+    """Regex matching the line the content is inserted before or after."""
+    start: Optional[str] = None
+    """Marker line opening the region replaced by `between`."""
+    end: Optional[str] = None
+    """Marker line closing the region replaced by `between`."""
+
+
+type TemplateMergeArrays = Literal["append-unique"] | Literal["replace"]
+
+
+class TemplateMerge(Model):
+    """A deep merge of a generated document into the existing one."""
+
+    arrays: TemplateMergeArrays
+    """How arrays present in both documents are combined."""
+
+
 class TemplateTree(Model):
     """A tree of template files grouped under a single heading."""
 
@@ -35,6 +83,22 @@ class TemplateTree(Model):
     description: str
     """Prose between the tree heading and the first file."""
     files: list[Template]
+    includes: list[TemplateInclude]
+    """Other catalog templates whose files are added to the tree."""
+    hooks: list[str]
+    """Handlebars shell commands run in the output directory after generation."""
+    location: Location
+
+
+class TemplateInclude(Model):
+    """A reference from a tree to another catalog template, resolved by the catalog."""
+
+    id: str
+    """Id of the included file or tree template."""
+    path: Optional[str] = None
+    """Directory the included files are placed under, relative to the tree root."""
+    args: dict[str, str]
+    """Included template args mapped to the including tree args they take values from."""
     location: Location
 
 
@@ -42,10 +106,22 @@ type Template = TemplateTree | TemplateFile
 """Union of templates."""
 
 
+class TemplatePartial(Model):
+    """A reusable Handlebars partial, included from templates with `{{> name}}`."""
+
+    name: str
+    description: str
+    content: str
+    """Raw partial content."""
+    location: Location
+
+
 class TemplateCollection(Model):
     """A collection of top-level templates parsed from a single markdown file."""
 
     name: str
     description: str
     templates: list[Template]
+    partials: list[TemplatePartial]
+    """Partials declared alongside the templates."""
     location: Location
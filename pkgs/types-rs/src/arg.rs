@@ -8,6 +8,9 @@ pub struct Arg {
     pub kind: ArgKind,
     #[serde(default = "default_required")]
     pub required: bool,
+    /// Handlebars expression computing the value from other arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derived: Option<String>,
 }
 
 fn default_required() -> bool {
@@ -21,6 +24,8 @@ pub enum ArgKind {
     Boolean(ArgKindBoolean),
     String(ArgKindString),
     Number(ArgKindNumber),
+    Array(ArgKindArray),
+    Object(ArgKindObject),
 }
 
 #[literal("any")]
@@ -34,3 +39,9 @@ pub struct ArgKindString;
 
 #[literal("number")]
 pub struct ArgKindNumber;
+
+#[literal("array")]
+pub struct ArgKindArray;
+
+#[literal("object")]
+pub struct ArgKindObject;
@@ -1,5 +1,6 @@
 //! Generation output shared by CLI and MCP.
 
+use litty::literal;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -12,11 +13,29 @@ pub struct OutputFile {
     pub lang: Option<String>,
     /// Rendered file content.
     pub content: String,
+    /// Set when content is base64-encoded binary data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<OutputFileEncodingBase64>,
+    /// Set when the content patches the existing file at `path` instead of replacing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<super::template::TemplatePatch>,
+    /// Set when the content is deep-merged into the existing document at `path`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge: Option<super::template::TemplateMerge>,
+    /// Rendered post-generation commands to run in the output directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Vec<String>>,
 }
 
+#[literal("base64")]
+pub struct OutputFileEncodingBase64;
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OutputTree {
     pub files: Vec<OutputFile>,
+    /// Rendered post-generation commands to run in the output directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
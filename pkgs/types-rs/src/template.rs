@@ -2,12 +2,13 @@
 
 use litty::literal;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// A single-file template node.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TemplateFile {
-    /// Discriminator for unions.
-    pub kind: TemplateFileKindFile,
+    /// Discriminator for unions; patches modify an existing file instead of writing one.
+    pub kind: TemplateFileKind,
     pub id: String,
     pub name: String,
     pub description: String,
@@ -20,12 +21,112 @@ pub struct TemplateFile {
     /// Optional relative path to use when writing to disk.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
+    /// Content is written verbatim, without Handlebars rendering.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<bool>,
+    /// Path of a file copied byte-for-byte instead of the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// Handlebars expression; in trees the file is only rendered when it is truthy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// Repeats the file once per element of an array arg when rendered in a tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub each: Option<TemplateEach>,
+    /// How a patch template applies its content to the existing file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<TemplatePatch>,
+    /// Deep-merges the content into an existing JSON, TOML or YAML file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge: Option<TemplateMerge>,
+    /// Handlebars shell commands run in the output directory after generation.
+    #[serde(default)]
+    pub hooks: Vec<String>,
     pub location: super::location::Location,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplateFileKind {
+    File(TemplateFileKindFile),
+    Patch(TemplateFileKindPatch),
+}
+
 #[literal("file")]
 pub struct TemplateFileKindFile;
 
+#[literal("patch")]
+pub struct TemplateFileKindPatch;
+
+/// Repetition of a file over the elements of an array arg.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateEach {
+    /// Name of the array arg.
+    pub arg: String,
+    /// Name the current element is bound to in the path and content.
+    pub binding: String,
+}
+
+/// An idempotent modification of an existing file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplatePatch {
+    pub op: TemplatePatchOp,
+    /// Regex matching the line the content is inserted before or after.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+    /// Marker line opening the region replaced by `between`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// Marker line closing the region replaced by `between`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplatePatchOp {
+    Append(TemplatePatchOpAppend),
+    Prepend(TemplatePatchOpPrepend),
+    Before(TemplatePatchOpBefore),
+    After(TemplatePatchOpAfter),
+    Between(TemplatePatchOpBetween),
+}
+
+#[literal("append")]
+pub struct TemplatePatchOpAppend;
+
+#[literal("prepend")]
+pub struct TemplatePatchOpPrepend;
+
+#[literal("before")]
+pub struct TemplatePatchOpBefore;
+
+#[literal("after")]
+pub struct TemplatePatchOpAfter;
+
+#[literal("between")]
+pub struct TemplatePatchOpBetween;
+
+/// A deep merge of a generated document into the existing one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateMerge {
+    /// How arrays present in both documents are combined.
+    pub arrays: TemplateMergeArrays,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplateMergeArrays {
+    AppendUnique(TemplateMergeArraysAppendUnique),
+    Replace(TemplateMergeArraysReplace),
+}
+
+#[literal("append-unique")]
+pub struct TemplateMergeArraysAppendUnique;
+
+#[literal("replace")]
+pub struct TemplateMergeArraysReplace;
+
 /// A tree of template files grouped under a single heading.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TemplateTree {
@@ -36,12 +137,31 @@ pub struct TemplateTree {
     /// Prose between the tree heading and the first file.
     pub description: String,
     pub files: Vec<Template>,
+    /// Other catalog templates whose files are added to the tree.
+    #[serde(default)]
+    pub includes: Vec<TemplateInclude>,
+    /// Handlebars shell commands run in the output directory after generation.
+    #[serde(default)]
+    pub hooks: Vec<String>,
     pub location: super::location::Location,
 }
 
 #[literal("tree")]
 pub struct TemplateTreeKindTree;
 
+/// A reference from a tree to another catalog template, resolved by the catalog.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateInclude {
+    /// Id of the included file or tree template.
+    pub id: String,
+    /// Directory the included files are placed under, relative to the tree root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Included template args mapped to the including tree args they take values from.
+    pub args: BTreeMap<String, String>,
+    pub location: super::location::Location,
+}
+
 /// Union of templates.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -50,11 +170,24 @@ pub enum Template {
     TemplateFile(TemplateFile),
 }
 
+/// A reusable Handlebars partial, included from templates with `{{> name}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplatePartial {
+    pub name: String,
+    pub description: String,
+    /// Raw partial content.
+    pub content: String,
+    pub location: super::location::Location,
+}
+
 /// A collection of top-level templates parsed from a single markdown file.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TemplateCollection {
     pub name: String,
     pub description: String,
     pub templates: Vec<Template>,
+    /// Partials declared alongside the templates.
+    #[serde(default)]
+    pub partials: Vec<TemplatePartial>,
     pub location: super::location::Location,
 }
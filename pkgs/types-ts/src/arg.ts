@@ -3,6 +3,14 @@ export interface Arg {
   description: string;
   kind: ArgKind;
   required: boolean;
+  /** Handlebars expression computing the value from other arguments. */
+  derived?: string | undefined;
 }
 
-export type ArgKind = "any" | "boolean" | "string" | "number";
+export type ArgKind =
+  | "any"
+  | "boolean"
+  | "string"
+  | "number"
+  | "array"
+  | "object";
@@ -7,10 +7,20 @@ export interface OutputFile {
   lang?: string | undefined;
   /** Rendered file content. */
   content: string;
+  /** Set when content is base64-encoded binary data. */
+  encoding?: "base64" | undefined;
+  /** Set when the content patches the existing file at `path` instead of replacing it. */
+  patch?: import("./template.js").TemplatePatch | undefined;
+  /** Set when the content is deep-merged into the existing document at `path`. */
+  merge?: import("./template.js").TemplateMerge | undefined;
+  /** Rendered post-generation commands to run in the output directory. */
+  hooks?: Array<string> | undefined;
 }
 
 export interface OutputTree {
   files: Array<OutputFile>;
+  /** Rendered post-generation commands to run in the output directory. */
+  hooks?: Array<string> | undefined;
 }
 
 export type Output = OutputTree | OutputFile;
@@ -2,8 +2,8 @@
 
 /** A single-file template node. */
 export interface TemplateFile {
-  /** Discriminator for unions. */
-  kind: "file";
+  /** Discriminator for unions; patches modify an existing file instead of writing one. */
+  kind: "file" | "patch";
   id: string;
   name: string;
   description: string;
@@ -14,9 +14,57 @@ export interface TemplateFile {
   content: string;
   /** Optional relative path to use when writing to disk. */
   path?: string | undefined;
+  /** Content is written verbatim, without Handlebars rendering. */
+  raw?: boolean | undefined;
+  /** Path of a file copied byte-for-byte instead of the content. */
+  asset?: string | undefined;
+  /** Handlebars expression; in trees the file is only rendered when it is truthy. */
+  condition?: string | undefined;
+  /** Repeats the file once per element of an array arg when rendered in a tree. */
+  each?: TemplateEach | undefined;
+  /** How a patch template applies its content to the existing file. */
+  patch?: TemplatePatch | undefined;
+  /** Deep-merges the content into an existing JSON, TOML or YAML file. */
+  merge?: TemplateMerge | undefined;
+  /** Handlebars shell commands run in the output directory after generation. */
+  hooks: Array<string>;
   location: import("./location.js").Location;
 }
 
+/** Repetition of a file over the elements of an array arg. */
+export interface TemplateEach {
+  /** Name of the array arg. */
+  arg: string;
+  /** Name the current element is bound to in the path and content. */
+  binding: string;
+}
+
+/** An idempotent modification of an existing file. */
+export interface TemplatePatch {
+  op: TemplatePatchOp;
+  /** Regex matching the line the content is inserted before or after. */
+  anchor?: string | undefined;
+  /** Marker line opening the region replaced by `between`. */
+  start?: string | undefined;
+  /** Marker line closing the region replaced by `between`. */
+  end?: string | undefined;
+}
+
+export type TemplatePatchOp =
+  | "append"
+  | "prepend"
+  | "before"
+  | "after"
+  | "between";
+
+/** A deep merge of a generated document into the existing one. */
+export interface TemplateMerge {
+  /** How arrays present in both documents are combined. */
+  arrays: TemplateMergeArrays;
+}
+
+export type TemplateMergeArrays = "append-unique" | "replace";
+
 /** A tree of template files grouped under a single heading. */
 export interface TemplateTree {
   /** Discriminator for unions. */
@@ -26,16 +74,42 @@ export interface TemplateTree {
   /** Prose between the tree heading and the first file. */
   description: string;
   files: Array<Template>;
+  /** Other catalog templates whose files are added to the tree. */
+  includes: Array<TemplateInclude>;
+  /** Handlebars shell commands run in the output directory after generation. */
+  hooks: Array<string>;
+  location: import("./location.js").Location;
+}
+
+/** A reference from a tree to another catalog template, resolved by the catalog. */
+export interface TemplateInclude {
+  /** Id of the included file or tree template. */
+  id: string;
+  /** Directory the included files are placed under, relative to the tree root. */
+  path?: string | undefined;
+  /** Included template args mapped to the including tree args they take values from. */
+  args: Record<string, string>;
   location: import("./location.js").Location;
 }
 
 /** Union of templates. */
 export type Template = TemplateTree | TemplateFile;
 
+/** A reusable Handlebars partial, included from templates with `{{> name}}`. */
+export interface TemplatePartial {
+  name: string;
+  description: string;
+  /** Raw partial content. */
+  content: string;
+  location: import("./location.js").Location;
+}
+
 /** A collection of top-level templates parsed from a single markdown file. */
 export interface TemplateCollection {
   name: string;
   description: string;
   templates: Array<Template>;
+  /** Partials declared alongside the templates. */
+  partials: Array<TemplatePartial>;
   location: import("./location.js").Location;
 }